@@ -0,0 +1,14 @@
+//! Serve the fake people-search site used by the end-to-end tests, e.g. to
+//! try a connector script against it. Prints the base URL.
+
+use clap::Parser;
+
+#[path = "../tests/mock_site/mod.rs"]
+mod mock_site;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let base = mock_site::spawn(mock_site::Args::parse()).await?;
+    println!("{base}");
+    std::future::pending().await
+}
@@ -1,28 +1,37 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
-use reqwest::Client;
 use serde::Deserialize;
 
+use super::http::{HttpClient, HttpRequest, HttpService};
 use super::{
-    BrokerConnector, ConnectorCapabilities, DeletionStatusCheck, DeletionSubmission, FoundRecord,
-    PersonQuery,
+    BrokerConnector, ConnectorCapabilities, DeletionMethod, DeletionStatusCheck,
+    DeletionSubmission, FoundRecord, ManualStep, ManualStepKind, PersonQuery, QueryField,
 };
+use crate::error::AppError;
+
+const OPTOUT_URL: &str = "https://www.beenverified.com/app/optout/search";
+const SEARCH_API_URL: &str = "https://www.beenverified.com/svc/optout/search/optouts";
+const DOMAIN: &str = "beenverified.com";
 
-/// BeenVerified opt-out search connector (scan-only).
+/// BeenVerified opt-out search connector.
 ///
 /// Uses the public opt-out search endpoints to find records.
-/// Deletion is not yet supported — BeenVerified's opt-out submission
-/// requires CAPTCHA interaction that cannot be automated here.
+/// BeenVerified's opt-out submission requires CAPTCHA interaction that
+/// cannot be automated here, so deletion is handed to the user as a series
+/// of manual steps.
 pub struct BeenVerifiedBroker {
-    client: Client,
+    client: Arc<dyn HttpClient>,
 }
 
 impl BeenVerifiedBroker {
-    pub fn new() -> anyhow::Result<Self> {
-        let client = Client::builder()
-            .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-            .timeout(std::time::Duration::from_secs(30))
-            .build()?;
-        Ok(Self { client })
+    pub fn new(http: &HttpService) -> anyhow::Result<Self> {
+        let context = http.context("beenverified", &[DOMAIN.to_string()])?;
+        Ok(Self::with_client(context.http))
+    }
+
+    pub fn with_client(client: Arc<dyn HttpClient>) -> Self {
+        Self { client }
     }
 }
 
@@ -134,6 +143,38 @@ fn bv_record_to_found_records(rec: &BvRecord) -> Vec<FoundRecord> {
     out
 }
 
+/// Collect the values the user will need to paste into the opt-out form.
+/// Found records take precedence over the query, which may be sparse when
+/// deleting from stored records.
+fn optout_prefill(query: &PersonQuery, records: &[FoundRecord]) -> Vec<(String, String)> {
+    let mut prefill = Vec::new();
+    let record_value = |data_type: &str| {
+        records
+            .iter()
+            .find(|r| r.data_type == data_type)
+            .map(|r| r.data_value.clone())
+    };
+
+    let query_name = format!("{} {}", query.first_name, query.last_name);
+    let name = record_value("name").or_else(|| {
+        let trimmed = query_name.trim();
+        (!trimmed.is_empty()).then(|| trimmed.to_string())
+    });
+    if let Some(name) = name {
+        prefill.push(("Name".to_string(), name));
+    }
+    if let Some(state) = query.state.clone().filter(|s| !s.is_empty()) {
+        prefill.push(("State".to_string(), state));
+    }
+    if let Some(address) = record_value("address") {
+        prefill.push(("Address".to_string(), address));
+    }
+    if let Some(email) = query.email.clone().or_else(|| record_value("email")) {
+        prefill.push(("Email".to_string(), email));
+    }
+    prefill
+}
+
 // ---------------------------------------------------------------------------
 // BrokerConnector implementation
 // ---------------------------------------------------------------------------
@@ -151,37 +192,38 @@ impl BrokerConnector for BeenVerifiedBroker {
     fn capabilities(&self) -> ConnectorCapabilities {
         ConnectorCapabilities {
             can_scan: true,
-            can_delete: false,
+            can_delete: true,
             can_check_status: false,
+            required_fields: vec![
+                QueryField::FirstName,
+                QueryField::LastName,
+                QueryField::State,
+            ],
+            optional_fields: vec![QueryField::Email],
+            data_types: ["name", "age", "address", "relatives"]
+                .map(String::from)
+                .to_vec(),
+            deletion_method: Some(DeletionMethod::Form),
+            needs_verification: true,
+            batch: false,
+            processing_days: Some(1),
         }
     }
 
     async fn scan(&self, query: &PersonQuery) -> anyhow::Result<Vec<FoundRecord>> {
-        let state = query
-            .state
-            .as_deref()
-            .filter(|s| !s.is_empty())
-            .ok_or_else(|| {
-                anyhow::anyhow!("BeenVerified requires a state abbreviation (e.g. --state NY)")
-            })?;
+        self.capabilities().validate(self.name(), query)?;
+        let params = [
+            ("firstName", query.first_name.as_str()),
+            ("lastName", query.last_name.as_str()),
+            ("state", query.state.as_deref().unwrap_or_default()),
+        ];
 
         // Attempt 1: JSON API endpoint
-        let resp = self
-            .client
-            .get("https://www.beenverified.com/svc/optout/search/optouts")
-            .query(&[
-                ("firstName", query.first_name.as_str()),
-                ("lastName", query.last_name.as_str()),
-                ("state", state),
-            ])
-            .header("Accept", "application/json")
-            .send()
-            .await;
-
-        match resp {
-            Ok(r) if r.status().is_success() => {
-                let body = r.text().await.unwrap_or_default();
-                if let Ok(parsed) = serde_json::from_str::<BvSearchResponse>(&body) {
+        let request = HttpRequest::get_with_query(SEARCH_API_URL, &params)?
+            .header("Accept", "application/json");
+        match self.client.send(request).await {
+            Ok(r) if r.is_success() => {
+                if let Ok(parsed) = serde_json::from_str::<BvSearchResponse>(&r.body) {
                     let records: Vec<FoundRecord> = parsed
                         .into_records()
                         .iter()
@@ -196,7 +238,7 @@ impl BrokerConnector for BeenVerifiedBroker {
             Ok(r) => {
                 tracing::debug!(
                     "JSON API returned status {}, trying HTML fallback",
-                    r.status()
+                    r.status
                 );
             }
             Err(e) => {
@@ -205,26 +247,16 @@ impl BrokerConnector for BeenVerifiedBroker {
         }
 
         // Attempt 2: HTML fallback (stub — logs a warning, returns empty)
-        let html_resp = self
-            .client
-            .get("https://www.beenverified.com/app/optout/search")
-            .query(&[
-                ("firstName", query.first_name.as_str()),
-                ("lastName", query.last_name.as_str()),
-                ("state", state),
-            ])
-            .send()
-            .await;
-
-        match html_resp {
-            Ok(r) if r.status().is_success() => {
+        let request = HttpRequest::get_with_query(OPTOUT_URL, &params)?;
+        match self.client.send(request).await {
+            Ok(r) if r.is_success() => {
                 tracing::warn!(
                     "HTML fallback received a response but structured HTML parsing is not yet implemented. \
-                     Install the `scraper` crate and add parsing logic to extract records from the HTML page."
+                     Add parsing logic with the `scraper` crate to extract records from the HTML page."
                 );
             }
             Ok(r) => {
-                tracing::debug!("HTML fallback returned status {}", r.status());
+                tracing::debug!("HTML fallback returned status {}", r.status);
             }
             Err(e) => {
                 tracing::debug!("HTML fallback request failed: {e}");
@@ -236,23 +268,60 @@ impl BrokerConnector for BeenVerifiedBroker {
 
     async fn request_deletion(
         &self,
-        _query: &PersonQuery,
-        _records: &[FoundRecord],
+        query: &PersonQuery,
+        records: &[FoundRecord],
     ) -> anyhow::Result<DeletionSubmission> {
-        anyhow::bail!(
-            "BeenVerified deletion is not yet supported — the opt-out form requires CAPTCHA \
-             interaction that cannot be automated without browser automation"
-        )
+        let prefill = optout_prefill(query, records);
+        let manual_steps = vec![
+            ManualStep {
+                key: "search".into(),
+                kind: ManualStepKind::OpenUrl,
+                instructions: "Open the BeenVerified opt-out page, search for your name and \
+                               select the listing that matches you"
+                    .into(),
+                url: Some(OPTOUT_URL.into()),
+                prefill: prefill.clone(),
+            },
+            ManualStep {
+                key: "captcha".into(),
+                kind: ManualStepKind::SolveCaptcha,
+                instructions: "Enter your email address, solve the CAPTCHA and submit the \
+                               opt-out form"
+                    .into(),
+                url: Some(OPTOUT_URL.into()),
+                prefill: prefill
+                    .iter()
+                    .filter(|(label, _)| label == "Email")
+                    .cloned()
+                    .collect(),
+            },
+            ManualStep {
+                key: "verify-email".into(),
+                kind: ManualStepKind::EmailLink,
+                instructions: "Click the verification link in the email BeenVerified sends you"
+                    .into(),
+                url: None,
+                prefill: vec![],
+            },
+        ];
+
+        Ok(DeletionSubmission {
+            external_ref: format!("BV-LOCAL-{}", uuid::Uuid::new_v4()),
+            message: Some("BeenVerified opt-out requires manual steps".into()),
+            manual_steps,
+        })
     }
 
     async fn check_deletion_status(
         &self,
         _external_ref: &str,
     ) -> anyhow::Result<DeletionStatusCheck> {
-        anyhow::bail!(
+        Err(AppError::Unsupported(
             "BeenVerified deletion status checking is not yet supported — \
-             deletion must be implemented first"
+             BeenVerified does not expose opt-out progress"
+                .to_string(),
         )
+        .into())
     }
 }
 
@@ -263,6 +332,75 @@ impl BrokerConnector for BeenVerifiedBroker {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::broker::http::{HttpService, test_cassette};
+
+    fn cassette_broker(name: &str) -> BeenVerifiedBroker {
+        BeenVerifiedBroker::with_client(test_cassette(name, || {
+            HttpService::default()
+                .client("beenverified", &[DOMAIN.to_string()])
+                .unwrap()
+        }))
+    }
+
+    fn jane_smith() -> PersonQuery {
+        PersonQuery {
+            first_name: "Jane".into(),
+            last_name: "Smith".into(),
+            email: Some("jane@example.com".into()),
+            phone: None,
+            city: None,
+            state: Some("NY".into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_json_api() {
+        let broker = cassette_broker("beenverified-json");
+        let found = broker.scan(&jane_smith()).await.unwrap();
+        // name, age, address, relatives + name, age, city/state address
+        assert_eq!(found.len(), 7);
+        assert_eq!(found[0].data_value, "Jane Smith");
+        assert_eq!(
+            found[0].profile_url.as_deref(),
+            Some("https://www.beenverified.com/people/jane-smith/ny/brooklyn/")
+        );
+        assert_eq!(found[6].data_value, "Albany, NY");
+    }
+
+    #[tokio::test]
+    async fn test_scan_falls_back_to_html() {
+        let broker = cassette_broker("beenverified-html-fallback");
+        let found = broker.scan(&jane_smith()).await.unwrap();
+        assert!(found.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_full_flow_offline() {
+        let broker = cassette_broker("beenverified-json");
+        let query = jane_smith();
+        let found = broker.scan(&query).await.unwrap();
+
+        let submission = broker.request_deletion(&query, &found).await.unwrap();
+        assert!(submission.external_ref.starts_with("BV-LOCAL-"));
+        let keys: Vec<_> = submission
+            .manual_steps
+            .iter()
+            .map(|s| s.key.as_str())
+            .collect();
+        assert_eq!(keys, ["search", "captcha", "verify-email"]);
+        assert!(
+            submission.manual_steps[1]
+                .prefill
+                .contains(&("Email".to_string(), "jane@example.com".to_string()))
+        );
+
+        assert!(
+            broker
+                .check_deletion_status(&submission.external_ref)
+                .await
+                .is_err()
+        );
+    }
 
     fn full_record() -> BvRecord {
         BvRecord {
@@ -331,6 +469,24 @@ mod tests {
         assert!(found.is_empty()); // no name, no age, no addresses, no relatives
     }
 
+    #[test]
+    fn test_optout_prefill_prefers_records() {
+        let query = PersonQuery {
+            first_name: String::new(),
+            last_name: String::new(),
+            email: None,
+            phone: None,
+            city: None,
+            state: Some("NY".into()),
+        };
+        let records = bv_record_to_found_records(&full_record());
+        let prefill = optout_prefill(&query, &records);
+        assert_eq!(prefill[0], ("Name".to_string(), "Jane Smith".to_string()));
+        assert_eq!(prefill[1], ("State".to_string(), "NY".to_string()));
+        assert_eq!(prefill[2].0, "Address");
+        assert!(!prefill.iter().any(|(label, _)| label == "Email"));
+    }
+
     #[test]
     fn test_city_state_fallback_address() {
         let rec = BvRecord {
@@ -0,0 +1,298 @@
+//! Conformance checks every `BrokerConnector` must pass.
+//!
+//! [`check`] drives a connector through scan, deletion and status and
+//! asserts that:
+//!
+//! - each operation its capabilities leave out fails with a typed
+//!   `AppError` instead of succeeding or panicking;
+//! - scan results only use `KNOWN_DATA_TYPES`, and only those the
+//!   capabilities list when they list any;
+//! - leaving out a field in `required_fields` fails with
+//!   `AppError::MissingField` naming that field;
+//! - the reference from `request_deletion` is accepted by
+//!   `check_deletion_status` and yields a known status;
+//! - a declared workflow is valid and belongs to a connector that can
+//!   delete; without one, `run_step` fails with a typed `AppError`;
+//! - no call outlives the spec's deadline.
+//!
+//! A new connector gets one test at the bottom of this file.
+
+use std::future::Future;
+use std::time::Duration;
+
+use super::{BrokerConnector, KNOWN_DATA_TYPES, PersonQuery, QueryField};
+use crate::error::AppError;
+
+const STATUSES: &[&str] = &[
+    "pending",
+    "awaiting_user",
+    "submitted",
+    "in_progress",
+    "completed",
+    "not_found",
+    "failed",
+    "rejected",
+];
+
+pub struct Spec {
+    /// A query the connector can search with.
+    pub query: PersonQuery,
+    pub deadline: Duration,
+}
+
+impl Spec {
+    pub fn new(query: PersonQuery) -> Self {
+        Self {
+            query,
+            deadline: Duration::from_secs(10),
+        }
+    }
+}
+
+pub fn jane_doe() -> PersonQuery {
+    PersonQuery {
+        first_name: "Jane".into(),
+        last_name: "Doe".into(),
+        email: Some("jane@example.com".into()),
+        phone: Some("555-0100".into()),
+        city: Some("Albany".into()),
+        state: Some("NY".into()),
+    }
+}
+
+fn without(query: &PersonQuery, field: QueryField) -> PersonQuery {
+    let mut q = query.clone();
+    match field {
+        QueryField::FirstName => q.first_name.clear(),
+        QueryField::LastName => q.last_name.clear(),
+        QueryField::Email => q.email = None,
+        QueryField::Phone => q.phone = None,
+        QueryField::City => q.city = None,
+        QueryField::State => q.state = None,
+    }
+    q
+}
+
+async fn within<T>(spec: &Spec, what: &str, fut: impl Future<Output = T>) -> T {
+    tokio::time::timeout(spec.deadline, fut)
+        .await
+        .unwrap_or_else(|_| panic!("{what} did not finish within {:?}", spec.deadline))
+}
+
+fn assert_typed(id: &str, what: &str, err: &anyhow::Error) {
+    assert!(
+        err.downcast_ref::<AppError>().is_some(),
+        "{id}: {what} failed with an untyped error: {err:#}"
+    );
+}
+
+pub async fn check(connector: &dyn BrokerConnector, spec: &Spec) {
+    let id = connector.id();
+    assert!(!id.is_empty(), "connector ID is empty");
+    assert!(!connector.name().is_empty(), "{id}: name is empty");
+    let caps = connector.capabilities();
+
+    let scan = within(spec, "scan", connector.scan(&spec.query)).await;
+    let records = match (caps.can_scan, scan) {
+        (true, Ok(records)) => records,
+        (true, Err(e)) => panic!("{id}: scan failed: {e:#}"),
+        (false, Ok(_)) => panic!("{id}: scan succeeded without can_scan"),
+        (false, Err(e)) => {
+            assert_typed(id, "unsupported scan", &e);
+            vec![]
+        }
+    };
+    for record in &records {
+        assert!(
+            KNOWN_DATA_TYPES.contains(&record.data_type.as_str()),
+            "{id}: unknown data type '{}'",
+            record.data_type
+        );
+        assert!(!record.data_value.is_empty(), "{id}: empty data value");
+        assert!(
+            caps.data_types.is_empty() || caps.data_types.contains(&record.data_type),
+            "{id}: data type '{}' missing from capabilities",
+            record.data_type
+        );
+    }
+    for data_type in &caps.data_types {
+        assert!(
+            KNOWN_DATA_TYPES.contains(&data_type.as_str()),
+            "{id}: capabilities list unknown data type '{data_type}'"
+        );
+    }
+    for field in &caps.required_fields {
+        assert!(
+            !caps.optional_fields.contains(field),
+            "{id}: {} is both required and optional",
+            field.as_str()
+        );
+    }
+
+    if caps.can_scan {
+        for &field in &caps.required_fields {
+            let name = field.as_str();
+            let query = without(&spec.query, field);
+            let err = within(spec, "scan", connector.scan(&query))
+                .await
+                .expect_err(&format!("{id}: scan succeeded without {name}"));
+            match err.downcast_ref::<AppError>() {
+                Some(AppError::MissingField { field: f, .. }) => assert_eq!(f, name),
+                _ => panic!("{id}: missing {name} gave {err:#}, not AppError::MissingField"),
+            }
+        }
+    }
+
+    let submission = within(
+        spec,
+        "request_deletion",
+        connector.request_deletion(&spec.query, &records),
+    )
+    .await;
+    let external_ref = match (caps.can_delete, submission) {
+        (true, Ok(sub)) => {
+            assert!(!sub.external_ref.is_empty(), "{id}: empty external_ref");
+            let mut keys: Vec<_> = sub.manual_steps.iter().map(|s| &s.key).collect();
+            keys.sort();
+            keys.dedup();
+            assert_eq!(
+                keys.len(),
+                sub.manual_steps.len(),
+                "{id}: duplicate manual step keys"
+            );
+            sub.external_ref
+        }
+        (true, Err(e)) => panic!("{id}: request_deletion failed: {e:#}"),
+        (false, Ok(_)) => panic!("{id}: request_deletion succeeded without can_delete"),
+        (false, Err(e)) => {
+            assert_typed(id, "unsupported request_deletion", &e);
+            format!("{id}-conformance-ref")
+        }
+    };
+
+    let status = within(
+        spec,
+        "check_deletion_status",
+        connector.check_deletion_status(&external_ref),
+    )
+    .await;
+    match (caps.can_check_status, status) {
+        (true, Ok(check)) => assert!(
+            STATUSES.contains(&check.status.as_str()),
+            "{id}: unknown status '{}'",
+            check.status
+        ),
+        (true, Err(e)) => panic!("{id}: check_deletion_status({external_ref}) failed: {e:#}"),
+        (false, Ok(_)) => panic!("{id}: check_deletion_status succeeded without can_check_status"),
+        (false, Err(e)) => assert_typed(id, "unsupported check_deletion_status", &e),
+    }
+
+    match connector.workflow() {
+        Some(workflow) => {
+            assert!(caps.can_delete, "{id}: workflow without can_delete");
+            if let Err(e) = workflow.validate() {
+                panic!("{id}: invalid workflow: {e}");
+            }
+        }
+        None => {
+            let data = serde_json::Map::new();
+            let err = within(
+                spec,
+                "run_step",
+                connector.run_step(&external_ref, "submit", &data),
+            )
+            .await
+            .expect_err(&format!("{id}: run_step succeeded without a workflow"));
+            assert_typed(id, "unsupported run_step", &err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::broker::beenverified::BeenVerifiedBroker;
+    use crate::broker::dummy::DummyBroker;
+    use crate::broker::email::EmailBroker;
+    use crate::broker::http::{HttpService, Replayer};
+    use crate::broker::postal::PostalBroker;
+    use crate::broker::script::{Limits, ScriptConnector};
+
+    #[tokio::test]
+    async fn test_dummy_conforms() {
+        check(&DummyBroker::default(), &Spec::new(jane_doe())).await;
+    }
+
+    #[tokio::test]
+    async fn test_beenverified_conforms() {
+        let cassette = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/cassettes/beenverified-json.json"
+        );
+        let broker = BeenVerifiedBroker::with_client(Arc::new(Replayer::load(cassette).unwrap()));
+        let query = PersonQuery {
+            first_name: "Jane".into(),
+            last_name: "Smith".into(),
+            ..jane_doe()
+        };
+        assert!(
+            broker
+                .capabilities()
+                .required_fields
+                .contains(&QueryField::State)
+        );
+        check(&broker, &Spec::new(query)).await;
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_email_conforms() {
+        let broker = EmailBroker::new(
+            "mailonly",
+            "Mail Only",
+            "privacy@example.com",
+            None,
+            String::new(),
+        );
+        check(&broker, &Spec::new(jane_doe())).await;
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_postal_conforms() {
+        let broker = PostalBroker::new(
+            "postonly",
+            "Post Only",
+            "1 Main St\nAlbany, NY",
+            None,
+            String::new(),
+            std::env::temp_dir(),
+        );
+        check(&broker, &Spec::new(jane_doe())).await;
+    }
+
+    #[tokio::test]
+    async fn test_scan_only_script_conforms() {
+        let source = r#"
+            fn scan(query) {
+                [#{ data_type: "name", data_value: query.first_name + " " + query.last_name }]
+            }
+        "#;
+        let limits = Limits {
+            max_operations: 100_000,
+            timeout: Duration::from_secs(5),
+            max_requests: 0,
+        };
+        let script = ScriptConnector::compile(
+            "scan-only",
+            None,
+            source,
+            vec![],
+            limits,
+            &HttpService::default(),
+        )
+        .await
+        .unwrap();
+        check(&script, &Spec::new(jane_doe())).await;
+    }
+}
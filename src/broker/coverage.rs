@@ -0,0 +1,196 @@
+//! What data-breaker can do for each known broker.
+//!
+//! The brokers table lists far more brokers than there are connectors. A
+//! broker without a connector for an operation is still covered by hand when
+//! the registry says where to look or how to ask: people-search sites with a
+//! website get a manual search task from `scan`, and an opt-out page,
+//! request methods, privacy email or postal address make removal possible.
+//! Other brokers (marketing, unknown) publish no listings to search.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{BrokerConnector, ManualStep, ManualStepKind, PersonQuery};
+use crate::db::Database;
+use crate::db::models::Broker;
+
+/// `step_key` of the task asking the user to search a broker's site.
+pub const MANUAL_SCAN_STEP: &str = "manual_scan";
+/// `external_ref` of manual scan tasks, which belong to no request.
+pub const MANUAL_SCAN_REF: &str = "scan";
+
+/// How an operation is covered for a broker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Automated,
+    Manual,
+    None,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Automated => "automated",
+            Level::Manual => "manual",
+            Level::None => "-",
+        }
+    }
+}
+
+/// Coverage of one broker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coverage {
+    pub scan: Level,
+    pub deletion: Level,
+    pub status: Level,
+}
+
+pub fn assess(broker: &Broker, connector: Option<&Arc<dyn BrokerConnector>>) -> Coverage {
+    let caps = connector.map(|c| c.capabilities()).unwrap_or_default();
+    let level = |automated: bool, manual: bool| match (automated, manual) {
+        (true, _) => Level::Automated,
+        (false, true) => Level::Manual,
+        _ => Level::None,
+    };
+    let deletion = level(
+        caps.can_delete,
+        broker.opt_out_url.is_some()
+            || !broker.request_methods.is_empty()
+            || broker.privacy_email.is_some()
+            || broker.postal_address.is_some(),
+    );
+    Coverage {
+        scan: level(caps.can_scan, search_url(broker).is_some()),
+        deletion,
+        // Without status checks the user confirms the removal with a re-scan.
+        status: level(caps.can_check_status, deletion != Level::None),
+    }
+}
+
+/// Every broker a scan could cover: the brokers table, plus connectors the
+/// registry does not know (plugins, local scripts), sorted by id.
+pub fn known_brokers(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+) -> anyhow::Result<Vec<Broker>> {
+    let mut brokers = db.list_brokers(None)?;
+    for (id, connector) in connectors {
+        if !brokers.iter().any(|b| &b.id == id) {
+            brokers.push(connector_broker(id, connector.as_ref()));
+        }
+    }
+    brokers.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(brokers)
+}
+
+/// The broker row for a connector the registry does not define.
+pub fn connector_broker(id: &str, connector: &dyn BrokerConnector) -> Broker {
+    let now = chrono::Utc::now().to_rfc3339();
+    Broker {
+        id: id.to_string(),
+        name: connector.name().to_string(),
+        website: None,
+        description: None,
+        category: None,
+        connector: Some(id.to_string()),
+        privacy_email: None,
+        postal_address: None,
+        script: None,
+        wasm_manifest: None,
+        registry_source: None,
+        registry_updated_at: None,
+        retired_at: None,
+        opt_out_url: None,
+        request_methods: vec![],
+        verification: vec![],
+        processing_days: None,
+        relisting_days: None,
+        jurisdictions: vec![],
+        parent_id: None,
+        shared_database: false,
+        created_at: now.clone(),
+        updated_at: now,
+    }
+}
+
+/// Where to search a broker by hand: its site, else its opt-out page. Only
+/// people-search sites show listings to look for.
+fn search_url(broker: &Broker) -> Option<&str> {
+    if broker.category.as_deref() != Some("people-search") {
+        return None;
+    }
+    broker.website.as_deref().or(broker.opt_out_url.as_deref())
+}
+
+/// The task asking the user to look for `query` on a broker that cannot be
+/// scanned automatically, if there is anywhere to look.
+pub fn manual_scan_step(broker: &Broker, query: &PersonQuery) -> Option<ManualStep> {
+    let url = search_url(broker)?;
+    let mut prefill = vec![
+        ("First name".to_string(), query.first_name.clone()),
+        ("Last name".to_string(), query.last_name.clone()),
+    ];
+    for (label, value) in [("City", &query.city), ("State", &query.state)] {
+        if let Some(value) = value {
+            prefill.push((label.to_string(), value.clone()));
+        }
+    }
+    Some(ManualStep {
+        key: MANUAL_SCAN_STEP.to_string(),
+        kind: ManualStepKind::OpenUrl,
+        instructions: format!(
+            "Search {} for a listing of you. If there is one, finish this task with --value <profile URL>; otherwise just mark it done.",
+            broker.name
+        ),
+        url: Some(url.to_string()),
+        prefill,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::dummy::DummyBroker;
+
+    #[test]
+    fn test_coverage_levels() {
+        let dummy: Arc<dyn BrokerConnector> = Arc::new(DummyBroker::default());
+        let mut broker = connector_broker("dummy", dummy.as_ref());
+        let all = |level| Coverage {
+            scan: level,
+            deletion: level,
+            status: level,
+        };
+        assert_eq!(assess(&broker, Some(&dummy)), all(Level::Automated));
+        assert_eq!(assess(&broker, None), all(Level::None));
+
+        broker.category = Some("people-search".into());
+        broker.privacy_email = Some("privacy@example.com".into());
+        assert_eq!(
+            assess(&broker, None),
+            Coverage {
+                scan: Level::None,
+                deletion: Level::Manual,
+                status: Level::Manual,
+            }
+        );
+        broker.opt_out_url = Some("https://example.com/optout".into());
+        assert_eq!(assess(&broker, None), all(Level::Manual));
+
+        let query = PersonQuery {
+            first_name: "Jane".into(),
+            last_name: "Doe".into(),
+            email: None,
+            phone: None,
+            city: Some("Austin".into()),
+            state: None,
+        };
+        let step = manual_scan_step(&broker, &query).unwrap();
+        assert_eq!(step.key, MANUAL_SCAN_STEP);
+        assert_eq!(step.url.as_deref(), Some("https://example.com/optout"));
+        assert_eq!(step.prefill.len(), 3);
+        broker.category = Some("marketing".into());
+        assert!(manual_scan_step(&broker, &query).is_none());
+        assert_eq!(assess(&broker, None).scan, Level::None);
+    }
+}
@@ -1,13 +1,178 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
 use super::{
-    BrokerConnector, ConnectorCapabilities, DeletionStatusCheck, DeletionSubmission, FoundRecord,
-    PersonQuery,
+    BrokerConnector, ConnectorCapabilities, DeletionMethod, DeletionStatusCheck,
+    DeletionSubmission, FoundRecord, PersonQuery, QueryField,
 };
+use crate::config::{DummyConfig, DummyScenario};
 
 /// A dummy broker connector for testing and demonstration.
 /// Returns fake data — useful for verifying the CLI pipeline works end-to-end.
-pub struct DummyBroker;
+///
+/// By default every scan finds the same records and every request stays
+/// `in_progress`. A [`Scenario`] makes it behave more like a real broker:
+/// requests progress and complete, calls fail, namesakes show up, removed
+/// records come back. Requests are kept in a small ledger file so the
+/// simulation carries over between runs.
+#[derive(Default)]
+pub struct DummyBroker {
+    scenario: Scenario,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    /// Time per status step; `None` keeps requests `in_progress` forever.
+    pub progress: Option<Duration>,
+    pub failure_rate: f64,
+    pub namesakes: u32,
+    /// How long a removed record stays gone; `None` means for good.
+    pub relist: Option<Duration>,
+    pub delay: Duration,
+    /// Where submitted requests are remembered.
+    pub ledger: Option<PathBuf>,
+}
+
+/// Environment variable naming extra presets, comma-separated.
+pub const SCENARIO_ENV: &str = "DATA_BREAKER_DUMMY_SCENARIO";
+
+impl Scenario {
+    /// Combine the configured presets, those named in `extra` (the value of
+    /// [`SCENARIO_ENV`]), and explicit settings (which win).
+    pub fn from_config(config: &DummyConfig, extra: Option<&str>) -> anyhow::Result<Self> {
+        let mut presets = config.scenarios.clone();
+        if let Some(names) = extra {
+            for name in names.split(',').filter(|n| !n.trim().is_empty()) {
+                presets.push(name.parse().map_err(anyhow::Error::msg)?);
+            }
+        }
+
+        let mut scenario = Scenario::default();
+        for preset in presets {
+            match preset {
+                DummyScenario::Lifecycle => {
+                    scenario.progress.get_or_insert(Duration::from_secs(60));
+                }
+                DummyScenario::Flaky => scenario.failure_rate = 0.3,
+                DummyScenario::Namesakes => scenario.namesakes = 2,
+                DummyScenario::Relisting => {
+                    scenario.progress.get_or_insert(Duration::from_secs(60));
+                    scenario.relist = Some(Duration::from_secs(300));
+                }
+                DummyScenario::Slow => scenario.delay = Duration::from_secs(3),
+            }
+        }
+        if let Some(secs) = config.progress_secs {
+            scenario.progress = Some(Duration::from_secs(secs));
+        }
+        if let Some(rate) = config.failure_rate {
+            scenario.failure_rate = rate;
+        }
+        if let Some(n) = config.namesakes {
+            scenario.namesakes = n;
+        }
+        if let Some(secs) = config.relist_secs {
+            scenario.relist = Some(Duration::from_secs(secs));
+        }
+        if let Some(ms) = config.delay_ms {
+            scenario.delay = Duration::from_millis(ms);
+        }
+        if scenario.progress.is_some() {
+            scenario.ledger = Some(
+                crate::config::project_dirs()?
+                    .data_dir()
+                    .join("dummy-broker.json"),
+            );
+        }
+        Ok(scenario)
+    }
+}
+
+impl DummyBroker {
+    pub fn new(scenario: Scenario) -> Self {
+        Self { scenario }
+    }
+
+    async fn simulate(&self, what: &str) -> anyhow::Result<()> {
+        if !self.scenario.delay.is_zero() {
+            tokio::time::sleep(self.scenario.delay).await;
+        }
+        if self.unlucky() {
+            anyhow::bail!("Dummy Broker {what} failed (simulated outage)");
+        }
+        Ok(())
+    }
+
+    fn unlucky(&self) -> bool {
+        // 53 random bits from a v4 UUID, as a number in [0, 1).
+        let roll = (uuid::Uuid::new_v4().as_u128() >> 75) as f64 / (1u64 << 53) as f64;
+        roll < self.scenario.failure_rate
+    }
+
+    fn load_ledger(&self) -> anyhow::Result<Vec<LedgerEntry>> {
+        match &self.scenario.ledger {
+            Some(path) if path.exists() => {
+                Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+            }
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn save_ledger(&self, entries: &[LedgerEntry]) -> anyhow::Result<()> {
+        if let Some(path) = &self.scenario.ledger {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, serde_json::to_string_pretty(entries)?)?;
+        }
+        Ok(())
+    }
+
+    /// When a request reaches its final status, if it progresses at all.
+    fn finished_at(&self, entry: &LedgerEntry) -> Option<DateTime<Utc>> {
+        let step = chrono::Duration::from_std(self.scenario.progress?).ok()?;
+        Some(entry.submitted_at + step * 2)
+    }
+
+    /// Whether a value is currently delisted by a completed request.
+    fn is_removed(&self, ledger: &[LedgerEntry], value: &str, now: DateTime<Utc>) -> bool {
+        ledger.iter().any(|entry| {
+            let Some(done) = self.finished_at(entry) else {
+                return false;
+            };
+            let relisted = self
+                .scenario
+                .relist
+                .and_then(|d| chrono::Duration::from_std(d).ok())
+                .is_some_and(|d| now >= done + d);
+            entry.outcome == "completed"
+                && now >= done
+                && !relisted
+                && entry.values.iter().any(|v| v == value)
+        })
+    }
+}
+
+/// A request the dummy broker has "received".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LedgerEntry {
+    external_ref: String,
+    submitted_at: DateTime<Utc>,
+    values: Vec<String>,
+    /// Final status: `completed` or `rejected`.
+    outcome: String,
+}
+
+const NAMESAKE_CITIES: &[(&str, &str)] = &[
+    ("Springfield", "IL"),
+    ("Portland", "OR"),
+    ("Columbus", "OH"),
+    ("Richmond", "VA"),
+];
 
 #[async_trait]
 impl BrokerConnector for DummyBroker {
@@ -24,10 +189,26 @@ impl BrokerConnector for DummyBroker {
             can_scan: true,
             can_delete: true,
             can_check_status: true,
+            required_fields: vec![QueryField::FirstName, QueryField::LastName],
+            optional_fields: vec![
+                QueryField::Email,
+                QueryField::Phone,
+                QueryField::City,
+                QueryField::State,
+            ],
+            data_types: ["name", "address", "email", "phone", "age"]
+                .map(String::from)
+                .to_vec(),
+            deletion_method: Some(DeletionMethod::Api),
+            needs_verification: false,
+            batch: true,
+            processing_days: Some(0),
         }
     }
 
     async fn scan(&self, query: &PersonQuery) -> anyhow::Result<Vec<FoundRecord>> {
+        self.capabilities().validate(self.name(), query)?;
+        self.simulate("search").await?;
         let full_name = format!("{} {}", query.first_name, query.last_name);
 
         let mut records = vec![
@@ -67,17 +248,62 @@ impl BrokerConnector for DummyBroker {
             });
         }
 
+        // Same name, different person.
+        for (i, (city, state)) in NAMESAKE_CITIES
+            .iter()
+            .cycle()
+            .take(self.scenario.namesakes as usize)
+            .enumerate()
+        {
+            let profile = format!("https://dummy-broker.example.com/profile/{}", 20000 + i);
+            records.push(FoundRecord {
+                data_type: "address".into(),
+                data_value: format!("{} Oak Ave, {city}, {state}", 400 + i * 17),
+                profile_url: Some(profile.clone()),
+                metadata: None,
+            });
+            records.push(FoundRecord {
+                data_type: "age".into(),
+                data_value: (23 + i * 19).to_string(),
+                profile_url: Some(profile),
+                metadata: None,
+            });
+        }
+
+        let ledger = self.load_ledger()?;
+        let now = Utc::now();
+        records.retain(|r| !self.is_removed(&ledger, &r.data_value, now));
         Ok(records)
     }
 
     async fn request_deletion(
         &self,
         _query: &PersonQuery,
-        _records: &[FoundRecord],
+        records: &[FoundRecord],
     ) -> anyhow::Result<DeletionSubmission> {
+        self.simulate("opt-out submission").await?;
+        let external_ref = format!("DUMMY-{}", uuid::Uuid::new_v4());
+
+        if self.scenario.progress.is_some() {
+            let mut ledger = self.load_ledger()?;
+            ledger.push(LedgerEntry {
+                external_ref: external_ref.clone(),
+                submitted_at: Utc::now(),
+                values: records.iter().map(|r| r.data_value.clone()).collect(),
+                outcome: if self.unlucky() {
+                    "rejected"
+                } else {
+                    "completed"
+                }
+                .into(),
+            });
+            self.save_ledger(&ledger)?;
+        }
+
         Ok(DeletionSubmission {
-            external_ref: format!("DUMMY-{}", uuid::Uuid::new_v4()),
+            external_ref,
             message: Some("Deletion request submitted to Dummy Broker".into()),
+            manual_steps: vec![],
         })
     }
 
@@ -85,11 +311,176 @@ impl BrokerConnector for DummyBroker {
         &self,
         external_ref: &str,
     ) -> anyhow::Result<DeletionStatusCheck> {
-        // Always returns in_progress for the dummy
-        Ok(DeletionStatusCheck {
-            status: "in_progress".into(),
-            completed_at: None,
-            message: Some(format!("Request {external_ref} is being processed")),
+        self.simulate("status check").await?;
+        let entry = self
+            .load_ledger()?
+            .into_iter()
+            .find(|e| e.external_ref == external_ref);
+        let (Some(entry), Some(step)) = (entry, self.scenario.progress) else {
+            // Without a scenario, requests stay in progress.
+            return Ok(DeletionStatusCheck {
+                status: "in_progress".into(),
+                completed_at: None,
+                message: Some(format!("Request {external_ref} is being processed")),
+            });
+        };
+
+        let elapsed = (Utc::now() - entry.submitted_at)
+            .to_std()
+            .unwrap_or_default();
+        let done = self.finished_at(&entry).filter(|_| elapsed >= step * 2);
+        Ok(match done {
+            Some(at) if entry.outcome == "completed" => DeletionStatusCheck {
+                status: "completed".into(),
+                completed_at: Some(at.to_rfc3339()),
+                message: Some(format!("Request {external_ref} completed")),
+            },
+            Some(_) => DeletionStatusCheck {
+                status: "rejected".into(),
+                completed_at: None,
+                message: Some("Dummy Broker could not verify your identity".into()),
+            },
+            None if elapsed >= step => DeletionStatusCheck {
+                status: "in_progress".into(),
+                completed_at: None,
+                message: Some(format!("Request {external_ref} is being processed")),
+            },
+            None => DeletionStatusCheck {
+                status: "submitted".into(),
+                completed_at: None,
+                message: Some(format!("Request {external_ref} is queued")),
+            },
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query() -> PersonQuery {
+        PersonQuery {
+            first_name: "Jane".into(),
+            last_name: "Doe".into(),
+            email: None,
+            phone: None,
+            city: Some("Albany".into()),
+            state: Some("NY".into()),
+        }
+    }
+
+    fn ledger_path() -> PathBuf {
+        std::env::temp_dir().join(format!("dummy-ledger-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_completes_and_relists() {
+        let ledger = ledger_path();
+        let broker = DummyBroker::new(Scenario {
+            progress: Some(Duration::from_secs(3600)),
+            relist: Some(Duration::from_secs(3600)),
+            ledger: Some(ledger.clone()),
+            ..Scenario::default()
+        });
+        // Move the request's submission back instead of waiting.
+        let submitted_ago = |minutes| {
+            let mut entries = broker.load_ledger().unwrap();
+            entries[0].submitted_at = Utc::now() - chrono::Duration::minutes(minutes);
+            broker.save_ledger(&entries).unwrap();
+        };
+
+        let records = broker.scan(&query()).await.unwrap();
+        assert_eq!(records.len(), 2);
+        let sub = broker.request_deletion(&query(), &records).await.unwrap();
+        let r = sub.external_ref.as_str();
+        assert_eq!(
+            broker.check_deletion_status(r).await.unwrap().status,
+            "submitted"
+        );
+        submitted_ago(90);
+        assert_eq!(
+            broker.check_deletion_status(r).await.unwrap().status,
+            "in_progress"
+        );
+        submitted_ago(150);
+        assert_eq!(
+            broker.check_deletion_status(r).await.unwrap().status,
+            "completed"
+        );
+
+        // Delisted until the relist interval passes.
+        assert!(broker.scan(&query()).await.unwrap().is_empty());
+        submitted_ago(200);
+        assert_eq!(broker.scan(&query()).await.unwrap().len(), 2);
+
+        std::fs::remove_file(ledger).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flaky_fails_and_rejects() {
+        let ledger = ledger_path();
+        let scenario = Scenario {
+            progress: Some(Duration::ZERO),
+            failure_rate: 1.0,
+            ledger: Some(ledger.clone()),
+            ..Scenario::default()
+        };
+        let broker = DummyBroker::new(scenario.clone());
+        assert!(broker.scan(&query()).await.is_err());
+        assert!(broker.request_deletion(&query(), &[]).await.is_err());
+
+        let calm = DummyBroker::new(Scenario {
+            failure_rate: 0.0,
+            ..scenario
+        });
+        calm.save_ledger(&[LedgerEntry {
+            external_ref: "DUMMY-x".into(),
+            submitted_at: Utc::now(),
+            values: vec![],
+            outcome: "rejected".into(),
+        }])
+        .unwrap();
+        let check = calm.check_deletion_status("DUMMY-x").await.unwrap();
+        assert_eq!(check.status, "rejected");
+
+        std::fs::remove_file(ledger).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_namesakes_share_the_name() {
+        let broker = DummyBroker::new(Scenario {
+            namesakes: 2,
+            ..Scenario::default()
+        });
+        let records = broker.scan(&query()).await.unwrap();
+        let profiles: std::collections::HashSet<_> = records
+            .iter()
+            .filter_map(|r| r.profile_url.clone())
+            .collect();
+        assert_eq!(profiles.len(), 3);
+        assert!(
+            records
+                .iter()
+                .any(|r| r.data_value.contains("Springfield, IL"))
+        );
+    }
+
+    #[test]
+    fn test_presets_and_overrides() {
+        let config = DummyConfig {
+            scenarios: vec![DummyScenario::Relisting, DummyScenario::Slow],
+            relist_secs: Some(10),
+            ..DummyConfig::default()
+        };
+        let scenario = Scenario::from_config(&config, None).unwrap();
+        assert_eq!(scenario.progress, Some(Duration::from_secs(60)));
+        assert_eq!(scenario.relist, Some(Duration::from_secs(10)));
+        assert_eq!(scenario.delay, Duration::from_secs(3));
+        assert!(scenario.ledger.is_some());
+
+        let scenario = Scenario::from_config(&config, Some("flaky, namesakes")).unwrap();
+        assert_eq!(scenario.failure_rate, 0.3);
+        assert_eq!(scenario.namesakes, 2);
+        assert!(Scenario::from_config(&config, Some("bogus")).is_err());
+    }
+}
@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::{Mailbox, Message};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+
+use super::{
+    BrokerConnector, ConnectorCapabilities, DeletionMethod, DeletionStatusCheck,
+    DeletionSubmission, FoundRecord, PersonQuery, QueryField,
+};
+use crate::config::{Config, SmtpConfig, SmtpTls};
+use crate::db::Database;
+use crate::error::AppError;
+
+/// Registry `connector` value for brokers handled by email.
+pub const EMAIL_CONNECTOR: &str = "email";
+
+/// Built-in request template. The first line is the subject.
+const DEFAULT_TEMPLATE: &str = include_str!("request.txt");
+
+const DEFAULT_SUBJECT: &str = "Request to delete my personal information";
+
+/// Deletion by email to a broker's privacy address.
+///
+/// One instance exists per registry broker whose connector is `email`.
+/// The request is rendered from a template and sent through the configured
+/// SMTP server; its Message-ID becomes the request's `external_ref` so
+/// replies can be matched later.
+pub struct EmailBroker {
+    id: String,
+    name: String,
+    to: String,
+    smtp: Option<SmtpConfig>,
+    template: String,
+}
+
+impl EmailBroker {
+    pub fn new(id: &str, name: &str, to: &str, smtp: Option<SmtpConfig>, template: String) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            to: to.to_string(),
+            smtp,
+            template,
+        }
+    }
+
+    fn render(&self, query: &PersonQuery, records: &[FoundRecord], from: &str) -> (String, String) {
+        let query_name = format!("{} {}", query.first_name, query.last_name);
+        let full_name = records
+            .iter()
+            .find(|r| r.data_type == "name")
+            .map(|r| r.data_value.clone())
+            .unwrap_or_else(|| query_name.trim().to_string());
+
+        let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        let rendered = render_template(
+            &self.template,
+            &[
+                ("broker_name", &self.name),
+                ("full_name", &full_name),
+                ("email", from),
+                ("records", &list_records(records)),
+                ("date", &date),
+            ],
+        );
+        split_subject(&rendered)
+    }
+}
+
+/// Bullet list of the records a request is about, for request templates.
+pub(super) fn list_records(records: &[FoundRecord]) -> String {
+    let mut listed = String::new();
+    for r in records {
+        listed.push_str(&format!("- {}: {}", r.data_type, r.data_value));
+        if let Some(url) = &r.profile_url {
+            listed.push_str(&format!(" ({url})"));
+        }
+        listed.push('\n');
+    }
+    if listed.is_empty() {
+        listed.push_str("- (no specific records; please search by my name and email)");
+    }
+    listed.trim_end().to_string()
+}
+
+/// Replace `{{key}}` placeholders. Unknown placeholders are left untouched.
+pub(super) fn render_template(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = template.to_string();
+    for (key, value) in vars {
+        out = out.replace(&format!("{{{{{key}}}}}"), value);
+    }
+    out
+}
+
+/// Split a leading `Subject:` line off a rendered template.
+fn split_subject(rendered: &str) -> (String, String) {
+    match rendered.split_once('\n') {
+        Some((first, rest)) if first.starts_with("Subject:") => (
+            first["Subject:".len()..].trim().to_string(),
+            rest.trim_start_matches('\n').to_string(),
+        ),
+        _ => (DEFAULT_SUBJECT.to_string(), rendered.to_string()),
+    }
+}
+
+fn smtp_transport(smtp: &SmtpConfig) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
+    let mut builder = match smtp.tls {
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host),
+        SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?,
+        SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp.host)?,
+    };
+    if let Some(port) = smtp.port {
+        builder = builder.port(port);
+    }
+    if let (Some(user), Some(pass)) = (&smtp.username, smtp.password()) {
+        builder = builder.credentials(Credentials::new(user.clone(), pass));
+    }
+    Ok(builder.build())
+}
+
+/// Message-IDs use the sender's domain rather than the local hostname,
+/// which would otherwise leak into every request.
+fn new_message_id(from: &str) -> String {
+    let domain = from.rsplit_once('@').map(|(_, d)| d).unwrap_or("localhost");
+    format!("<{}@{domain}>", uuid::Uuid::new_v4())
+}
+
+#[async_trait]
+impl BrokerConnector for EmailBroker {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> ConnectorCapabilities {
+        ConnectorCapabilities {
+            can_scan: false,
+            can_delete: self.smtp.is_some(),
+            can_check_status: false,
+            // The request is signed with the user's name.
+            required_fields: vec![QueryField::FirstName, QueryField::LastName],
+            deletion_method: Some(DeletionMethod::Email),
+            batch: true,
+            ..Default::default()
+        }
+    }
+
+    async fn scan(&self, _query: &PersonQuery) -> anyhow::Result<Vec<FoundRecord>> {
+        Err(AppError::Unsupported(format!(
+            "{} only accepts deletion requests by email",
+            self.name
+        ))
+        .into())
+    }
+
+    async fn request_deletion(
+        &self,
+        query: &PersonQuery,
+        records: &[FoundRecord],
+    ) -> anyhow::Result<DeletionSubmission> {
+        let smtp = self.smtp.as_ref().ok_or_else(|| {
+            AppError::Config("Email deletion requires an [smtp] section in config.toml".into())
+        })?;
+        if records.iter().all(|r| r.data_type != "name") {
+            self.capabilities().validate(&self.name, query)?;
+        }
+
+        let (subject, body) = self.render(query, records, &smtp.from);
+        let message_id = new_message_id(&smtp.from);
+        let message = Message::builder()
+            .from(Mailbox::new(smtp.from_name.clone(), smtp.from.parse()?))
+            .to(Mailbox::new(None, self.to.parse()?))
+            .subject(subject)
+            .message_id(Some(message_id.clone()))
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)?;
+
+        smtp_transport(smtp)?.send(message).await?;
+
+        Ok(DeletionSubmission {
+            external_ref: message_id,
+            message: Some(format!("Deletion request emailed to {}", self.to)),
+            manual_steps: vec![],
+        })
+    }
+
+    async fn check_deletion_status(
+        &self,
+        _external_ref: &str,
+    ) -> anyhow::Result<DeletionStatusCheck> {
+        Err(AppError::Unsupported(format!(
+            "{} status can only be learned from its email replies",
+            self.name
+        ))
+        .into())
+    }
+}
+
+/// The request template at `path`, or the built-in one. An unreadable
+/// template is warned about rather than failing every command.
+fn load_template(path: Option<&std::path::Path>) -> String {
+    let Some(path) = path else {
+        return DEFAULT_TEMPLATE.to_string();
+    };
+    std::fs::read_to_string(path).unwrap_or_else(|e| {
+        tracing::warn!(
+            "Cannot read email template {}: {e}; using the built-in one",
+            path.display()
+        );
+        DEFAULT_TEMPLATE.to_string()
+    })
+}
+
+/// Register an `EmailBroker` for every registry broker whose connector is
+/// `email`. Compiled-in connectors with the same ID take precedence.
+pub fn add_email_connectors(
+    connectors: &mut HashMap<String, Arc<dyn BrokerConnector>>,
+    db: &Database,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let template = load_template(config.email.template.as_deref());
+    if config.smtp.is_none() {
+        tracing::debug!("No [smtp] configuration; email brokers cannot receive requests");
+    }
+
+    for broker in db.list_brokers(None)? {
+        if broker.connector.as_deref() != Some(EMAIL_CONNECTOR)
+            || connectors.contains_key(&broker.id)
+        {
+            continue;
+        }
+        let Some(to) = &broker.privacy_email else {
+            tracing::warn!("Broker '{}' uses email but has no privacy email", broker.id);
+            continue;
+        };
+        connectors.insert(
+            broker.id.clone(),
+            Arc::new(EmailBroker::new(
+                &broker.id,
+                &broker.name,
+                to,
+                config.smtp.clone(),
+                template.clone(),
+            )),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Minimal SMTP sink accepting a single message and returning its DATA.
+    async fn smtp_sink(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+
+        let mut data = String::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let cmd = line.to_ascii_uppercase();
+            if cmd.starts_with("EHLO") || cmd.starts_with("HELO") {
+                write.write_all(b"250 sink\r\n").await.unwrap();
+            } else if cmd.starts_with("DATA") {
+                write
+                    .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                    .await
+                    .unwrap();
+                while let Some(body_line) = lines.next_line().await.unwrap() {
+                    if body_line == "." {
+                        break;
+                    }
+                    data.push_str(&body_line);
+                    data.push('\n');
+                }
+                write.write_all(b"250 OK queued\r\n").await.unwrap();
+            } else if cmd.starts_with("QUIT") {
+                write.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                write.write_all(b"250 OK\r\n").await.unwrap();
+            }
+        }
+        data
+    }
+
+    fn query() -> PersonQuery {
+        PersonQuery {
+            first_name: "Jane".into(),
+            last_name: "Doe".into(),
+            email: None,
+            phone: None,
+            city: None,
+            state: None,
+        }
+    }
+
+    #[test]
+    fn test_render_template_and_subject() {
+        let broker = EmailBroker::new(
+            "acme",
+            "Acme Data",
+            "privacy@acme.example",
+            None,
+            DEFAULT_TEMPLATE.to_string(),
+        );
+        let records = vec![FoundRecord {
+            data_type: "name".into(),
+            data_value: "Jane Doe".into(),
+            profile_url: Some("https://acme.example/p/1".into()),
+            metadata: None,
+        }];
+        let (subject, body) = broker.render(&query(), &records, "jane@example.com");
+        assert_eq!(subject, DEFAULT_SUBJECT);
+        assert!(body.starts_with("Hello Acme Data privacy team,"));
+        assert!(body.contains("My name: Jane Doe"));
+        assert!(body.contains("Contact email: jane@example.com"));
+        assert!(body.contains("- name: Jane Doe (https://acme.example/p/1)"));
+        assert!(!body.contains("{{"));
+    }
+
+    #[test]
+    fn test_unreadable_template_falls_back() {
+        let missing = std::env::temp_dir().join(format!("db-missing-{}.txt", uuid::Uuid::new_v4()));
+        assert_eq!(load_template(Some(&missing)), DEFAULT_TEMPLATE);
+        assert_eq!(load_template(None), DEFAULT_TEMPLATE);
+    }
+
+    #[test]
+    fn test_split_subject_without_header() {
+        let (subject, body) = split_subject("Just a body");
+        assert_eq!(subject, DEFAULT_SUBJECT);
+        assert_eq!(body, "Just a body");
+    }
+
+    #[test]
+    fn test_capabilities_follow_smtp_config() {
+        let broker = EmailBroker::new("acme", "Acme", "privacy@acme.example", None, String::new());
+        assert!(!broker.capabilities().can_delete);
+        assert!(!broker.capabilities().can_scan);
+    }
+
+    #[tokio::test]
+    async fn test_request_deletion_against_smtp_sink() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        let smtp = SmtpConfig {
+            host: "127.0.0.1".into(),
+            port: Some(port),
+            tls: SmtpTls::None,
+            username: None,
+            password: None,
+            from: "jane@example.com".into(),
+            from_name: Some("Jane Doe".into()),
+        };
+        let broker = EmailBroker::new(
+            "acme",
+            "Acme Data",
+            "privacy@acme.example",
+            Some(smtp),
+            DEFAULT_TEMPLATE.to_string(),
+        );
+
+        let submission = broker.request_deletion(&query(), &[]).await.unwrap();
+        assert!(submission.external_ref.starts_with('<'));
+        assert!(submission.external_ref.ends_with("@example.com>"));
+
+        let data = sink.await.unwrap();
+        assert!(data.contains(&format!("Message-ID: {}", submission.external_ref)));
+        assert!(data.contains("To: privacy@acme.example"));
+        assert!(data.contains("Subject: Request to delete my personal information"));
+        assert!(data.contains("Hello Acme Data privacy team,"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_reaches_email_broker_without_records() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        let db = Database::open_in_memory().unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        db.upsert_broker(&crate::db::models::Broker {
+            id: "acme".into(),
+            name: "Acme Data".into(),
+            website: None,
+            description: None,
+            category: None,
+            connector: Some(EMAIL_CONNECTOR.into()),
+            privacy_email: Some("privacy@acme.example".into()),
+            postal_address: None,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: None,
+            shared_database: false,
+            script: None,
+            wasm_manifest: None,
+            registry_source: None,
+            registry_updated_at: None,
+            retired_at: None,
+            created_at: now.clone(),
+            updated_at: now,
+        })
+        .unwrap();
+        let config = Config {
+            smtp: Some(SmtpConfig {
+                host: "127.0.0.1".into(),
+                port: Some(port),
+                tls: SmtpTls::None,
+                username: None,
+                password: None,
+                from: "jane@example.com".into(),
+                from_name: None,
+            }),
+            ..Default::default()
+        };
+        let mut connectors = HashMap::new();
+        add_email_connectors(&mut connectors, &db, &config).unwrap();
+
+        // Email brokers are never scanned, so there are no records to name.
+        crate::cli::delete::delete(&db, &connectors, &query(), false, Some("acme"), None, false)
+            .await
+            .unwrap();
+        assert!(sink.await.unwrap().contains("To: privacy@acme.example"));
+
+        let requests = db.list_deletion_requests(Some("acme")).unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].status, "submitted");
+        assert_eq!(requests[0].personal_record_id, None);
+    }
+}
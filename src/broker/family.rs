@@ -0,0 +1,271 @@
+//! Broker families.
+//!
+//! Many people-search brands are front ends to one database: a removal at
+//! the controlling broker takes the listing off every brand marked
+//! `shared_database` below it. `delete` submits once to the controller and
+//! files a linked request per brand (`covered_by`), which follows the
+//! controller's request until it completes. A completed removal is not
+//! trusted to have propagated: brands that can be scanned stay `submitted`
+//! until a re-scan of that brand no longer finds the records.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{BrokerConnector, FoundRecord};
+use crate::db::Database;
+use crate::db::models::DeletionRequest;
+
+/// Request statuses that are final.
+const SETTLED_STATUSES: &[&str] = &["completed", "not_found", "rejected", "failed"];
+
+/// The broker whose removal covers `broker_id`: the top of its chain of
+/// `shared_database` parents, or the broker itself.
+pub fn controller(db: &Database, broker_id: &str) -> anyhow::Result<String> {
+    let mut current = broker_id.to_string();
+    let mut seen = vec![current.clone()];
+    while let Some(broker) = db.get_broker(&current)?
+        && broker.shared_database
+        && let Some(parent) = broker.parent_id
+    {
+        // The registry refuses cycles, but a stale database might not.
+        if seen.contains(&parent) {
+            break;
+        }
+        seen.push(parent.clone());
+        current = parent;
+    }
+    Ok(current)
+}
+
+/// File linked requests for `brand`, one per record (or a single one), that
+/// ride on the controller's request `covering`.
+pub fn link(
+    db: &Database,
+    covering: &DeletionRequest,
+    brand: &str,
+    record_ids: &[String],
+) -> anyhow::Result<usize> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let record_ids: Vec<Option<&String>> = if record_ids.is_empty() {
+        vec![None]
+    } else {
+        record_ids.iter().map(Some).collect()
+    };
+    for record_id in &record_ids {
+        db.insert_deletion_request(&DeletionRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            broker_id: brand.to_string(),
+            personal_record_id: record_id.cloned(),
+            status: covering.status.clone(),
+            submitted_at: covering.submitted_at.clone(),
+            completed_at: None,
+            error_message: None,
+            external_ref: covering.external_ref.clone(),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            covered_by: Some(covering.id.clone()),
+        })?;
+    }
+    Ok(record_ids.len())
+}
+
+/// Bring linked requests in line with the requests covering them. Returns
+/// how many changed.
+pub fn sync(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+) -> anyhow::Result<usize> {
+    let mut changed = 0;
+    for mut req in db.list_deletion_requests(None)? {
+        if SETTLED_STATUSES.contains(&req.status.as_str()) {
+            continue;
+        }
+        let Some(covering) = req.covered_by.as_deref() else {
+            continue;
+        };
+        let Some(covering) = db.get_deletion_request(covering)? else {
+            continue;
+        };
+        let (status, message) = match covering.status.as_str() {
+            "completed" => {
+                let scannable = connectors
+                    .get(&req.broker_id)
+                    .is_some_and(|c| c.capabilities().can_scan);
+                if scannable {
+                    // Keep what a verification scan found, if it ran.
+                    let message = req.error_message.clone().unwrap_or_else(|| {
+                        format!(
+                            "Removed at {}; run `data-breaker scan --brokers {}` to verify",
+                            covering.broker_id, req.broker_id
+                        )
+                    });
+                    ("submitted", Some(message))
+                } else {
+                    ("completed", None)
+                }
+            }
+            status @ ("failed" | "rejected") => (
+                status,
+                Some(format!("Request at {} {status}", covering.broker_id)),
+            ),
+            status => (status, None),
+        };
+        if req.status == status && req.error_message == message {
+            continue;
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        req.status = status.to_string();
+        req.error_message = message;
+        if status == "completed" {
+            req.completed_at = Some(now.clone());
+        }
+        req.updated_at = now;
+        db.update_deletion_request(&req)?;
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+/// After scanning `broker_id`, complete its linked requests whose removal
+/// went through and whose records are no longer listed. Returns how many
+/// were verified.
+pub fn verify_scan(db: &Database, broker_id: &str, found: &[FoundRecord]) -> anyhow::Result<usize> {
+    let mut verified = 0;
+    for mut req in db.list_deletion_requests(Some(broker_id))? {
+        if SETTLED_STATUSES.contains(&req.status.as_str()) {
+            continue;
+        }
+        let Some(covering) = req.covered_by.as_deref() else {
+            continue;
+        };
+        if db
+            .get_deletion_request(covering)?
+            .is_none_or(|c| c.status != "completed")
+        {
+            continue;
+        }
+        let still_listed = match &req.personal_record_id {
+            Some(record_id) => db.get_personal_record(record_id)?.is_some_and(|record| {
+                found
+                    .iter()
+                    .any(|f| f.data_type == record.data_type && f.data_value == record.data_value)
+            }),
+            None => !found.is_empty(),
+        };
+        let now = chrono::Utc::now().to_rfc3339();
+        if still_listed {
+            req.error_message = Some(format!(
+                "Still listed on {broker_id} after the removal at its parent"
+            ));
+        } else {
+            req.status = "completed".to_string();
+            req.completed_at = Some(now.clone());
+            req.error_message = None;
+            verified += 1;
+        }
+        req.updated_at = now;
+        db.update_deletion_request(&req)?;
+    }
+    Ok(verified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::Broker;
+
+    fn broker(db: &Database, id: &str, parent: Option<&str>, shared_database: bool) {
+        let now = chrono::Utc::now().to_rfc3339();
+        db.upsert_broker(&Broker {
+            id: id.into(),
+            name: id.into(),
+            website: None,
+            description: None,
+            category: None,
+            connector: None,
+            privacy_email: None,
+            postal_address: None,
+            script: None,
+            wasm_manifest: None,
+            registry_source: None,
+            registry_updated_at: None,
+            retired_at: None,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: parent.map(Into::into),
+            shared_database,
+            created_at: now.clone(),
+            updated_at: now,
+        })
+        .unwrap();
+    }
+
+    fn request(db: &Database, broker_id: &str) -> DeletionRequest {
+        db.list_deletion_requests(Some(broker_id))
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn test_family_requests_follow_the_controller() {
+        let db = Database::open_in_memory().unwrap();
+        broker(&db, "people-inc", None, false);
+        broker(&db, "finder", Some("people-inc"), true);
+        broker(&db, "finder-mobile", Some("finder"), true);
+        broker(&db, "owned", Some("people-inc"), false);
+
+        assert_eq!(controller(&db, "finder-mobile").unwrap(), "people-inc");
+        assert_eq!(controller(&db, "people-inc").unwrap(), "people-inc");
+        assert_eq!(controller(&db, "owned").unwrap(), "owned");
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut covering = DeletionRequest {
+            id: "del-1".into(),
+            broker_id: "people-inc".into(),
+            personal_record_id: None,
+            status: "submitted".into(),
+            submitted_at: Some(now.clone()),
+            completed_at: None,
+            error_message: None,
+            external_ref: Some("R1".into()),
+            created_at: now.clone(),
+            updated_at: now,
+            covered_by: None,
+        };
+        db.insert_deletion_request(&covering).unwrap();
+        assert_eq!(link(&db, &covering, "finder", &[]).unwrap(), 1);
+        link(&db, &covering, "finder-mobile", &[]).unwrap();
+        assert_eq!(request(&db, "finder").status, "submitted");
+        assert_eq!(request(&db, "finder").covered_by.as_deref(), Some("del-1"));
+
+        // Nothing to verify until the controller's removal went through.
+        let listed = FoundRecord {
+            data_type: "name".into(),
+            data_value: "Jane Doe".into(),
+            profile_url: None,
+            metadata: None,
+        };
+        assert_eq!(verify_scan(&db, "finder", &[]).unwrap(), 0);
+        assert_eq!(request(&db, "finder").status, "submitted");
+
+        covering.status = "completed".into();
+        db.update_deletion_request(&covering).unwrap();
+        assert_eq!(verify_scan(&db, "finder", &[listed]).unwrap(), 0);
+        assert!(
+            request(&db, "finder")
+                .error_message
+                .unwrap()
+                .contains("Still listed")
+        );
+        assert_eq!(verify_scan(&db, "finder", &[]).unwrap(), 1);
+        assert_eq!(request(&db, "finder").status, "completed");
+
+        // Without a connector to re-scan with, the controller's word counts.
+        assert_eq!(sync(&db, &HashMap::new()).unwrap(), 1);
+        assert_eq!(request(&db, "finder-mobile").status, "completed");
+    }
+}
@@ -0,0 +1,716 @@
+//! HTTP client abstraction shared by connectors.
+//!
+//! Connectors send requests through an [`HttpClient`] instead of holding a
+//! `reqwest::Client` directly, so tests can swap the network for a
+//! cassette: a JSON file of recorded request/response pairs.
+//!
+//! [`HttpService`] builds every client from the `[http]` config section:
+//! timeouts, proxy (Tor included) and TLS settings are the same for all of
+//! them. The clients it hands to connectors refuse URLs outside the
+//! connector's domains, log each exchange with personal data redacted and,
+//! in a [`ConnectorContext`], carry the connector's session cookies.
+//!
+//! - [`LiveClient`] talks to the network.
+//! - [`Recorder`] wraps another client and writes every exchange to a
+//!   cassette when dropped.
+//! - [`Replayer`] answers from a cassette and fails on any request it has
+//!   no recording for, so tests never reach the network by accident.
+//!
+//! Outside of unit tests, setting `DATA_BREAKER_CASSETTES=<dir>` makes
+//! every connector replay from `<dir>/<connector id>.json`; adding
+//! `DATA_BREAKER_RECORD=1` records there instead.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::session::{ConnectorContext, Session};
+use crate::config::HttpConfig;
+use crate::db::Database;
+use crate::error::AppError;
+
+/// Tor's default SOCKS port. `socks5h` resolves host names through Tor too.
+const TOR_PROXY: &str = "socks5h://127.0.0.1:9050";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Overrides the client's timeout for this request.
+    #[serde(skip)]
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    pub fn get(url: &str) -> Self {
+        Self {
+            method: "GET".into(),
+            url: url.into(),
+            headers: vec![],
+            body: None,
+            timeout: None,
+        }
+    }
+
+    pub fn post(url: &str, body: impl Into<String>) -> Self {
+        Self {
+            method: "POST".into(),
+            body: Some(body.into()),
+            ..Self::get(url)
+        }
+    }
+
+    /// POST `fields` as `application/x-www-form-urlencoded`.
+    pub fn post_form(url: &str, fields: &[(String, String)]) -> anyhow::Result<Self> {
+        // `Url` does the encoding; only its query string is kept.
+        let encoded = reqwest::Url::parse_with_params("http://form.invalid/", fields)?;
+        Ok(Self::post(url, encoded.query().unwrap_or_default())
+            .header("Content-Type", "application/x-www-form-urlencoded"))
+    }
+
+    /// GET `url` with `params` appended as an encoded query string.
+    pub fn get_with_query(url: &str, params: &[(&str, &str)]) -> anyhow::Result<Self> {
+        let url = reqwest::Url::parse_with_params(url, params)?;
+        Ok(Self::get(url.as_str()))
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Two requests are the same exchange if method, URL and body match.
+    /// Headers are ignored: they carry user agents and cookies that change
+    /// between recordings.
+    fn matches(&self, other: &HttpRequest) -> bool {
+        self.method.eq_ignore_ascii_case(&other.method)
+            && self.url == other.url
+            && self.body == other.body
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpResponse {
+    pub status: u16,
+    /// Final URL after redirects.
+    pub url: String,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+#[async_trait]
+pub trait HttpClient: Send + Sync {
+    async fn send(&self, request: HttpRequest) -> anyhow::Result<HttpResponse>;
+}
+
+/// Sends requests over the network with `reqwest`.
+pub struct LiveClient {
+    client: reqwest::Client,
+}
+
+impl LiveClient {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl HttpClient for LiveClient {
+    async fn send(&self, request: HttpRequest) -> anyhow::Result<HttpResponse> {
+        let method = reqwest::Method::from_bytes(request.method.as_bytes())?;
+        let mut builder = self.client.request(method, &request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let resp = builder.send().await?;
+        let status = resp.status().as_u16();
+        let url = resp.url().to_string();
+        let headers = resp
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+            .collect();
+        let body = resp.text().await?;
+        Ok(HttpResponse {
+            status,
+            url,
+            headers,
+            body,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: HttpRequest,
+    response: HttpResponse,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+/// Passes requests to `inner` and saves every exchange to `path` on drop.
+pub struct Recorder {
+    inner: Arc<dyn HttpClient>,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl Recorder {
+    pub fn new(inner: Arc<dyn HttpClient>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let cassette = self.cassette.lock().unwrap();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&*cassette)? + "\n")?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HttpClient for Recorder {
+    async fn send(&self, request: HttpRequest) -> anyhow::Result<HttpResponse> {
+        let response = self.inner.send(request.clone()).await?;
+        self.cassette
+            .lock()
+            .unwrap()
+            .interactions
+            .push(Interaction {
+                request,
+                response: response.clone(),
+            });
+        Ok(response)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.save() {
+            tracing::warn!("Failed to write cassette {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Answers requests from a recorded cassette. Each recording is used once,
+/// in order, so repeated requests can get different responses.
+pub struct Replayer {
+    path: PathBuf,
+    remaining: Mutex<Vec<Interaction>>,
+}
+
+impl Replayer {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read cassette {}: {e}", path.display()))?;
+        let cassette: Cassette = serde_json::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("Invalid cassette {}: {e}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            remaining: Mutex::new(cassette.interactions),
+        })
+    }
+}
+
+#[async_trait]
+impl HttpClient for Replayer {
+    async fn send(&self, request: HttpRequest) -> anyhow::Result<HttpResponse> {
+        let mut remaining = self.remaining.lock().unwrap();
+        let index = remaining
+            .iter()
+            .position(|i| i.request.matches(&request))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No recording for {} {} in {}",
+                    request.method,
+                    request.url,
+                    self.path.display()
+                )
+            })?;
+        Ok(remaining.remove(index).response)
+    }
+}
+
+/// Builds the HTTP clients for connectors and everything else that goes
+/// out to the network, from one `[http]` config.
+#[derive(Clone, Default)]
+pub struct HttpService {
+    config: HttpConfig,
+    proxy: Option<reqwest::Proxy>,
+    /// Where connector sessions are kept; in memory only without one.
+    sessions: Option<Arc<Database>>,
+}
+
+impl HttpService {
+    pub fn new(config: &HttpConfig) -> anyhow::Result<Self> {
+        let proxy_url = match (&config.proxy, config.tor) {
+            (Some(_), true) => {
+                return Err(AppError::Config("[http] sets both `proxy` and `tor`".into()).into());
+            }
+            (Some(url), false) => Some(url.as_str()),
+            (None, true) => Some(TOR_PROXY),
+            (None, false) => None,
+        };
+        let proxy = proxy_url
+            .map(|url| {
+                reqwest::Proxy::all(url)
+                    .map_err(|e| AppError::Config(format!("[http] proxy '{url}': {e}")))
+            })
+            .transpose()?;
+        Ok(Self {
+            config: config.clone(),
+            proxy,
+            sessions: None,
+        })
+    }
+
+    /// Persist connector sessions in `db`.
+    pub fn with_sessions(mut self, db: Arc<Database>) -> Self {
+        self.sessions = Some(db);
+        self
+    }
+
+    /// A `reqwest` builder with the shared timeouts, proxy and TLS settings.
+    pub fn builder(&self) -> reqwest::ClientBuilder {
+        let user_agent = self
+            .config
+            .user_agent
+            .clone()
+            .unwrap_or_else(|| format!("data-breaker/{}", env!("CARGO_PKG_VERSION")));
+        let builder = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .timeout(Duration::from_secs(self.config.timeout_secs))
+            .connect_timeout(Duration::from_secs(self.config.connect_timeout_secs))
+            .use_rustls_tls()
+            .min_tls_version(reqwest::tls::Version::TLS_1_2);
+        match &self.proxy {
+            Some(proxy) => builder.proxy(proxy.clone()),
+            None => builder,
+        }
+    }
+
+    /// The client connector `id` sends its requests through. It only
+    /// reaches `allowed_domains` (see [`url_allowed`]), redirects included,
+    /// and replays from `DATA_BREAKER_CASSETTES` when that is set.
+    pub fn client(
+        &self,
+        id: &str,
+        allowed_domains: &[String],
+    ) -> anyhow::Result<Arc<dyn HttpClient>> {
+        self.guarded(id, allowed_domains, None)
+    }
+
+    /// A client as from [`HttpService::client`] that also sends and keeps
+    /// the cookies of `id`'s stored session, along with that session.
+    pub fn context(
+        &self,
+        id: &str,
+        allowed_domains: &[String],
+    ) -> anyhow::Result<ConnectorContext> {
+        let session = Arc::new(match &self.sessions {
+            Some(db) => Session::load(db.clone(), id)?,
+            None => Session::in_memory(id),
+        });
+        Ok(ConnectorContext {
+            http: self.guarded(id, allowed_domains, Some(session.clone()))?,
+            session,
+        })
+    }
+
+    fn guarded(
+        &self,
+        id: &str,
+        allowed_domains: &[String],
+        session: Option<Arc<Session>>,
+    ) -> anyhow::Result<Arc<dyn HttpClient>> {
+        let allowed = allowed_domains.to_vec();
+        let policy = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > 5 {
+                attempt.error("too many redirects")
+            } else if url_allowed(attempt.url(), &allowed) {
+                attempt.follow()
+            } else {
+                attempt.error("redirect to a domain the connector may not access")
+            }
+        });
+        let live = Arc::new(LiveClient::new(self.builder().redirect(policy).build()?));
+
+        let inner: Arc<dyn HttpClient> = match std::env::var_os("DATA_BREAKER_CASSETTES") {
+            None => live,
+            Some(dir) => {
+                let path = Path::new(&dir).join(format!("{id}.json"));
+                if std::env::var_os("DATA_BREAKER_RECORD").is_some() {
+                    Arc::new(Recorder::new(live, path))
+                } else {
+                    Arc::new(Replayer::load(path)?)
+                }
+            }
+        };
+        Ok(Arc::new(Guarded {
+            id: id.to_string(),
+            allowed_domains: allowed_domains.to_vec(),
+            session,
+            inner,
+        }))
+    }
+}
+
+/// Enforces a connector's domain allowlist, logs its requests and keeps
+/// its session cookies.
+struct Guarded {
+    id: String,
+    allowed_domains: Vec<String>,
+    session: Option<Arc<Session>>,
+    inner: Arc<dyn HttpClient>,
+}
+
+#[async_trait]
+impl HttpClient for Guarded {
+    async fn send(&self, mut request: HttpRequest) -> anyhow::Result<HttpResponse> {
+        let url = reqwest::Url::parse(&request.url)
+            .map_err(|e| anyhow::anyhow!("Invalid URL '{}': {e}", request.url))?;
+        let redacted = redact(&url);
+        if !url_allowed(&url, &self.allowed_domains) {
+            return Err(AppError::Blocked {
+                connector: self.id.clone(),
+                url: redacted,
+            }
+            .into());
+        }
+
+        let has_cookie = request
+            .headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("cookie"));
+        if let Some(session) = &self.session
+            && !has_cookie
+            && let Some(cookies) = session.cookie_header(&url)
+        {
+            request.headers.push(("Cookie".into(), cookies));
+        }
+
+        let method = request.method.clone();
+        let started = Instant::now();
+        let result = self.inner.send(request).await;
+        let elapsed = started.elapsed().as_millis();
+        match &result {
+            Ok(resp) => tracing::debug!(
+                "[{}] {method} {redacted} -> {} in {elapsed} ms",
+                self.id,
+                resp.status
+            ),
+            Err(e) => tracing::debug!(
+                "[{}] {method} {redacted} failed after {elapsed} ms: {}",
+                self.id,
+                e.to_string().replace(url.as_str(), &redacted)
+            ),
+        }
+        if let (Some(session), Ok(resp)) = (&self.session, &result) {
+            let final_url = reqwest::Url::parse(&resp.url).unwrap_or(url);
+            session.store_cookies(&final_url, &resp.headers)?;
+        }
+        result
+    }
+}
+
+/// HTTPS to an allowed domain or one of its subdomains; plain HTTP only to
+/// loopback hosts that are explicitly allowed.
+pub fn url_allowed(url: &reqwest::Url, allowed_domains: &[String]) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.to_ascii_lowercase();
+    let scheme_ok = match url.scheme() {
+        "https" => true,
+        "http" => host == "localhost" || host == "127.0.0.1" || host == "[::1]",
+        _ => false,
+    };
+    scheme_ok
+        && allowed_domains.iter().any(|d| {
+            let d = d.to_ascii_lowercase();
+            host == d || host.ends_with(&format!(".{d}"))
+        })
+}
+
+/// `url` as it may appear in logs: query values are dropped, and so is every
+/// path segment after the first, since sites put names, cities and ids there
+/// (`/name/jane-doe/seattle-wa`). Request bodies are never logged.
+pub fn redact(url: &reqwest::Url) -> String {
+    let mut out = format!("{}://{}", url.scheme(), url.host_str().unwrap_or_default());
+    if let Some(port) = url.port() {
+        out.push_str(&format!(":{port}"));
+    }
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(|segments| {
+            segments
+                .enumerate()
+                .map(|(i, s)| if i == 0 || s.is_empty() { s } else { "***" })
+                .collect()
+        })
+        .unwrap_or_default();
+    out.push('/');
+    out.push_str(&segments.join("/"));
+    let keys: Vec<String> = url.query_pairs().map(|(k, _)| format!("{k}=***")).collect();
+    if !keys.is_empty() {
+        out.push('?');
+        out.push_str(&keys.join("&"));
+    }
+    out
+}
+
+/// Client for a connector test backed by `tests/cassettes/<name>.json`.
+///
+/// Replays by default. With `DATA_BREAKER_RECORD=1` set, requests go to
+/// `live` and the cassette is rewritten, to refresh fixtures after a broker
+/// changes its site.
+#[cfg(test)]
+pub fn test_cassette(
+    name: &str,
+    live: impl FnOnce() -> Arc<dyn HttpClient>,
+) -> Arc<dyn HttpClient> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/cassettes")
+        .join(format!("{name}.json"));
+    if std::env::var_os("DATA_BREAKER_RECORD").is_some() {
+        Arc::new(Recorder::new(live(), path))
+    } else {
+        Arc::new(Replayer::load(&path).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Canned;
+
+    #[async_trait]
+    impl HttpClient for Canned {
+        async fn send(&self, request: HttpRequest) -> anyhow::Result<HttpResponse> {
+            Ok(HttpResponse {
+                status: 200,
+                url: request.url.clone(),
+                headers: vec![],
+                body: format!("hello from {}", request.url),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = std::env::temp_dir().join(format!("db-cassette-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("canned.json");
+
+        let recorder = Recorder::new(Arc::new(Canned), &path);
+        let req = HttpRequest::get_with_query("https://example.com/search", &[("q", "a b")])
+            .unwrap()
+            .header("Accept", "text/plain");
+        assert_eq!(req.url, "https://example.com/search?q=a+b");
+        recorder.send(req.clone()).await.unwrap();
+        drop(recorder);
+
+        let replayer = Replayer::load(&path).unwrap();
+        let resp = replayer.send(HttpRequest::get(&req.url)).await.unwrap();
+        assert_eq!(resp.body, "hello from https://example.com/search?q=a+b");
+
+        // Each recording answers once; anything unrecorded is an error.
+        let err = replayer.send(req).await.unwrap_err();
+        assert!(err.to_string().contains("No recording for GET"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_live_client_honours_timeout() {
+        // Accept connections but never answer.
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let http = HttpService::new(&HttpConfig {
+            timeout_secs: 1,
+            ..HttpConfig::default()
+        })
+        .unwrap();
+        let client = http.client("test", &["127.0.0.1".into()]).unwrap();
+        let started = std::time::Instant::now();
+        assert!(client.send(HttpRequest::get(&url)).await.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // A per-request timeout wins over the configured one.
+        let request = HttpRequest {
+            timeout: Some(Duration::from_millis(100)),
+            ..HttpRequest::get(&url)
+        };
+        let started = std::time::Instant::now();
+        assert!(client.send(request).await.is_err());
+        assert!(started.elapsed() < Duration::from_millis(900));
+    }
+
+    #[tokio::test]
+    async fn test_client_refuses_other_domains() {
+        let client = HttpService::default()
+            .client("acme", &["acme.example".into()])
+            .unwrap();
+        let err = client
+            .send(HttpRequest::get(
+                "https://evil.example/?email=jane@example.com",
+            ))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AppError>(),
+            Some(AppError::Blocked { connector, url })
+                if connector == "acme" && url == "https://evil.example/?email=***"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_context_carries_session_cookies() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Set a cookie on the first request, echo the Cookie header after.
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut first = true;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let cookie = request
+                    .lines()
+                    .find_map(|l| l.strip_prefix("cookie: "))
+                    .unwrap_or("")
+                    .to_string();
+                let extra = if first { "Set-Cookie: sid=abc\r\n" } else { "" };
+                first = false;
+                let reply = format!(
+                    "HTTP/1.1 200 OK\r\n{extra}Content-Length: {}\r\nConnection: close\r\n\r\n{cookie}",
+                    cookie.len()
+                );
+                stream.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+
+        let db = Arc::new(Database::open_in_memory().unwrap());
+        let http = HttpService::default().with_sessions(db.clone());
+        let context = http.context("acme", &["127.0.0.1".into()]).unwrap();
+        assert_eq!(
+            context
+                .http
+                .send(HttpRequest::get(&url))
+                .await
+                .unwrap()
+                .body,
+            ""
+        );
+        assert_eq!(
+            context
+                .http
+                .send(HttpRequest::get(&url))
+                .await
+                .unwrap()
+                .body,
+            "sid=abc"
+        );
+
+        // A later run picks the stored cookie up again.
+        let context = http.context("acme", &["127.0.0.1".into()]).unwrap();
+        assert_eq!(
+            context
+                .http
+                .send(HttpRequest::get(&url))
+                .await
+                .unwrap()
+                .body,
+            "sid=abc"
+        );
+    }
+
+    #[test]
+    fn test_url_allowed() {
+        let allowed = vec!["example.com".to_string(), "127.0.0.1".to_string()];
+        let ok = |u: &str| url_allowed(&reqwest::Url::parse(u).unwrap(), &allowed);
+        assert!(ok("https://example.com/a"));
+        assert!(ok("https://www.example.com/a"));
+        assert!(ok("http://127.0.0.1:8080/"));
+        assert!(!ok("http://example.com/"));
+        assert!(!ok("https://badexample.com/"));
+        assert!(!ok("https://example.com.evil.net/"));
+        assert!(!ok("file:///etc/passwd"));
+    }
+
+    #[test]
+    fn test_redact() {
+        let url = reqwest::Url::parse(
+            "https://acme.example:8443/people/jane%40example.com/5550100123?first=Jane&last=Doe",
+        )
+        .unwrap();
+        assert_eq!(
+            redact(&url),
+            "https://acme.example:8443/people/***/***?first=***&last=***"
+        );
+        let url = reqwest::Url::parse("https://acme.example/name/jane-doe/seattle-wa").unwrap();
+        assert_eq!(redact(&url), "https://acme.example/name/***/***");
+        let url = reqwest::Url::parse("https://acme.example/").unwrap();
+        assert_eq!(redact(&url), "https://acme.example/");
+    }
+
+    #[test]
+    fn test_tor_and_proxy_conflict() {
+        let config = HttpConfig {
+            tor: true,
+            proxy: Some("http://proxy.example:3128".into()),
+            ..HttpConfig::default()
+        };
+        assert!(HttpService::new(&config).is_err());
+        assert!(
+            HttpService::new(&HttpConfig {
+                proxy: None,
+                ..config
+            })
+            .is_ok()
+        );
+    }
+}
@@ -1,6 +1,19 @@
 pub mod beenverified;
+#[cfg(test)]
+pub mod conformance;
+pub mod coverage;
 pub mod dummy;
+pub mod email;
+pub mod family;
+pub mod http;
+pub mod plugin;
+pub mod postal;
 pub mod registry;
+pub mod script;
+pub mod session;
+pub mod state_registry;
+pub mod wasm;
+pub mod workflow;
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -8,6 +21,8 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
+use crate::error::AppError;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PersonQuery {
     pub first_name: String,
@@ -26,10 +41,68 @@ pub struct FoundRecord {
     pub metadata: Option<serde_json::Value>,
 }
 
+/// Values connectors may use for `FoundRecord::data_type`.
+pub const KNOWN_DATA_TYPES: &[&str] = &["name", "age", "address", "phone", "email", "relatives"];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeletionSubmission {
     pub external_ref: String,
     pub message: Option<String>,
+    /// Steps the user must perform before the request is actually submitted.
+    /// Empty when the connector completed the submission on its own.
+    #[serde(default)]
+    pub manual_steps: Vec<ManualStep>,
+}
+
+/// Kind of human action a connector cannot perform itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManualStepKind {
+    OpenUrl,
+    SolveCaptcha,
+    PhoneVerification,
+    EmailLink,
+    /// Print a letter and mail it.
+    PostalMail,
+    Other,
+}
+
+impl ManualStepKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ManualStepKind::OpenUrl => "open_url",
+            ManualStepKind::SolveCaptcha => "solve_captcha",
+            ManualStepKind::PhoneVerification => "phone_verification",
+            ManualStepKind::EmailLink => "email_link",
+            ManualStepKind::PostalMail => "postal_mail",
+            ManualStepKind::Other => "other",
+        }
+    }
+}
+
+/// A human step emitted by a connector, e.g. "solve the CAPTCHA on this page".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManualStep {
+    /// Connector-defined key, handed back to `resume_deletion` once done.
+    pub key: String,
+    pub kind: ManualStepKind,
+    pub instructions: String,
+    pub url: Option<String>,
+    /// Values to paste into the broker's form, as (label, value) pairs.
+    #[serde(default)]
+    pub prefill: Vec<(String, String)>,
+}
+
+/// What happens after the user completed a manual step.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManualStepOutcome {
+    /// New deletion status, if the connector knows it.
+    pub status: Option<String>,
+    /// Replacement reference, e.g. the broker's confirmation number.
+    pub external_ref: Option<String>,
+    pub message: Option<String>,
+    #[serde(default)]
+    pub next_steps: Vec<ManualStep>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,11 +112,105 @@ pub struct DeletionStatusCheck {
     pub message: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+/// A `PersonQuery` field a connector can search with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryField {
+    FirstName,
+    LastName,
+    Email,
+    Phone,
+    City,
+    State,
+}
+
+impl QueryField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueryField::FirstName => "first_name",
+            QueryField::LastName => "last_name",
+            QueryField::Email => "email",
+            QueryField::Phone => "phone",
+            QueryField::City => "city",
+            QueryField::State => "state",
+        }
+    }
+}
+
+impl PersonQuery {
+    /// Whether `field` holds a non-blank value.
+    pub fn has(&self, field: QueryField) -> bool {
+        let value = match field {
+            QueryField::FirstName => Some(&self.first_name),
+            QueryField::LastName => Some(&self.last_name),
+            QueryField::Email => self.email.as_ref(),
+            QueryField::Phone => self.phone.as_ref(),
+            QueryField::City => self.city.as_ref(),
+            QueryField::State => self.state.as_ref(),
+        };
+        value.is_some_and(|v| !v.trim().is_empty())
+    }
+}
+
+/// How a broker takes deletion requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletionMethod {
+    /// An opt-out form on the broker's site.
+    Form,
+    Email,
+    Api,
+    /// Steps only the user can do, e.g. a phone call.
+    Manual,
+    Postal,
+}
+
+impl DeletionMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeletionMethod::Form => "form",
+            DeletionMethod::Email => "email",
+            DeletionMethod::Api => "api",
+            DeletionMethod::Manual => "manual",
+            DeletionMethod::Postal => "postal",
+        }
+    }
+}
+
+/// What a connector can do and what it needs to do it. Everything past the
+/// three `can_*` flags is optional in plugin handshakes and script `info()`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConnectorCapabilities {
     pub can_scan: bool,
     pub can_delete: bool,
     pub can_check_status: bool,
+    /// Query fields a scan cannot run without.
+    pub required_fields: Vec<QueryField>,
+    /// Query fields that narrow a scan down when given.
+    pub optional_fields: Vec<QueryField>,
+    /// `FoundRecord::data_type` values scans can return.
+    pub data_types: Vec<String>,
+    pub deletion_method: Option<DeletionMethod>,
+    /// The broker asks the user to confirm a request, e.g. by email link.
+    pub needs_verification: bool,
+    /// One request removes every record found, not just one listing.
+    pub batch: bool,
+    /// Days the broker usually takes to process a request.
+    pub processing_days: Option<u32>,
+}
+
+impl ConnectorCapabilities {
+    /// Check `query` has every required field before a connector is called.
+    pub fn validate(&self, broker: &str, query: &PersonQuery) -> Result<(), AppError> {
+        match self.required_fields.iter().find(|f| !query.has(**f)) {
+            Some(field) => Err(AppError::MissingField {
+                broker: broker.to_string(),
+                field: field.as_str().to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
 }
 
 #[async_trait]
@@ -61,17 +228,53 @@ pub trait BrokerConnector: Send + Sync {
         &self,
         external_ref: &str,
     ) -> anyhow::Result<DeletionStatusCheck>;
+
+    /// Continue a deletion flow after the user completed one of the manual
+    /// steps returned in a `DeletionSubmission`. `input` is whatever the user
+    /// entered (an SMS code, a confirmation number, ...).
+    ///
+    /// The default has nothing left to do: once every step is resolved the
+    /// request is considered submitted.
+    async fn resume_deletion(
+        &self,
+        _external_ref: &str,
+        _step_key: &str,
+        _input: Option<&str>,
+    ) -> anyhow::Result<ManualStepOutcome> {
+        Ok(ManualStepOutcome::default())
+    }
+
+    /// The steps that follow `request_deletion`, for brokers whose opt-out
+    /// does not end with the first submission. See `broker::workflow`.
+    fn workflow(&self) -> Option<workflow::Workflow> {
+        None
+    }
+
+    /// Perform an `action` step of the connector's workflow. `data` holds
+    /// what earlier steps collected. May run more than once for the same
+    /// step if a previous run was interrupted.
+    async fn run_step(
+        &self,
+        _external_ref: &str,
+        step: &str,
+        _data: &serde_json::Map<String, serde_json::Value>,
+    ) -> anyhow::Result<workflow::StepOutcome> {
+        Err(AppError::Unsupported(format!("{} has no workflow step '{step}'", self.name())).into())
+    }
 }
 
 /// Build the map of all compiled-in connectors.
 /// Contributors: add your connector here.
-pub fn build_connector_registry() -> HashMap<String, Arc<dyn BrokerConnector>> {
+pub fn build_connector_registry(
+    scenario: dummy::Scenario,
+    http: &http::HttpService,
+) -> HashMap<String, Arc<dyn BrokerConnector>> {
     let mut map: HashMap<String, Arc<dyn BrokerConnector>> = HashMap::new();
 
-    let dummy = Arc::new(dummy::DummyBroker);
+    let dummy = Arc::new(dummy::DummyBroker::new(scenario));
     map.insert(dummy.id().to_string(), dummy);
 
-    match beenverified::BeenVerifiedBroker::new() {
+    match beenverified::BeenVerifiedBroker::new(http) {
         Ok(bv) => {
             let bv = Arc::new(bv);
             map.insert(bv.id().to_string(), bv);
@@ -90,7 +293,8 @@ mod tests {
 
     #[test]
     fn test_build_connector_registry() {
-        let reg = build_connector_registry();
+        let reg =
+            build_connector_registry(dummy::Scenario::default(), &http::HttpService::default());
         assert!(reg.contains_key("dummy-broker"));
         let dummy = reg.get("dummy-broker").unwrap();
         assert_eq!(dummy.name(), "Dummy Broker");
@@ -101,7 +305,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_dummy_scan() {
-        let dummy = dummy::DummyBroker;
+        let dummy = dummy::DummyBroker::default();
         let query = PersonQuery {
             first_name: "John".into(),
             last_name: "Doe".into(),
@@ -116,18 +320,19 @@ mod tests {
 
     #[test]
     fn test_registry_contains_beenverified() {
-        let reg = build_connector_registry();
+        let reg =
+            build_connector_registry(dummy::Scenario::default(), &http::HttpService::default());
         assert!(reg.contains_key("beenverified"));
         let bv = reg.get("beenverified").unwrap();
         assert_eq!(bv.name(), "BeenVerified");
         assert!(bv.capabilities().can_scan);
-        assert!(!bv.capabilities().can_delete);
+        assert!(bv.capabilities().can_delete);
         assert!(!bv.capabilities().can_check_status);
     }
 
     #[tokio::test]
     async fn test_beenverified_requires_state() {
-        let bv = beenverified::BeenVerifiedBroker::new().unwrap();
+        let bv = beenverified::BeenVerifiedBroker::new(&http::HttpService::default()).unwrap();
         let query = PersonQuery {
             first_name: "John".into(),
             last_name: "Doe".into(),
@@ -144,24 +349,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_capabilities_validate_query() {
+        let caps = ConnectorCapabilities {
+            required_fields: vec![QueryField::LastName, QueryField::City],
+            ..Default::default()
+        };
+        let mut query = PersonQuery {
+            first_name: String::new(),
+            last_name: "Doe".into(),
+            email: None,
+            phone: None,
+            city: Some("  ".into()),
+            state: None,
+        };
+        let err = caps.validate("Acme", &query).unwrap_err();
+        assert_eq!(err.to_string(), "Acme requires --city");
+
+        query.city = Some("Albany".into());
+        assert!(caps.validate("Acme", &query).is_ok());
+    }
+
     #[tokio::test]
-    async fn test_beenverified_deletion_not_supported() {
-        let bv = beenverified::BeenVerifiedBroker::new().unwrap();
+    async fn test_beenverified_deletion_is_manual() {
+        let bv = beenverified::BeenVerifiedBroker::new(&http::HttpService::default()).unwrap();
         let query = PersonQuery {
             first_name: "John".into(),
             last_name: "Doe".into(),
             email: None,
             phone: None,
             city: None,
-            state: None,
+            state: Some("NY".into()),
         };
-        assert!(bv.request_deletion(&query, &[]).await.is_err());
+        let submission = bv.request_deletion(&query, &[]).await.unwrap();
+        assert!(!submission.manual_steps.is_empty());
+        assert!(
+            submission
+                .manual_steps
+                .iter()
+                .any(|s| s.kind == ManualStepKind::SolveCaptcha)
+        );
         assert!(bv.check_deletion_status("ref-123").await.is_err());
     }
 
     #[tokio::test]
     async fn test_dummy_deletion() {
-        let dummy = dummy::DummyBroker;
+        let dummy = dummy::DummyBroker::default();
         let query = PersonQuery {
             first_name: "John".into(),
             last_name: "Doe".into(),
@@ -179,5 +412,6 @@ mod tests {
             .await
             .unwrap();
         assert_eq!(status.status, "in_progress");
+        assert!(submission.manual_steps.is_empty());
     }
 }
@@ -0,0 +1,600 @@
+//! Out-of-process connectors.
+//!
+//! A plugin is any executable in the plugins directory. It is started with
+//! piped stdin/stdout and speaks JSON-RPC 2.0, one message per line:
+//!
+//! ```text
+//! -> {"jsonrpc":"2.0","id":0,"method":"handshake","params":{"protocol_version":1}}
+//! <- {"jsonrpc":"2.0","id":0,"result":{"protocol_version":1,"id":"acme","name":"Acme",
+//!     "capabilities":{"can_scan":true,"can_delete":true,"can_check_status":false}}}
+//! ```
+//!
+//! `capabilities` may also carry the optional `ConnectorCapabilities` fields,
+//! e.g. `"required_fields":["state"]` or `"deletion_method":"form"`. A
+//! plugin whose opt-out takes several steps adds a `workflow` (see
+//! `broker::workflow`) next to them.
+//!
+//! After the handshake the methods mirror `BrokerConnector`:
+//!
+//! | method                  | params                                   | result                |
+//! |-------------------------|------------------------------------------|-----------------------|
+//! | `scan`                  | `{query}`                                | `[FoundRecord]`       |
+//! | `request_deletion`      | `{query, records}`                       | `DeletionSubmission`  |
+//! | `check_deletion_status` | `{external_ref}`                         | `DeletionStatusCheck` |
+//! | `resume_deletion`       | `{external_ref, step_key, input}`        | `ManualStepOutcome`   |
+//! | `run_step`              | `{external_ref, step, data}`             | `StepOutcome`         |
+//!
+//! `run_step` is only called for the `action` steps of the plugin's
+//! workflow. `resume_deletion` is optional; answering "method not found" (-32601)
+//! means there is nothing left to do. Errors are reported with a JSON-RPC
+//! `error` object whose message is shown to the user. Anything the plugin
+//! writes to stderr is logged at debug level.
+//!
+//! Each call has a deadline. A plugin that times out, exits or writes
+//! garbage is killed and started again on the next call, so a misbehaving
+//! plugin can only fail its own requests.
+//!
+//! Handshakes are remembered in `.handshakes.json` in the plugins directory
+//! for as long as the executable's size and modification time stay the
+//! same, so a plugin is only started once a command actually calls it.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+
+use super::workflow::{StepOutcome, Workflow};
+use super::{
+    BrokerConnector, ConnectorCapabilities, DeletionStatusCheck, DeletionSubmission, FoundRecord,
+    ManualStepOutcome, PersonQuery,
+};
+use crate::config::PluginsConfig;
+
+/// Version of the plugin protocol spoken by this build.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// How long a plugin may take to answer the handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// File in the plugins directory that remembers each plugin's handshake.
+const HANDSHAKE_CACHE: &str = ".handshakes.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Handshake {
+    protocol_version: u32,
+    id: String,
+    name: String,
+    capabilities: ConnectorCapabilities,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workflow: Option<Workflow>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// Failure of a single call, keeping JSON-RPC errors apart from transport
+/// failures (which also take the process down).
+enum CallError {
+    Rpc(RpcError),
+    Transport(anyhow::Error),
+}
+
+impl From<CallError> for anyhow::Error {
+    fn from(e: CallError) -> Self {
+        match e {
+            CallError::Rpc(e) => anyhow::anyhow!("{} (code {})", e.message, e.code),
+            CallError::Transport(e) => e,
+        }
+    }
+}
+
+struct PluginProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl PluginProcess {
+    fn spawn(path: &Path) -> anyhow::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to start plugin {}: {e}", path.display()))?;
+
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+        if let Some(stderr) = child.stderr.take() {
+            let name = path.display().to_string();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("[plugin {name}] {line}");
+                }
+            });
+        }
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    async fn call(&mut self, id: u64, method: &str, params: Value) -> Result<Value, CallError> {
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let mut line =
+            serde_json::to_string(&request).map_err(|e| CallError::Transport(e.into()))?;
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| CallError::Transport(e.into()))?;
+        self.stdin
+            .flush()
+            .await
+            .map_err(|e| CallError::Transport(e.into()))?;
+
+        loop {
+            let mut buf = String::new();
+            let n = self
+                .stdout
+                .read_line(&mut buf)
+                .await
+                .map_err(|e| CallError::Transport(e.into()))?;
+            if n == 0 {
+                let status = self.child.try_wait().ok().flatten();
+                return Err(CallError::Transport(match status {
+                    Some(s) => anyhow::anyhow!("plugin exited ({s})"),
+                    None => anyhow::anyhow!("plugin closed its output"),
+                }));
+            }
+            if buf.trim().is_empty() {
+                continue;
+            }
+            let response: Value = serde_json::from_str(&buf).map_err(|e| {
+                CallError::Transport(anyhow::anyhow!("plugin wrote invalid JSON: {e}"))
+            })?;
+            // Skip notifications. A call that times out takes the process
+            // down, so no answer meant for an earlier call can show up here.
+            if response.get("id").and_then(Value::as_u64) != Some(id) {
+                continue;
+            }
+            if let Some(error) = response.get("error") {
+                let error: RpcError = serde_json::from_value(error.clone()).map_err(|e| {
+                    CallError::Transport(anyhow::anyhow!("plugin wrote a malformed error: {e}"))
+                })?;
+                return Err(CallError::Rpc(error));
+            }
+            return Ok(response.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+}
+
+/// Spawn a plugin process and check that it speaks our protocol version.
+async fn start(path: &Path) -> anyhow::Result<(PluginProcess, Handshake)> {
+    let mut process = PluginProcess::spawn(path)?;
+    let params = json!({ "protocol_version": PROTOCOL_VERSION });
+    let result = tokio::time::timeout(HANDSHAKE_TIMEOUT, process.call(0, "handshake", params))
+        .await
+        .map_err(|_| anyhow::anyhow!("handshake timed out"))??;
+    let handshake: Handshake = serde_json::from_value(result)
+        .map_err(|e| anyhow::anyhow!("invalid handshake response: {e}"))?;
+    if handshake.protocol_version != PROTOCOL_VERSION {
+        anyhow::bail!(
+            "speaks protocol version {}, expected {}",
+            handshake.protocol_version,
+            PROTOCOL_VERSION
+        );
+    }
+    if let Some(workflow) = &handshake.workflow {
+        workflow
+            .validate()
+            .map_err(|e| anyhow::anyhow!("invalid workflow: {e}"))?;
+    }
+    Ok((process, handshake))
+}
+
+/// A connector implemented by an external executable.
+pub struct PluginConnector {
+    path: PathBuf,
+    id: String,
+    name: String,
+    capabilities: ConnectorCapabilities,
+    workflow: Option<Workflow>,
+    timeout: Duration,
+    process: Mutex<Option<PluginProcess>>,
+    next_id: AtomicU64,
+}
+
+impl PluginConnector {
+    /// A plugin described by `handshake`. Without a running `process`, it is
+    /// started on the first call.
+    fn new(
+        path: &Path,
+        handshake: Handshake,
+        process: Option<PluginProcess>,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            id: handshake.id,
+            name: handshake.name,
+            capabilities: handshake.capabilities,
+            workflow: handshake.workflow,
+            timeout,
+            process: Mutex::new(process),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Call a method, (re)starting the process if needed. Transport failures
+    /// and timeouts kill the process so the next call starts fresh.
+    async fn call_raw(&self, method: &str, params: Value) -> Result<Value, CallError> {
+        let mut guard = self.process.lock().await;
+        if guard.is_none() {
+            let (process, _) = start(&self.path).await.map_err(CallError::Transport)?;
+            *guard = Some(process);
+        }
+        let process = guard.as_mut().expect("process started above");
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let result =
+            match tokio::time::timeout(self.timeout, process.call(id, method, params)).await {
+                Ok(result) => result,
+                Err(_) => Err(CallError::Transport(anyhow::anyhow!(
+                    "no response within {}s",
+                    self.timeout.as_secs()
+                ))),
+            };
+        if let Err(CallError::Transport(e)) = &result {
+            tracing::warn!(
+                "Plugin '{}' failed during {method}: {e}; restarting",
+                self.id
+            );
+            *guard = None;
+        }
+        result
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> anyhow::Result<T> {
+        let result = self
+            .call_raw(method, params)
+            .await
+            .map_err(|e| anyhow::Error::from(e).context(format!("Plugin '{}'", self.id)))?;
+        serde_json::from_value(result).map_err(|e| {
+            anyhow::anyhow!(
+                "Plugin '{}' returned an invalid {method} result: {e}",
+                self.id
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl BrokerConnector for PluginConnector {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> ConnectorCapabilities {
+        self.capabilities.clone()
+    }
+
+    async fn scan(&self, query: &PersonQuery) -> anyhow::Result<Vec<FoundRecord>> {
+        self.capabilities.validate(&self.name, query)?;
+        self.call("scan", json!({ "query": query })).await
+    }
+
+    async fn request_deletion(
+        &self,
+        query: &PersonQuery,
+        records: &[FoundRecord],
+    ) -> anyhow::Result<DeletionSubmission> {
+        self.call(
+            "request_deletion",
+            json!({ "query": query, "records": records }),
+        )
+        .await
+    }
+
+    async fn check_deletion_status(
+        &self,
+        external_ref: &str,
+    ) -> anyhow::Result<DeletionStatusCheck> {
+        self.call(
+            "check_deletion_status",
+            json!({ "external_ref": external_ref }),
+        )
+        .await
+    }
+
+    async fn resume_deletion(
+        &self,
+        external_ref: &str,
+        step_key: &str,
+        input: Option<&str>,
+    ) -> anyhow::Result<ManualStepOutcome> {
+        let params = json!({ "external_ref": external_ref, "step_key": step_key, "input": input });
+        match self.call_raw("resume_deletion", params).await {
+            Err(CallError::Rpc(e)) if e.code == METHOD_NOT_FOUND => {
+                Ok(ManualStepOutcome::default())
+            }
+            Err(e) => Err(anyhow::Error::from(e).context(format!("Plugin '{}'", self.id))),
+            Ok(result) => Ok(serde_json::from_value(result)?),
+        }
+    }
+
+    fn workflow(&self) -> Option<Workflow> {
+        self.workflow.clone()
+    }
+
+    async fn run_step(
+        &self,
+        external_ref: &str,
+        step: &str,
+        data: &serde_json::Map<String, Value>,
+    ) -> anyhow::Result<StepOutcome> {
+        self.call(
+            "run_step",
+            json!({ "external_ref": external_ref, "step": step, "data": data }),
+        )
+        .await
+    }
+}
+
+/// Executables directly inside `dir`, sorted by name.
+fn discover(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && is_executable(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "exe")
+}
+
+/// A remembered handshake and the executable it came from.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedHandshake {
+    size: u64,
+    modified_ns: u64,
+    handshake: Handshake,
+}
+
+/// `(size, modification time)` of an executable, to tell when it changed.
+fn fingerprint(path: &Path) -> Option<(u64, u64)> {
+    let metadata = path.metadata().ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_nanos() as u64))
+}
+
+/// Register every plugin in the plugins directory, starting only those
+/// whose handshake is not remembered yet. Compiled-in connectors with the
+/// same ID take precedence; plugins that fail to start are skipped with a
+/// warning.
+pub async fn add_plugin_connectors(
+    connectors: &mut HashMap<String, Arc<dyn BrokerConnector>>,
+    config: &PluginsConfig,
+) -> anyhow::Result<()> {
+    let dir = match &config.dir {
+        Some(dir) => dir.clone(),
+        None => crate::config::plugins_dir()?,
+    };
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    let cache_path = dir.join(HANDSHAKE_CACHE);
+    let mut cache: HashMap<String, CachedHandshake> = std::fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let mut changed = false;
+    let mut seen = Vec::new();
+
+    let timeout = Duration::from_secs(config.timeout_secs);
+    for path in discover(&dir)? {
+        let key = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let stamp = fingerprint(&path);
+        seen.push(key.clone());
+        let cached = cache
+            .get(&key)
+            .filter(|c| Some((c.size, c.modified_ns)) == stamp);
+        let plugin = match cached {
+            Some(c) => PluginConnector::new(&path, c.handshake.clone(), None, timeout),
+            None => match start(&path).await {
+                Ok((process, handshake)) => {
+                    if let Some((size, modified_ns)) = stamp {
+                        cache.insert(
+                            key,
+                            CachedHandshake {
+                                size,
+                                modified_ns,
+                                handshake: handshake.clone(),
+                            },
+                        );
+                        changed = true;
+                    }
+                    PluginConnector::new(&path, handshake, Some(process), timeout)
+                }
+                Err(e) => {
+                    tracing::warn!("Skipping plugin {}: {e}", path.display());
+                    continue;
+                }
+            },
+        };
+        if connectors.contains_key(plugin.id()) {
+            tracing::warn!(
+                "Plugin {} uses ID '{}', which is already taken",
+                path.display(),
+                plugin.id()
+            );
+            continue;
+        }
+        tracing::info!("Loaded plugin '{}' from {}", plugin.id(), path.display());
+        connectors.insert(plugin.id().to_string(), Arc::new(plugin));
+    }
+
+    let before = cache.len();
+    cache.retain(|key, _| seen.contains(key));
+    if changed || cache.len() != before {
+        let json = serde_json::to_string_pretty(&cache)?;
+        if let Err(e) = std::fs::write(&cache_path, json) {
+            tracing::warn!("Could not save {}: {e}", cache_path.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// A plugin written in sh: answers by method name, echoing the request id.
+    const SCRIPT: &str = r#"#!/bin/sh
+echo started >> "$0.starts"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+  case "$line" in
+    *'"handshake"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocol_version":1,"id":"sh-broker","name":"Shell Broker","capabilities":{"can_scan":true,"can_delete":true,"can_check_status":true},"workflow":{"steps":[{"name":"submit","type":"action"}]}}}\n' "$id" ;;
+    *'"run_step"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"data":{"ticket":"T-1"}}}\n' "$id" ;;
+    *'"scan"'*)
+      echo "scanning" >&2
+      printf '{"jsonrpc":"2.0","id":%s,"result":[{"data_type":"name","data_value":"Jane Doe","profile_url":null,"metadata":null}]}\n' "$id" ;;
+    *'"request_deletion"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":1,"message":"broker is down"}}\n' "$id" ;;
+    *'"check_deletion_status"'*)
+      sleep 5 ;;
+    *)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"method not found"}}\n' "$id" ;;
+  esac
+done
+"#;
+
+    fn write_plugin(dir: &Path) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("sh-broker");
+        std::fs::write(&path, SCRIPT).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(dir.join("README"), "not a plugin").unwrap();
+        path
+    }
+
+    fn query() -> PersonQuery {
+        PersonQuery {
+            first_name: "Jane".into(),
+            last_name: "Doe".into(),
+            email: None,
+            phone: None,
+            city: None,
+            state: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_round_trip_errors_and_timeout() {
+        let dir = std::env::temp_dir().join(format!("db-plugins-{}", uuid::Uuid::new_v4()));
+        write_plugin(&dir);
+
+        let mut connectors: HashMap<String, Arc<dyn BrokerConnector>> = HashMap::new();
+        let config = PluginsConfig {
+            dir: Some(dir.clone()),
+            timeout_secs: 1,
+        };
+        add_plugin_connectors(&mut connectors, &config)
+            .await
+            .unwrap();
+        assert_eq!(connectors.len(), 1);
+        let plugin = connectors.get("sh-broker").unwrap();
+        assert_eq!(plugin.name(), "Shell Broker");
+        assert!(plugin.capabilities().can_check_status);
+
+        // Once the handshake is remembered, a plugin starts on first use.
+        let starts = || {
+            std::fs::read_to_string(dir.join("sh-broker.starts"))
+                .unwrap()
+                .lines()
+                .count()
+        };
+        assert_eq!(starts(), 1);
+        let mut again: HashMap<String, Arc<dyn BrokerConnector>> = HashMap::new();
+        add_plugin_connectors(&mut again, &config).await.unwrap();
+        assert_eq!(again["sh-broker"].name(), "Shell Broker");
+        assert_eq!(starts(), 1);
+        again["sh-broker"].scan(&query()).await.unwrap();
+        assert_eq!(starts(), 2);
+
+        let records = plugin.scan(&query()).await.unwrap();
+        assert_eq!(records[0].data_value, "Jane Doe");
+
+        let err = plugin
+            .request_deletion(&query(), &records)
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("broker is down"));
+
+        // Optional method: "method not found" means nothing left to do.
+        let outcome = plugin.resume_deletion("ref", "step", None).await.unwrap();
+        assert!(outcome.status.is_none());
+
+        // The workflow comes with the handshake and survives the cache.
+        assert_eq!(plugin.workflow().unwrap().steps[0].name, "submit");
+        assert_eq!(again["sh-broker"].workflow().unwrap().steps.len(), 1);
+        let outcome = plugin
+            .run_step("ref", "submit", &serde_json::Map::new())
+            .await
+            .unwrap();
+        assert_eq!(outcome.data["ticket"], "T-1");
+
+        // A hung call times out, and the plugin is restarted afterwards.
+        let err = plugin.check_deletion_status("ref").await.unwrap_err();
+        assert!(format!("{err:#}").contains("no response within 1s"));
+        let records = plugin.scan(&query()).await.unwrap();
+        assert_eq!(records.len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
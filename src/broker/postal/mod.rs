@@ -0,0 +1,401 @@
+mod pdf;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use super::email::{list_records, render_template};
+use super::{
+    BrokerConnector, ConnectorCapabilities, DeletionMethod, DeletionStatusCheck,
+    DeletionSubmission, FoundRecord, ManualStep, ManualStepKind, ManualStepOutcome, PersonQuery,
+};
+use crate::config::{Config, PostalConfig};
+use crate::db::Database;
+use crate::error::AppError;
+
+/// Registry `connector` value for brokers that only accept requests by mail.
+pub const POSTAL_CONNECTOR: &str = "postal";
+
+/// Step key of the "print and mail the letter" task.
+pub const MAIL_STEP: &str = "mail-letter";
+
+/// Built-in letter template.
+const DEFAULT_TEMPLATE: &str = include_str!("letter.txt");
+
+/// Characters per line of 11pt Helvetica within one-inch margins.
+const LINE_WIDTH: usize = 88;
+const FONT_SIZE: f32 = 11.0;
+const MARGIN: f32 = 72.0;
+
+/// Deletion by postal mail.
+///
+/// One instance exists per registry broker whose connector is `postal`.
+/// `request_deletion` writes a print-ready PDF (the letter plus an envelope
+/// page) and hands the user a task to mail it; the request counts as
+/// submitted once the mailing is logged.
+pub struct PostalBroker {
+    id: String,
+    name: String,
+    address: Vec<String>,
+    sender: Option<PostalConfig>,
+    template: String,
+    letters_dir: PathBuf,
+}
+
+impl PostalBroker {
+    pub fn new(
+        id: &str,
+        name: &str,
+        address: &str,
+        sender: Option<PostalConfig>,
+        template: String,
+        letters_dir: PathBuf,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            address: address_lines(address),
+            sender,
+            template,
+            letters_dir,
+        }
+    }
+
+    fn render_letter(&self, sender: &PostalConfig, records: &[FoundRecord]) -> Vec<String> {
+        let body = render_template(
+            &self.template,
+            &[
+                ("broker_name", &self.name),
+                ("full_name", &sender.name),
+                ("address", &sender.address.join(", ")),
+                ("email", sender.email.as_deref().unwrap_or("(not provided)")),
+                ("records", &list_records(records)),
+            ],
+        );
+
+        let mut lines = vec![sender.name.clone()];
+        lines.extend(sender.address.iter().cloned());
+        lines.push(String::new());
+        lines.push(chrono::Local::now().format("%B %-d, %Y").to_string());
+        lines.push(String::new());
+        lines.extend(self.address.iter().cloned());
+        lines.push(String::new());
+        lines.extend(pdf::wrap(&body, LINE_WIDTH));
+        lines
+    }
+
+    fn render_pdf(&self, sender: &PostalConfig, records: &[FoundRecord]) -> Vec<u8> {
+        let leading = FONT_SIZE * 1.3;
+        let per_page = ((pdf::PAGE_HEIGHT - 2.0 * MARGIN) / leading) as usize;
+        let mut pages: Vec<pdf::Page> = self
+            .render_letter(sender, records)
+            .chunks(per_page)
+            .map(|chunk| {
+                let mut page = pdf::Page::new();
+                page.text(MARGIN, pdf::PAGE_HEIGHT - MARGIN, FONT_SIZE, chunk);
+                page
+            })
+            .collect();
+        pages.push(self.envelope_page(sender));
+        pdf::render(&pages)
+    }
+
+    /// A #10 envelope outline (9.5 x 4.125 in, scaled to fit the page width)
+    /// to cut out as a label or copy by hand.
+    fn envelope_page(&self, sender: &PostalConfig) -> pdf::Page {
+        let width = pdf::PAGE_WIDTH - 2.0 * MARGIN;
+        let height = width * 4.125 / 9.5;
+        let bottom = pdf::PAGE_HEIGHT - MARGIN - 40.0 - height;
+
+        let mut page = pdf::Page::new();
+        page.text(
+            MARGIN,
+            pdf::PAGE_HEIGHT - MARGIN,
+            FONT_SIZE,
+            &[
+                "Envelope: cut along the outline and tape to the envelope, or copy the addresses."
+                    .to_string(),
+            ],
+        );
+        page.rect(MARGIN, bottom, width, height);
+
+        let mut from = vec![sender.name.clone()];
+        from.extend(sender.address.iter().cloned());
+        page.text(MARGIN + 14.0, bottom + height - 20.0, 9.0, &from);
+        page.text(
+            MARGIN + width * 0.4,
+            bottom + height * 0.55,
+            12.0,
+            &self.address,
+        );
+        page
+    }
+
+    fn letter_path(&self) -> PathBuf {
+        let date = chrono::Utc::now().format("%Y-%m-%d");
+        let short = &uuid::Uuid::new_v4().simple().to_string()[..8];
+        self.letters_dir
+            .join(format!("{}-{date}-{short}.pdf", self.id))
+    }
+}
+
+/// Registry addresses are single strings with newline-separated lines.
+fn address_lines(address: &str) -> Vec<String> {
+    address
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn file_url(path: &Path) -> String {
+    reqwest::Url::from_file_path(path)
+        .map(|u| u.to_string())
+        .unwrap_or_else(|()| path.display().to_string())
+}
+
+#[async_trait]
+impl BrokerConnector for PostalBroker {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> ConnectorCapabilities {
+        ConnectorCapabilities {
+            can_scan: false,
+            can_delete: self.sender.is_some(),
+            can_check_status: false,
+            deletion_method: Some(DeletionMethod::Postal),
+            batch: true,
+            ..Default::default()
+        }
+    }
+
+    async fn scan(&self, _query: &PersonQuery) -> anyhow::Result<Vec<FoundRecord>> {
+        Err(AppError::Unsupported(format!(
+            "{} only accepts deletion requests by mail",
+            self.name
+        ))
+        .into())
+    }
+
+    async fn request_deletion(
+        &self,
+        _query: &PersonQuery,
+        records: &[FoundRecord],
+    ) -> anyhow::Result<DeletionSubmission> {
+        let sender = self.sender.as_ref().ok_or_else(|| {
+            AppError::Config("Postal deletion requires a [postal] section in config.toml".into())
+        })?;
+
+        std::fs::create_dir_all(&self.letters_dir)?;
+        let path = self.letter_path();
+        std::fs::write(&path, self.render_pdf(sender, records))?;
+
+        Ok(DeletionSubmission {
+            external_ref: format!("POSTAL-{}", uuid::Uuid::new_v4()),
+            message: Some(format!("Letter written to {}", path.display())),
+            manual_steps: vec![ManualStep {
+                key: MAIL_STEP.to_string(),
+                kind: ManualStepKind::PostalMail,
+                instructions: format!(
+                    "Print and sign the letter, mail it to {}, then record the mailing with \
+                     `data-breaker postal log`.",
+                    self.name
+                ),
+                url: Some(file_url(&path)),
+                prefill: vec![("Mail to".to_string(), self.address.join(", "))],
+            }],
+        })
+    }
+
+    async fn check_deletion_status(
+        &self,
+        _external_ref: &str,
+    ) -> anyhow::Result<DeletionStatusCheck> {
+        Err(AppError::Unsupported(format!(
+            "{} replies by mail; its status cannot be checked",
+            self.name
+        ))
+        .into())
+    }
+
+    async fn resume_deletion(
+        &self,
+        _external_ref: &str,
+        step_key: &str,
+        input: Option<&str>,
+    ) -> anyhow::Result<ManualStepOutcome> {
+        if step_key != MAIL_STEP {
+            return Ok(ManualStepOutcome::default());
+        }
+        Ok(ManualStepOutcome {
+            message: Some(match input {
+                Some(tracking) => format!("Letter mailed (tracking {tracking})"),
+                None => "Letter mailed".to_string(),
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+/// The letter template at `path`, or the built-in one. An unreadable
+/// template is warned about rather than failing every command.
+fn load_template(path: Option<&Path>) -> String {
+    let Some(path) = path else {
+        return DEFAULT_TEMPLATE.to_string();
+    };
+    std::fs::read_to_string(path).unwrap_or_else(|e| {
+        tracing::warn!(
+            "Cannot read letter template {}: {e}; using the built-in one",
+            path.display()
+        );
+        DEFAULT_TEMPLATE.to_string()
+    })
+}
+
+/// Register a `PostalBroker` for every registry broker whose connector is
+/// `postal`. Compiled-in connectors with the same ID take precedence.
+pub fn add_postal_connectors(
+    connectors: &mut HashMap<String, Arc<dyn BrokerConnector>>,
+    db: &Database,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let template = load_template(config.postal.as_ref().and_then(|p| p.template.as_deref()));
+    if config.postal.is_none() {
+        tracing::debug!("No [postal] configuration; mail-only brokers cannot receive requests");
+    }
+
+    let letters_dir = crate::config::letters_dir()?;
+    for broker in db.list_brokers(None)? {
+        if broker.connector.as_deref() != Some(POSTAL_CONNECTOR)
+            || connectors.contains_key(&broker.id)
+        {
+            continue;
+        }
+        let Some(address) = &broker.postal_address else {
+            tracing::warn!("Broker '{}' uses postal mail but has no address", broker.id);
+            continue;
+        };
+        connectors.insert(
+            broker.id.clone(),
+            Arc::new(PostalBroker::new(
+                &broker.id,
+                &broker.name,
+                address,
+                config.postal.clone(),
+                template.clone(),
+                letters_dir.clone(),
+            )),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sender() -> PostalConfig {
+        PostalConfig {
+            name: "Jane Doe".into(),
+            address: vec!["1 Main St".into(), "Springfield, IL 62701".into()],
+            email: Some("jane@example.com".into()),
+            template: None,
+        }
+    }
+
+    fn query() -> PersonQuery {
+        PersonQuery {
+            first_name: String::new(),
+            last_name: String::new(),
+            email: None,
+            phone: None,
+            city: None,
+            state: None,
+        }
+    }
+
+    #[test]
+    fn test_render_letter() {
+        let broker = PostalBroker::new(
+            "acme",
+            "Acme Data",
+            "Acme Data Inc.\nPO Box 42\n\nAnytown, CA 90000",
+            None,
+            DEFAULT_TEMPLATE.to_string(),
+            PathBuf::new(),
+        );
+        let lines = broker.render_letter(&sender(), &[]);
+        assert_eq!(lines[0], "Jane Doe");
+        assert!(lines.contains(&"Acme Data Inc.".to_string()));
+        assert!(lines.contains(&"Anytown, CA 90000".to_string()));
+        let text = lines.join("\n");
+        assert!(text.contains("My mailing address: 1 Main St, Springfield, IL 62701"));
+        assert!(text.contains("Contact email: jane@example.com"));
+        assert!(!text.contains("{{"));
+        assert!(lines.iter().all(|l| l.chars().count() <= LINE_WIDTH));
+    }
+
+    #[test]
+    fn test_unreadable_template_falls_back() {
+        let missing = std::env::temp_dir().join(format!("db-missing-{}.txt", uuid::Uuid::new_v4()));
+        assert_eq!(load_template(Some(&missing)), DEFAULT_TEMPLATE);
+    }
+
+    #[tokio::test]
+    async fn test_request_deletion_writes_letter() {
+        let dir = std::env::temp_dir().join(format!("db-letters-{}", uuid::Uuid::new_v4()));
+        let broker = PostalBroker::new(
+            "acme",
+            "Acme Data",
+            "PO Box 42\nAnytown, CA 90000",
+            Some(sender()),
+            DEFAULT_TEMPLATE.to_string(),
+            dir.clone(),
+        );
+        assert!(broker.capabilities().can_delete);
+
+        let submission = broker.request_deletion(&query(), &[]).await.unwrap();
+        assert!(submission.external_ref.starts_with("POSTAL-"));
+        let step = &submission.manual_steps[0];
+        assert_eq!(step.kind, ManualStepKind::PostalMail);
+        assert_eq!(step.key, MAIL_STEP);
+
+        let url = reqwest::Url::parse(step.url.as_deref().unwrap()).unwrap();
+        let pdf = std::fs::read(url.to_file_path().unwrap()).unwrap();
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(String::from_utf8_lossy(&pdf).contains("/Count 2"));
+
+        let outcome = broker
+            .resume_deletion(&submission.external_ref, MAIL_STEP, Some("9400"))
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome.message.as_deref(),
+            Some("Letter mailed (tracking 9400)")
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_capabilities_follow_postal_config() {
+        let broker = PostalBroker::new(
+            "acme",
+            "Acme",
+            "PO Box 1",
+            None,
+            String::new(),
+            PathBuf::new(),
+        );
+        assert!(!broker.capabilities().can_delete);
+        assert!(!broker.capabilities().can_scan);
+    }
+}
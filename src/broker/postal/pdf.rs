@@ -0,0 +1,192 @@
+//! A tiny PDF writer: US Letter pages, Helvetica text and stroked
+//! rectangles. Enough for a printable letter and an envelope label without
+//! pulling in a full layout engine.
+
+/// US Letter, in points.
+pub const PAGE_WIDTH: f32 = 612.0;
+pub const PAGE_HEIGHT: f32 = 792.0;
+
+#[derive(Debug, Default)]
+pub struct Page {
+    content: String,
+}
+
+impl Page {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw lines of text with the first baseline at (`x`, `y`).
+    pub fn text(&mut self, x: f32, y: f32, size: f32, lines: &[String]) {
+        let leading = size * 1.3;
+        self.content
+            .push_str(&format!("BT\n/F1 {size} Tf\n{leading} TL\n{x} {y} Td\n"));
+        for line in lines {
+            self.content
+                .push_str(&format!("({}) Tj\nT*\n", encode_text(line)));
+        }
+        self.content.push_str("ET\n");
+    }
+
+    /// Stroke a rectangle; (`x`, `y`) is the lower-left corner.
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.content
+            .push_str(&format!("0.5 w\n{x} {y} {width} {height} re\nS\n"));
+    }
+}
+
+/// Serialize pages into a complete PDF document.
+pub fn render(pages: &[Page]) -> Vec<u8> {
+    // Object numbers: 1 catalog, 2 page tree, 3 font, then a page and its
+    // content stream for each page.
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", 4 + i * 2))
+        .collect();
+
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    objects.push(
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+    );
+    objects.push(
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+    );
+    for (i, page) in pages.iter().enumerate() {
+        let content_id = 5 + i * 2;
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                 /Resources << /Font << /F1 3 0 R >> >> /Contents {content_id} 0 R >>"
+            )
+            .into_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", page.content.len()).into_bytes();
+        stream.extend_from_slice(page.content.as_bytes());
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        out.extend_from_slice(body);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref_offset = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n", objects.len() + 1).as_bytes());
+    out.extend_from_slice(b"0000000000 65535 f \n");
+    for offset in offsets {
+        out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            objects.len() + 1
+        )
+        .as_bytes(),
+    );
+    out
+}
+
+/// Escape a string for a PDF literal, mapping it onto WinAnsiEncoding.
+/// Latin-1 characters are written as octal escapes; anything else that has
+/// no WinAnsi code becomes `?`.
+fn encode_text(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\u{2018}' | '\u{2019}' => out.push('\''),
+            '\u{201c}' | '\u{201d}' => out.push('"'),
+            '\u{2013}' | '\u{2014}' => out.push('-'),
+            ' '..='~' => out.push(c),
+            '\u{a0}'..='\u{ff}' => out.push_str(&format!("\\{:03o}", c as u32)),
+            '\t' => out.push_str("    "),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+/// Greedy word wrap at `width` characters. Blank lines are kept, and
+/// continuation lines keep the paragraph's indentation.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let indent: String = paragraph
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .collect();
+        let mut current = indent.clone();
+        for word in paragraph.split_whitespace() {
+            let started = current.len() > indent.len();
+            if started && current.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::replace(&mut current, indent.clone()));
+            } else if started {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        if current.trim().is_empty() {
+            current.clear();
+        }
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_structure_and_xref() {
+        let mut page = Page::new();
+        page.text(72.0, 720.0, 11.0, &["Hello (world) \\ café".to_string()]);
+        page.rect(10.0, 10.0, 100.0, 50.0);
+        let pdf = render(&[page, Page::new()]);
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains(r"(Hello \(world\) \\ caf\351) Tj"));
+
+        // Every xref entry must point at the start of its object.
+        let startxref: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|rest| rest.lines().next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        let xref = std::str::from_utf8(&pdf[startxref..]).unwrap();
+        assert!(xref.starts_with("xref\n0 8\n"));
+        let entries: Vec<&str> = xref.lines().skip(3).take(7).collect();
+        for (i, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_wrap() {
+        let lines = wrap("one two three four\n\n  - indented item", 14);
+        assert_eq!(
+            lines,
+            vec!["one two three", "four", "", "  - indented", "  item"]
+        );
+    }
+}
@@ -1,48 +1,1419 @@
+//! The broker registry: which brokers exist and which connector drives each.
+//!
+//! Broker definitions are merged from several sources: the public registry
+//! plus any URLs, files and directories in `[[registry.sources]]`, so a team
+//! can keep an overlay of internal brokers and patched entries on top.
+//!
+//! Since the registry decides what code runs against which sites, a remote
+//! source is only applied when it carries a valid detached minisign
+//! signature (`<url>.minisig`) from one of the pinned [`REGISTRY_KEYS`] or a
+//! key the user trusts in `[registry] trusted_keys`. The signed timestamp is
+//! remembered per source, so an older registry cannot be replayed over a
+//! newer one. Local files are the user's own and are trusted as they are.
+//!
+//! A missing signature, or one from an unknown key, is refused unless the
+//! user turns `[registry] require_signature` off, which only warns about it.
+//! Tampered and rolled-back registries are refused either way.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use minisign_verify::{PublicKey, Signature};
 use serde::Deserialize;
+use serde_json::{Map, Value};
 
-use crate::config::REGISTRY_URL;
+use super::http::HttpService;
+use crate::config::{REGISTRY_KEYS, REGISTRY_URL, RegistryConfig};
 use crate::db::models::Broker;
+use crate::error::AppError;
+
+/// Version of the registry format this build reads, described by
+/// `schema/registry.schema.json`. Bump it only for changes older builds
+/// would misread; new optional fields do not need it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+    #[serde(rename = "$schema")]
+    _schema: Option<String>,
+    schema_version: u32,
+    brokers: Vec<Value>,
+}
 
+/// One broker definition. Everything but `id` may be left to another
+/// source; [`check_complete`] decides whether the merged entry is usable.
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct RegistryBroker {
     id: String,
-    name: String,
+    name: Option<String>,
     website: Option<String>,
     description: Option<String>,
     category: Option<String>,
     connector: Option<String>,
+    privacy_email: Option<String>,
+    postal_address: Option<String>,
+    script: Option<String>,
+    wasm: Option<super::wasm::WasmManifest>,
+    opt_out_url: Option<String>,
+    request_methods: Option<Vec<String>>,
+    verification: Option<Vec<String>>,
+    processing_days: Option<u32>,
+    relisting_days: Option<u32>,
+    jurisdictions: Option<Vec<String>>,
+    parent: Option<String>,
+    shared_database: Option<bool>,
+}
+
+/// Values of `request_methods`.
+pub const REQUEST_METHODS: &[&str] = &["web_form", "email", "postal", "phone"];
+
+/// Values of `verification`.
+pub const VERIFICATION_KINDS: &[&str] =
+    &["email_link", "phone", "id_document", "captcha", "account"];
+
+/// Who signed a registry, and when.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verified {
+    /// The public key that matched.
+    pub key: String,
+    /// Unix time from the signature's trusted comment, if it has one.
+    pub signed_at: Option<i64>,
+}
+
+/// Name of the source for the registry at `REGISTRY_URL`.
+pub const PUBLIC_SOURCE: &str = "public";
+
+/// Name of the snapshot compiled into the binary.
+pub const BUNDLED_SOURCE: &str = "bundled";
+
+/// Name of a registry file imported without a trusted signature.
+pub const IMPORTED_SOURCE: &str = "imported";
+
+/// Which signatures a registry is checked against.
+#[derive(Debug, Clone)]
+pub struct Trust {
+    /// The built-in keys plus those trusted in the config.
+    pub keys: Vec<String>,
+    /// Refuse registries that are unsigned or signed by an unknown key.
+    pub required: bool,
+}
+
+pub fn trust(config: &RegistryConfig) -> Trust {
+    Trust {
+        keys: REGISTRY_KEYS
+            .iter()
+            .map(|k| k.to_string())
+            .chain(config.trusted_keys.iter().cloned())
+            .collect(),
+        required: config.require_signature,
+    }
+}
+
+/// Check `signature` (a `.minisig` file) over `body` against `keys`.
+/// `not_before` is the signing time of the registry applied last; anything
+/// signed earlier is refused as a replay.
+pub fn verify(
+    body: &[u8],
+    signature: &str,
+    keys: &[String],
+    not_before: Option<i64>,
+) -> Result<Verified, AppError> {
+    match verify_known(body, signature, keys, not_before)? {
+        Some(verified) => Ok(verified),
+        None => Err(AppError::Registry(
+            "Registry is signed by a key that is not trusted; add it to [registry] trusted_keys to apply it".to_string(),
+        )),
+    }
+}
+
+/// Check `origin`'s `signature`, if it has one, as `trust` requires. `None`
+/// means the registry was let through unverified.
+pub fn check(
+    body: &[u8],
+    signature: Option<&str>,
+    trust: &Trust,
+    not_before: Option<i64>,
+    origin: &str,
+) -> Result<Option<Verified>, AppError> {
+    let verified = match signature {
+        Some(signature) if trust.required => {
+            return verify(body, signature, &trust.keys, not_before).map(Some);
+        }
+        Some(signature) => verify_known(body, signature, &trust.keys, not_before)?,
+        None if trust.required => {
+            return Err(AppError::Registry(format!(
+                "{origin} is not signed; set [registry] require_signature = false to apply an unsigned registry"
+            )));
+        }
+        None => None,
+    };
+    if verified.is_none() {
+        tracing::warn!(
+            "{origin} is not signed by a trusted key; applying it because [registry] require_signature is off"
+        );
+    }
+    Ok(verified)
+}
+
+/// [`verify`], with `None` for a signature from a key not in `keys`.
+fn verify_known(
+    body: &[u8],
+    signature: &str,
+    keys: &[String],
+    not_before: Option<i64>,
+) -> Result<Option<Verified>, AppError> {
+    let signature = Signature::decode(signature)
+        .map_err(|e| AppError::Registry(format!("Malformed registry signature: {e}")))?;
+
+    let mut matched = None;
+    for key in keys {
+        let public_key = PublicKey::from_base64(key)
+            .map_err(|e| AppError::Config(format!("Invalid registry key '{key}': {e}")))?;
+        match public_key.verify(body, &signature, false) {
+            Ok(()) => {
+                matched = Some(key.clone());
+                break;
+            }
+            // Signed by another key; try the next one.
+            Err(minisign_verify::Error::UnexpectedKeyId) => continue,
+            Err(e) => {
+                return Err(AppError::Registry(format!(
+                    "Registry signature does not match its contents ({e}); refusing a tampered registry"
+                )));
+            }
+        }
+    }
+    let Some(key) = matched else {
+        return Ok(None);
+    };
+
+    let signed_at = signature
+        .trusted_comment()
+        .split('\t')
+        .find_map(|part| part.trim().strip_prefix("timestamp:"))
+        .and_then(|t| t.parse().ok());
+    if let (Some(signed), Some(applied)) = (signed_at, not_before)
+        && signed < applied
+    {
+        return Err(AppError::Registry(format!(
+            "Registry was signed at {signed}, before the one already applied ({applied}); refusing to roll back"
+        )));
+    }
+    Ok(Some(Verified { key, signed_at }))
+}
+
+/// A configured source, ready to load.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    pub name: String,
+    pub location: Location,
+    pub priority: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Location {
+    Url(String),
+    Path(PathBuf),
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Url(url) => f.write_str(url),
+            Location::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// The sources to merge, lowest priority first. Among equal priorities a
+/// source listed later wins.
+pub fn sources(config: &RegistryConfig) -> Result<Vec<Source>, AppError> {
+    let mut sources = Vec::new();
+    if config.public {
+        sources.push(Source {
+            name: PUBLIC_SOURCE.to_string(),
+            location: Location::Url(REGISTRY_URL.to_string()),
+            priority: 0,
+        });
+    }
+    for source in &config.sources {
+        let location = match (&source.url, &source.path) {
+            (Some(url), None) => Location::Url(url.clone()),
+            (None, Some(path)) => Location::Path(path.clone()),
+            _ => {
+                return Err(AppError::Config(format!(
+                    "Registry source '{}' needs either `url` or `path`",
+                    source.name
+                )));
+            }
+        };
+        if sources.iter().any(|s: &Source| s.name == source.name) {
+            return Err(AppError::Config(format!(
+                "Registry source name '{}' is used twice",
+                source.name
+            )));
+        }
+        sources.push(Source {
+            name: source.name.clone(),
+            location,
+            priority: source.priority,
+        });
+    }
+    sources.sort_by_key(|s| s.priority);
+    Ok(sources)
+}
+
+/// Broker entries as read from one source.
+#[derive(Debug)]
+pub struct Layer {
+    pub source: String,
+    pub entries: Vec<Map<String, Value>>,
+    /// Entries that failed validation and were left out.
+    pub rejected: Vec<Rejected>,
+    /// Set for remote sources, whose signature was checked.
+    pub verified: Option<Verified>,
+    /// SHA-256 of what was read.
+    pub version: String,
+    /// What to send a conditional request against next time.
+    pub cached: Option<Cached>,
+    /// The server had nothing newer than `cached`.
+    pub not_modified: bool,
+}
+
+impl Layer {
+    fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            entries: vec![],
+            rejected: vec![],
+            verified: None,
+            version: String::new(),
+            cached: None,
+            not_modified: false,
+        }
+    }
+
+    fn with_entries(mut self, entries: Entries) -> Self {
+        self.entries = entries.valid;
+        self.rejected = entries.rejected;
+        self
+    }
+}
+
+/// A broker definition left out of the registry, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejected {
+    /// File, URL or source the entry came from.
+    pub origin: String,
+    /// Position in the origin's `brokers` list, if known.
+    pub index: Option<usize>,
+    pub id: Option<String>,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.origin)?;
+        if let Some(index) = self.index {
+            write!(f, " entry {index}")?;
+        }
+        if let Some(id) = &self.id {
+            write!(f, " ('{id}')")?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Entries that passed validation, and those that did not.
+#[derive(Debug, Default)]
+pub struct Entries {
+    pub valid: Vec<Map<String, Value>>,
+    pub rejected: Vec<Rejected>,
+}
+
+/// A remote source's last response, replayed when the server answers a
+/// conditional request with 304 Not Modified.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cached {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: String,
+    pub signature: String,
+}
+
+/// Read one source. Remote ones are checked against `trust`; `not_before`
+/// is the signing time of what was last applied from the same source, and
+/// `cached` what it returned then.
+pub async fn load(
+    http: &HttpService,
+    source: &Source,
+    trust: &Trust,
+    not_before: Option<i64>,
+    cached: Option<&Cached>,
+) -> anyhow::Result<Layer> {
+    let mut layer = Layer::new(&source.name);
+    let entries = match &source.location {
+        Location::Url(url) => {
+            let (fetched, not_modified) = fetch_signed(http, url, trust, cached).await?;
+            let body = fetched.body.as_bytes();
+            let signature = Some(fetched.signature.as_str()).filter(|s| !s.is_empty());
+            layer.verified = check(body, signature, trust, not_before, url)?;
+            layer.version = super::wasm::sha256_hex(body);
+            layer.not_modified = not_modified;
+            let entries = entries(body, url, false)?;
+            layer.cached = Some(fetched);
+            entries
+        }
+        Location::Path(path) => {
+            let files = if path.is_dir() {
+                let mut files: Vec<PathBuf> = std::fs::read_dir(path)?
+                    .map(|e| e.map(|e| e.path()))
+                    .collect::<Result<_, _>>()?;
+                files.retain(|p| p.extension().is_some_and(|e| e == "json"));
+                files.sort();
+                files
+            } else {
+                vec![path.clone()]
+            };
+            let mut all = Entries::default();
+            let mut bodies = Vec::new();
+            for file in files {
+                let body = std::fs::read(&file).map_err(|e| {
+                    AppError::Registry(format!("Cannot read {}: {e}", file.display()))
+                })?;
+                let entries = entries(&body, &file.display().to_string(), false)?;
+                all.valid.extend(entries.valid);
+                all.rejected.extend(entries.rejected);
+                bodies.extend(body);
+            }
+            layer.version = super::wasm::sha256_hex(&bodies);
+            all
+        }
+    };
+    Ok(layer.with_entries(entries))
 }
 
-/// Fetch the broker registry from the remote URL and return Broker models.
-pub async fn fetch_registry() -> anyhow::Result<Vec<Broker>> {
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(REGISTRY_URL)
-        .header("User-Agent", "data-breaker")
-        .send()
-        .await?;
+/// The registry snapshot shipped in the binary.
+const SNAPSHOT: &str = include_str!("../../registry/snapshot.json");
 
+/// The bundled snapshot, used until the public registry is first fetched.
+pub fn bundled() -> Result<Layer, AppError> {
+    let mut layer = Layer::new(BUNDLED_SOURCE);
+    layer.version = super::wasm::sha256_hex(SNAPSHOT.as_bytes());
+    Ok(layer.with_entries(entries(SNAPSHOT.as_bytes(), "bundled snapshot", false)?))
+}
+
+/// Read a registry file copied from elsewhere. With a `.minisig` next to it
+/// that verifies against a trusted key, it stands in for a download of the
+/// public registry. Anything else is only read with `allow_unsigned`, and
+/// then as [`IMPORTED_SOURCE`], to go on top of the public registry rather
+/// than replace it.
+pub fn load_file(
+    path: &Path,
+    trust: &Trust,
+    not_before: Option<i64>,
+    allow_unsigned: bool,
+) -> anyhow::Result<Layer> {
+    let body = std::fs::read(path)
+        .map_err(|e| AppError::Registry(format!("Cannot read {}: {e}", path.display())))?;
+    let mut signature_path = path.as_os_str().to_owned();
+    signature_path.push(".minisig");
+    let signature = std::fs::read_to_string(&signature_path).ok();
+    let verified = match &signature {
+        Some(signature) => verify_known(&body, signature, &trust.keys, not_before)?,
+        None => None,
+    };
+
+    let mut layer = match verified {
+        Some(verified) => {
+            let mut layer = Layer::new(PUBLIC_SOURCE);
+            layer.verified = Some(verified);
+            layer.cached = signature.map(|signature| Cached {
+                body: String::from_utf8_lossy(&body).into_owned(),
+                signature,
+                ..Default::default()
+            });
+            layer
+        }
+        None if allow_unsigned => Layer::new(IMPORTED_SOURCE),
+        None => {
+            let problem = match signature {
+                Some(_) => "is signed by a key that is not trusted",
+                None => "has no signature next to it",
+            };
+            return Err(AppError::Registry(format!(
+                "{} {problem}; pass --trust-unsigned to import it as source '{IMPORTED_SOURCE}' on top of the public registry",
+                path.display()
+            ))
+            .into());
+        }
+    };
+    layer.version = super::wasm::sha256_hex(&body);
+    let entries = entries(&body, &path.display().to_string(), false)?;
+    Ok(layer.with_entries(entries))
+}
+
+/// What `source` served last, rebuilt from its cached response without
+/// going to the network.
+pub fn from_cache(source: &str, cached: Cached, trust: &Trust) -> anyhow::Result<Layer> {
+    let mut layer = Layer::new(source);
+    let body = cached.body.as_bytes();
+    let signature = Some(cached.signature.as_str()).filter(|s| !s.is_empty());
+    layer.verified = check(body, signature, trust, None, source)?;
+    layer.version = super::wasm::sha256_hex(body);
+    layer.not_modified = true;
+    let entries = entries(body, source, false)?;
+    layer.cached = Some(cached);
+    Ok(layer.with_entries(entries))
+}
+
+/// Fetch a registry and its signature, or reuse `cached` if the server says
+/// it has not changed. Returns what to cache and whether it was reused; the
+/// signature is left empty if there is none and `trust` allows that.
+async fn fetch_signed(
+    http: &HttpService,
+    url: &str,
+    trust: &Trust,
+    cached: Option<&Cached>,
+) -> anyhow::Result<(Cached, bool)> {
+    let client = http.builder().build()?;
+    let mut request = client.get(url);
+    if let Some(cached) = cached {
+        if let Some(etag) = &cached.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cached.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let resp = request.send().await?;
+    if let Some(cached) = cached
+        && resp.status() == reqwest::StatusCode::NOT_MODIFIED
+    {
+        return Ok((cached.clone(), true));
+    }
     if !resp.status().is_success() {
-        anyhow::bail!("Failed to fetch registry: HTTP {}", resp.status());
+        anyhow::bail!("Failed to fetch registry {url}: HTTP {}", resp.status());
     }
+    let header = |name| {
+        resp.headers()
+            .get(name)
+            .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = header(reqwest::header::ETAG);
+    let last_modified = header(reqwest::header::LAST_MODIFIED);
+    let body = String::from_utf8(resp.bytes().await?.to_vec())
+        .map_err(|e| AppError::Registry(format!("Invalid registry {url}: {e}")))?;
 
-    let registry_brokers: Vec<RegistryBroker> = resp.json().await?;
-    let now = chrono::Utc::now().to_rfc3339();
+    let resp = client.get(format!("{url}.minisig")).send().await?;
+    let signature = if resp.status().is_success() {
+        resp.text().await?
+    } else if trust.required {
+        return Err(AppError::Registry(format!(
+            "Signature of {url} unavailable (HTTP {}); set [registry] require_signature = false to apply an unsigned registry",
+            resp.status()
+        ))
+        .into());
+    } else {
+        String::new()
+    };
+    let fetched = Cached {
+        etag,
+        last_modified,
+        body,
+        signature,
+    };
+    Ok((fetched, false))
+}
 
-    let brokers = registry_brokers
-        .into_iter()
-        .map(|rb| Broker {
+/// The broker objects of a registry document that pass validation. Overlays
+/// may leave out fields, so unless `complete` is set completeness is only
+/// checked after merging. A bare array or a single broker object is read as
+/// version 1. Only problems with the document as a whole are errors.
+fn entries(body: &[u8], origin: &str, complete: bool) -> Result<Entries, AppError> {
+    let value: Value = serde_json::from_slice(body)
+        .map_err(|e| AppError::Registry(format!("Invalid registry {origin}: {e}")))?;
+    let items = match value {
+        Value::Object(map) if map.contains_key("schema_version") => {
+            let document: Document = serde_json::from_value(Value::Object(map))
+                .map_err(|e| AppError::Registry(format!("Invalid registry {origin}: {e}")))?;
+            if document.schema_version == 0 || document.schema_version > SCHEMA_VERSION {
+                return Err(AppError::Registry(format!(
+                    "Registry {origin} uses schema version {}, but this version of data-breaker reads up to {SCHEMA_VERSION}; please upgrade",
+                    document.schema_version
+                )));
+            }
+            document.brokers
+        }
+        Value::Array(items) => items,
+        object @ Value::Object(_) => vec![object],
+        _ => {
+            return Err(AppError::Registry(format!(
+                "Invalid registry {origin}: not a registry document"
+            )));
+        }
+    };
+
+    let mut entries = Entries::default();
+    for (index, item) in items.into_iter().enumerate() {
+        let id = item.get("id").and_then(Value::as_str).map(str::to_string);
+        let reject = |reason: String| Rejected {
+            origin: origin.to_string(),
+            index: Some(index),
+            id: id.clone(),
+            reason,
+        };
+        let Value::Object(map) = item else {
+            entries.rejected.push(reject("not an object".to_string()));
+            continue;
+        };
+        let checked = check_entry(&map).and_then(|entry| {
+            if complete {
+                check_complete(&entry)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(reason) = checked {
+            entries.rejected.push(reject(reason));
+        } else if entries.valid.iter().any(|e| e.get("id") == map.get("id")) {
+            entries.rejected.push(reject("duplicate id".to_string()));
+        } else {
+            entries.valid.push(map);
+        }
+    }
+    Ok(entries)
+}
+
+/// Types, unknown fields and the format of the fields that are present.
+fn check_entry(map: &Map<String, Value>) -> Result<RegistryBroker, String> {
+    let entry: RegistryBroker =
+        serde_json::from_value(Value::Object(map.clone())).map_err(|e| e.to_string())?;
+    let is_id = |id: &str| {
+        !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    };
+    if !is_id(&entry.id) {
+        return Err("`id` must be lowercase letters, digits, '-' or '_'".to_string());
+    }
+    if let Some(parent) = &entry.parent
+        && !is_id(parent)
+    {
+        return Err(format!("`parent` is not a broker id: {parent}"));
+    }
+    for (field, url) in [
+        ("website", &entry.website),
+        ("opt_out_url", &entry.opt_out_url),
+    ] {
+        let Some(url) = url else { continue };
+        match reqwest::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+            _ => return Err(format!("`{field}` is not an http(s) URL: {url}")),
+        }
+    }
+    for (field, values, known) in [
+        ("request_methods", &entry.request_methods, REQUEST_METHODS),
+        ("verification", &entry.verification, VERIFICATION_KINDS),
+    ] {
+        if let Some(unknown) = values
+            .iter()
+            .flatten()
+            .find(|v| !known.contains(&v.as_str()))
+        {
+            return Err(format!(
+                "unknown `{field}` value '{unknown}' (expected one of {})",
+                known.join(", ")
+            ));
+        }
+    }
+    for jurisdiction in entry.jurisdictions.iter().flatten() {
+        let (country, region) = jurisdiction
+            .split_once('-')
+            .map_or((jurisdiction.as_str(), None), |(c, r)| (c, Some(r)));
+        let valid = country.len() == 2
+            && country.chars().all(|c| c.is_ascii_uppercase())
+            && region.is_none_or(|r| {
+                (1..=3).contains(&r.len())
+                    && r.chars()
+                        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+            });
+        if !valid {
+            return Err(format!(
+                "`jurisdictions` entries look like 'US' or 'US-CA', not '{jurisdiction}'"
+            ));
+        }
+    }
+    if let Some(email) = &entry.privacy_email
+        && (!email.contains('@') || email.contains(char::is_whitespace))
+    {
+        return Err(format!("`privacy_email` is not an email address: {email}"));
+    }
+    if let Some(wasm) = &entry.wasm
+        && (wasm.sha256.len() != 64 || !wasm.sha256.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Err("`wasm.sha256` must be 64 hex digits".to_string());
+    }
+    Ok(entry)
+}
+
+/// What a merged entry needs before it can become a broker.
+fn check_complete(entry: &RegistryBroker) -> Result<(), String> {
+    if entry.name.as_deref().is_none_or(str::is_empty) {
+        return Err("missing required field `name`".to_string());
+    }
+    if entry.shared_database == Some(true) && entry.parent.is_none() {
+        return Err("`shared_database` requires `parent`".to_string());
+    }
+    let required = match entry.connector.as_deref() {
+        Some(super::script::SCRIPT_CONNECTOR) => ("script", entry.script.is_some()),
+        Some(super::wasm::WASM_CONNECTOR) => ("wasm", entry.wasm.is_some()),
+        Some(super::email::EMAIL_CONNECTOR) => ("privacy_email", entry.privacy_email.is_some()),
+        Some(super::postal::POSTAL_CONNECTOR) => ("postal_address", entry.postal_address.is_some()),
+        _ => return Ok(()),
+    };
+    match required {
+        (field, false) => Err(format!(
+            "connector '{}' requires `{field}`",
+            entry.connector.as_deref().unwrap_or_default()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Validate a registry file as contributors submit it: every entry must be
+/// complete on its own.
+pub fn validate_file(path: &Path) -> anyhow::Result<Entries> {
+    let body = std::fs::read(path)
+        .map_err(|e| AppError::Registry(format!("Cannot read {}: {e}", path.display())))?;
+    Ok(entries(&body, &path.display().to_string(), true)?)
+}
+
+/// Brokers merged from all sources, and the definitions left out.
+#[derive(Debug, Default)]
+pub struct Merged {
+    pub brokers: Vec<Broker>,
+    pub rejected: Vec<Rejected>,
+}
+
+/// Merge layers given lowest priority first. Entries are matched by `id`;
+/// a later entry overrides field by field, so an overlay can patch a single
+/// field of a public entry, and `null` clears one. Brokers that end up
+/// incomplete are left out.
+pub fn merge(layers: &[Layer]) -> anyhow::Result<Merged> {
+    let mut merged: Vec<(String, Map<String, Value>, Vec<String>)> = Vec::new();
+    for layer in layers {
+        for entry in &layer.entries {
+            // Entries are validated on load, so they all have an `id`.
+            let id = entry.get("id").and_then(Value::as_str).unwrap_or_default();
+            match merged.iter_mut().find(|(i, ..)| i == id) {
+                Some((_, fields, sources)) => {
+                    fields.extend(entry.clone());
+                    if !sources.contains(&layer.source) {
+                        sources.insert(0, layer.source.clone());
+                    }
+                }
+                None => merged.push((id.to_string(), entry.clone(), vec![layer.source.clone()])),
+            }
+        }
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut result = Merged::default();
+    for (id, fields, sources) in merged {
+        let origin = sources.join(", ");
+        let checked = check_entry(&fields).and_then(|rb| check_complete(&rb).map(|()| rb));
+        let rb = match checked {
+            Ok(rb) => rb,
+            Err(reason) => {
+                result.rejected.push(Rejected {
+                    origin,
+                    index: None,
+                    id: Some(id),
+                    reason,
+                });
+                continue;
+            }
+        };
+        result.brokers.push(Broker {
             id: rb.id,
-            name: rb.name,
+            name: rb.name.unwrap_or_default(),
             website: rb.website,
             description: rb.description,
             category: rb.category,
             connector: rb.connector,
+            privacy_email: rb.privacy_email,
+            postal_address: rb.postal_address,
+            script: rb.script,
+            wasm_manifest: rb.wasm.as_ref().map(serde_json::to_string).transpose()?,
+            opt_out_url: rb.opt_out_url,
+            request_methods: rb.request_methods.unwrap_or_default(),
+            verification: rb.verification.unwrap_or_default(),
+            processing_days: rb.processing_days.map(i64::from),
+            relisting_days: rb.relisting_days.map(i64::from),
+            jurisdictions: rb.jurisdictions.unwrap_or_default(),
+            parent_id: rb.parent,
+            shared_database: rb.shared_database.unwrap_or_default(),
+            registry_source: Some(origin),
             registry_updated_at: Some(now.clone()),
+            retired_at: None,
             created_at: now.clone(),
             updated_at: now.clone(),
-        })
+        });
+    }
+
+    // A parent must be a broker of the same registry, and families cannot
+    // loop back on themselves.
+    let parents: HashMap<String, Option<String>> = result
+        .brokers
+        .iter()
+        .map(|b| (b.id.clone(), b.parent_id.clone()))
+        .collect();
+    let family_error = |broker: &Broker| {
+        let mut parent = broker.parent_id.as_ref()?;
+        for _ in 0..parents.len() {
+            if parent == &broker.id {
+                return Some(format!("`parent` '{parent}' makes a cycle"));
+            }
+            match parents.get(parent) {
+                Some(Some(next)) => parent = next,
+                Some(None) => return None,
+                None => return Some(format!("`parent` '{parent}' is not a known broker")),
+            }
+        }
+        Some("`parent` makes a cycle".to_string())
+    };
+    let (brokers, orphans): (Vec<_>, Vec<_>) = std::mem::take(&mut result.brokers)
+        .into_iter()
+        .map(|b| (family_error(&b), b))
+        .partition(|(error, _)| error.is_none());
+    result.brokers = brokers.into_iter().map(|(_, b)| b).collect();
+    for (reason, broker) in orphans {
+        result.rejected.push(Rejected {
+            origin: broker.registry_source.unwrap_or_default(),
+            index: None,
+            id: Some(broker.id),
+            reason: reason.unwrap_or_default(),
+        });
+    }
+    Ok(result)
+}
+
+/// A field that differs between the stored and the incoming definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// How a merged registry differs from the brokers stored before.
+#[derive(Debug, Default)]
+pub struct Diff {
+    pub added: Vec<Broker>,
+    pub changed: Vec<(Broker, Vec<FieldChange>)>,
+    /// Gone from the registry; to be retired.
+    pub removed: Vec<Broker>,
+    /// Retired before, back in the registry now.
+    pub restored: Vec<Broker>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.changed.is_empty()
+            && self.removed.is_empty()
+            && self.restored.is_empty()
+    }
+}
+
+/// Compare `incoming` with the registry brokers in the database, retired
+/// ones included.
+pub fn diff(stored: &[Broker], incoming: Vec<Broker>) -> Diff {
+    let mut diff = Diff::default();
+    for broker in &incoming {
+        match stored.iter().find(|s| s.id == broker.id) {
+            None => diff.added.push(broker.clone()),
+            Some(old) if old.retired_at.is_some() => diff.restored.push(broker.clone()),
+            Some(old) => {
+                let changes: Vec<FieldChange> = fields(old)
+                    .into_iter()
+                    .zip(fields(broker))
+                    .filter(|((_, old), (_, new))| old != new)
+                    .map(|((field, old), (_, new))| FieldChange { field, old, new })
+                    .collect();
+                if !changes.is_empty() {
+                    diff.changed.push((broker.clone(), changes));
+                }
+            }
+        }
+    }
+    diff.removed = stored
+        .iter()
+        .filter(|s| s.retired_at.is_none() && !incoming.iter().any(|b| b.id == s.id))
+        .cloned()
         .collect();
+    diff
+}
+
+/// The fields a change report compares, connector first since a change
+/// there swaps the code that runs against the broker.
+fn fields(broker: &Broker) -> [(&'static str, Option<String>); 18] {
+    let list = |values: &[String]| Some(values.join(", ")).filter(|v| !v.is_empty());
+    [
+        ("connector", broker.connector.clone()),
+        ("name", Some(broker.name.clone())),
+        ("website", broker.website.clone()),
+        ("description", broker.description.clone()),
+        ("category", broker.category.clone()),
+        ("privacy_email", broker.privacy_email.clone()),
+        ("postal_address", broker.postal_address.clone()),
+        ("opt_out_url", broker.opt_out_url.clone()),
+        ("request_methods", list(&broker.request_methods)),
+        ("verification", list(&broker.verification)),
+        (
+            "processing_days",
+            broker.processing_days.map(|d| d.to_string()),
+        ),
+        (
+            "relisting_days",
+            broker.relisting_days.map(|d| d.to_string()),
+        ),
+        ("jurisdictions", list(&broker.jurisdictions)),
+        ("parent", broker.parent_id.clone()),
+        ("shared_database", Some(broker.shared_database.to_string())),
+        ("script", broker.script.clone()),
+        ("wasm", broker.wasm_manifest.clone()),
+        ("sources", broker.registry_source.clone()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use blake2::{Blake2b512, Digest};
+    use ed25519_dalek::{Signer as _, SigningKey};
+
+    use super::*;
+    use crate::config::RegistrySource;
+
+    /// Signs like `minisign -S` (pre-hashed), from a fixed seed.
+    struct Signer {
+        key: SigningKey,
+        id: [u8; 8],
+    }
+
+    impl Signer {
+        fn new(seed: u8) -> Self {
+            Self {
+                key: SigningKey::from_bytes(&[seed; 32]),
+                id: [seed; 8],
+            }
+        }
+
+        fn public(&self) -> String {
+            let key = self.key.verifying_key();
+            STANDARD.encode([&b"Ed"[..], &self.id, key.as_bytes()].concat())
+        }
+
+        fn sign(&self, body: &[u8], timestamp: i64) -> String {
+            let signature = self.key.sign(&Blake2b512::digest(body)).to_bytes();
+            let trusted = format!("timestamp:{timestamp}\tfile:brokers.json\thashed");
+            let global = self
+                .key
+                .sign(&[&signature[..], trusted.as_bytes()].concat())
+                .to_bytes();
+            format!(
+                "untrusted comment: signature from test key\n{}\ntrusted comment: {trusted}\n{}\n",
+                STANDARD.encode([&b"ED"[..], &self.id, &signature].concat()),
+                STANDARD.encode(global)
+            )
+        }
+    }
+
+    const BODY: &[u8] =
+        br#"{"schema_version": 1, "brokers": [{"id": "acme", "name": "Acme Data"}]}"#;
+
+    fn registry_error(err: AppError) -> String {
+        match err {
+            AppError::Registry(message) => message,
+            other => panic!("expected a registry error, got {other}"),
+        }
+    }
+
+    #[test]
+    fn test_rotated_key_is_accepted() {
+        let (old, new) = (Signer::new(1), Signer::new(2));
+        let keys = vec![old.public(), new.public()];
+
+        let verified = verify(BODY, &old.sign(BODY, 100), &keys, None).unwrap();
+        assert_eq!(verified.key, old.public());
+        assert_eq!(verified.signed_at, Some(100));
+
+        let verified = verify(BODY, &new.sign(BODY, 200), &keys, Some(100)).unwrap();
+        assert_eq!(verified.key, new.public());
+        let mut layer =
+            Layer::new(PUBLIC_SOURCE).with_entries(entries(BODY, "test", false).unwrap());
+        layer.verified = Some(verified);
+        assert_eq!(merge(&[layer]).unwrap().brokers[0].name, "Acme Data");
+    }
+
+    #[test]
+    fn test_bad_registries_are_refused() {
+        let signer = Signer::new(1);
+        let keys = vec![signer.public()];
+        let signature = signer.sign(BODY, 100);
+
+        let tampered = BODY.to_vec().repeat(2);
+        let err = registry_error(verify(&tampered, &signature, &keys, None).unwrap_err());
+        assert!(err.contains("tampered"), "{err}");
+
+        let stranger = Signer::new(9);
+        let err = registry_error(verify(BODY, &stranger.sign(BODY, 100), &keys, None).unwrap_err());
+        assert!(err.contains("not trusted"), "{err}");
+
+        let err = registry_error(verify(BODY, &signature, &keys, Some(101)).unwrap_err());
+        assert!(err.contains("roll back"), "{err}");
+
+        let err = registry_error(verify(BODY, "not a signature", &keys, None).unwrap_err());
+        assert!(err.contains("Malformed"), "{err}");
+
+        assert!(matches!(
+            verify(BODY, &signature, &["RWbogus".to_string()], None),
+            Err(AppError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_pinned_keys_parse() {
+        let trust = trust(&RegistryConfig {
+            trusted_keys: vec![Signer::new(3).public()],
+            ..Default::default()
+        });
+        assert_eq!(trust.keys.len(), REGISTRY_KEYS.len() + 1);
+        for key in trust.keys {
+            PublicKey::from_base64(&key).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_pinned_keys_are_not_samples() {
+        // Public keys printed in the minisign and minisign-verify docs.
+        const SAMPLES: &[&str] = &[
+            "RWQf6LRCGA9i5yq1ThTMYhO0AaN3SWJnmgv3LruqA9fmDRkvVl7nZ4Lj",
+            "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3",
+            "RWQf6LRCGA9i59SLOFxz6NxvASXDJeRtuZykwQepbDEGt87ig1BNpWaV",
+        ];
+        for key in REGISTRY_KEYS {
+            assert!(!SAMPLES.contains(key), "{key} is a documentation sample");
+        }
+        assert!(RegistryConfig::default().require_signature);
+    }
+
+    #[test]
+    fn test_default_config_refuses_unverified_registries() {
+        let config: RegistryConfig = toml::from_str("").unwrap();
+        assert!(config.require_signature);
+        let trust = trust(&config);
+        let signer = Signer::new(1);
+        let signature = signer.sign(BODY, 100);
 
-    Ok(brokers)
+        let err = registry_error(check(BODY, None, &trust, None, "test").unwrap_err());
+        assert!(err.contains("not signed"), "{err}");
+        let err = registry_error(check(BODY, Some(&signature), &trust, None, "test").unwrap_err());
+        assert!(err.contains("not trusted"), "{err}");
+        let tampered = BODY.repeat(2);
+        assert!(check(&tampered, Some(&signature), &trust, None, "test").is_err());
+
+        // A key the user trusts lets its registries through, but not
+        // tampered copies of them.
+        let trust = super::trust(&RegistryConfig {
+            trusted_keys: vec![signer.public()],
+            ..config
+        });
+        assert!(
+            check(BODY, Some(&signature), &trust, None, "test")
+                .unwrap()
+                .is_some()
+        );
+        let err =
+            registry_error(check(&tampered, Some(&signature), &trust, None, "test").unwrap_err());
+        assert!(err.contains("tampered"), "{err}");
+
+        // Opting out applies unsigned registries.
+        let config: RegistryConfig = toml::from_str("require_signature = false").unwrap();
+        assert_eq!(
+            check(BODY, None, &super::trust(&config), None, "test").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unenforced_signatures_warn_but_refuse_tampering() {
+        let signer = Signer::new(1);
+        let signature = signer.sign(BODY, 100);
+        let mut trust = Trust {
+            keys: vec![],
+            required: false,
+        };
+        assert_eq!(check(BODY, None, &trust, None, "test").unwrap(), None);
+        assert_eq!(
+            check(BODY, Some(&signature), &trust, None, "test").unwrap(),
+            None
+        );
+
+        trust.keys.push(signer.public());
+        let verified = check(BODY, Some(&signature), &trust, None, "test").unwrap();
+        assert_eq!(verified.unwrap().signed_at, Some(100));
+        let tampered = BODY.repeat(2);
+        assert!(check(&tampered, Some(&signature), &trust, None, "test").is_err());
+        assert!(check(BODY, Some(&signature), &trust, Some(101), "test").is_err());
+
+        trust.required = true;
+        let err = registry_error(check(BODY, None, &trust, None, "test").unwrap_err());
+        assert!(err.contains("not signed"), "{err}");
+        let stranger = Signer::new(9).sign(BODY, 100);
+        let err = registry_error(check(BODY, Some(&stranger), &trust, None, "test").unwrap_err());
+        assert!(err.contains("not trusted"), "{err}");
+    }
+
+    #[test]
+    fn test_unsigned_file_is_imported_separately() {
+        let dir = std::env::temp_dir().join(format!("db-import-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("brokers.json");
+        std::fs::write(&path, BODY).unwrap();
+        let signer = Signer::new(1);
+        let trust = Trust {
+            keys: vec![signer.public()],
+            required: false,
+        };
+
+        let err = load_file(&path, &trust, None, false).unwrap_err();
+        assert!(err.to_string().contains("--trust-unsigned"), "{err}");
+        let layer = load_file(&path, &trust, None, true).unwrap();
+        assert_eq!(layer.source, IMPORTED_SOURCE);
+        assert!(layer.verified.is_none());
+
+        let signature = dir.join("brokers.json.minisig");
+        std::fs::write(&signature, Signer::new(9).sign(BODY, 100)).unwrap();
+        let err = load_file(&path, &trust, None, false).unwrap_err();
+        assert!(err.to_string().contains("not trusted"), "{err}");
+
+        std::fs::write(&signature, signer.sign(BODY, 100)).unwrap();
+        let layer = load_file(&path, &trust, None, false).unwrap();
+        assert_eq!(layer.source, PUBLIC_SOURCE);
+        assert!(layer.verified.is_some());
+        let cached = layer.cached.unwrap();
+        let again = from_cache(PUBLIC_SOURCE, cached, &trust).unwrap();
+        assert_eq!(again.entries, layer.entries);
+
+        std::fs::write(&path, BODY.repeat(2)).unwrap();
+        assert!(load_file(&path, &trust, None, true).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn layer(source: &str, body: &str) -> Layer {
+        Layer::new(source).with_entries(entries(body.as_bytes(), source, false).unwrap())
+    }
+
+    #[test]
+    fn test_overlay_patches_fields_by_priority() {
+        let public = layer(
+            PUBLIC_SOURCE,
+            r#"[{"id": "acme", "name": "Acme Data", "connector": "email", "privacy_email": "privacy@acme.test"},
+                {"id": "beta", "name": "Beta People"}]"#,
+        );
+        let team = layer(
+            "team",
+            r#"[{"id": "acme", "privacy_email": "dpo@acme.test", "description": null},
+                {"id": "internal", "name": "Internal Broker"}]"#,
+        );
+
+        let brokers = merge(&[public, team]).unwrap().brokers;
+        assert_eq!(brokers.len(), 3);
+        let acme = &brokers[0];
+        assert_eq!(acme.name, "Acme Data");
+        assert_eq!(acme.connector.as_deref(), Some("email"));
+        assert_eq!(acme.privacy_email.as_deref(), Some("dpo@acme.test"));
+        assert_eq!(acme.registry_source.as_deref(), Some("team, public"));
+        assert_eq!(brokers[1].registry_source.as_deref(), Some("public"));
+        assert_eq!(brokers[2].registry_source.as_deref(), Some("team"));
+
+        // A patch for a broker nobody defined lacks the required fields.
+        let orphan = layer(
+            "team",
+            r#"{"id": "ghost", "privacy_email": "x@ghost.test"}"#,
+        );
+        let merged = merge(&[orphan]).unwrap();
+        assert!(merged.brokers.is_empty());
+        assert_eq!(merged.rejected[0].id.as_deref(), Some("ghost"));
+    }
+
+    #[test]
+    fn test_sources_are_ordered_and_checked() {
+        let source = |name: &str, priority| RegistrySource {
+            name: name.to_string(),
+            url: None,
+            path: Some(PathBuf::from(format!("/tmp/{name}"))),
+            priority,
+        };
+        let config = RegistryConfig {
+            sources: vec![source("team", 20), source("mirror", 5)],
+            ..Default::default()
+        };
+        let names: Vec<_> = sources(&config)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["public", "mirror", "team"]);
+
+        let config = RegistryConfig {
+            sources: vec![source("public", 20)],
+            ..Default::default()
+        };
+        assert!(matches!(sources(&config), Err(AppError::Config(_))));
+
+        let mut both = source("both", 1);
+        both.url = Some("https://example.test/brokers.json".to_string());
+        let config = RegistryConfig {
+            public: false,
+            sources: vec![both],
+            ..Default::default()
+        };
+        assert!(matches!(sources(&config), Err(AppError::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_load_directory_source() {
+        let dir = std::env::temp_dir().join(format!("registry-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("b.json"),
+            r#"{"id": "acme", "website": "https://acme.test"}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("a.json"), r#"[{"id": "acme", "name": "Acme"}]"#).unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let http = HttpService::new(&Default::default()).unwrap();
+        let source = Source {
+            name: "local".to_string(),
+            location: Location::Path(dir.clone()),
+            priority: 1,
+        };
+        let trust = Trust {
+            keys: vec![],
+            required: true,
+        };
+        let layer = load(&http, &source, &trust, None, None).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(layer.verified.is_none());
+        let brokers = merge(&[layer]).unwrap().brokers;
+        assert_eq!(brokers[0].website.as_deref(), Some("https://acme.test"));
+    }
+
+    #[test]
+    fn test_bad_entries_are_skipped() {
+        let body = br#"{"schema_version": 1, "brokers": [
+            {"id": "good", "name": "Good"},
+            {"id": "typo", "name": "Typo", "webiste": "https://typo.test"},
+            {"id": "Bad Id", "name": "Bad"},
+            {"id": "mail", "name": "Mail", "privacy_email": "not an address"},
+            "acme",
+            {"id": "good", "name": "Again"}
+        ]}"#;
+        let read = entries(body, "brokers.json", false).unwrap();
+        assert_eq!(read.valid.len(), 1);
+        let rejected: Vec<_> = read.rejected.iter().map(|r| r.index.unwrap()).collect();
+        assert_eq!(rejected, [1, 2, 3, 4, 5]);
+        assert!(read.rejected[0].reason.contains("webiste"));
+        assert_eq!(
+            read.rejected[4].to_string(),
+            "brokers.json entry 5 ('good'): duplicate id"
+        );
+
+        let layer = layer("team", r#"{"id": "s", "name": "S", "connector": "script"}"#);
+        let merged = merge(&[layer]).unwrap();
+        assert!(merged.rejected[0].reason.contains("requires `script`"));
+
+        let err = entries(
+            br#"{"schema_version": 2, "brokers": []}"#,
+            "new.json",
+            false,
+        )
+        .unwrap_err();
+        assert!(registry_error(err).contains("upgrade"));
+    }
+
+    #[test]
+    fn test_schema_matches_entries() {
+        let schema: Value =
+            serde_json::from_str(include_str!("../../schema/registry.schema.json")).unwrap();
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            SCHEMA_VERSION
+        );
+        // Every property the schema allows is one the parser accepts.
+        let properties = schema["$defs"]["broker"]["properties"].as_object().unwrap();
+        let mut entry: Map<String, Value> = properties
+            .keys()
+            .map(|k| (k.clone(), Value::Null))
+            .collect();
+        entry.insert("id".into(), "acme".into());
+        entry.insert("name".into(), "Acme".into());
+        check_entry(&entry).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_conditional_fetch_reuses_cache() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let signer = Signer::new(1);
+        let signature = signer.sign(BODY, 100);
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let url = format!("http://{}/brokers.json", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = vec![0; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let (status, body) = if request.starts_with("get /brokers.json.minisig") {
+                    ("200 OK", signature.as_bytes())
+                } else if request.contains("if-none-match: \"v1\"") {
+                    ("304 Not Modified", &b""[..])
+                } else {
+                    ("200 OK", BODY)
+                };
+                let head = format!(
+                    "HTTP/1.1 {status}\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).await.unwrap();
+                stream.write_all(body).await.unwrap();
+            }
+        });
+
+        let http = HttpService::default();
+        let source = Source {
+            name: "mirror".to_string(),
+            location: Location::Url(url),
+            priority: 1,
+        };
+        let trust = Trust {
+            keys: vec![signer.public()],
+            required: true,
+        };
+        let first = load(&http, &source, &trust, None, None).await.unwrap();
+        assert!(!first.not_modified);
+        let cached = first.cached.unwrap();
+        assert_eq!(cached.etag.as_deref(), Some("\"v1\""));
+
+        let second = load(&http, &source, &trust, Some(100), Some(&cached))
+            .await
+            .unwrap();
+        assert!(second.not_modified);
+        assert_eq!(second.entries, first.entries);
+        assert_eq!(second.version, first.version);
+    }
+
+    #[test]
+    fn test_diff_reports_changes() {
+        let public = layer(
+            PUBLIC_SOURCE,
+            r#"[{"id": "acme", "name": "Acme", "connector": "email", "privacy_email": "a@acme.test"},
+                {"id": "beta", "name": "Beta"},
+                {"id": "gone", "name": "Gone"},
+                {"id": "back", "name": "Back"}]"#,
+        );
+        let mut stored = merge(&[public]).unwrap().brokers;
+        stored[3].retired_at = Some("2026-01-01T00:00:00Z".to_string());
+
+        let next = layer(
+            PUBLIC_SOURCE,
+            r#"[{"id": "acme", "name": "Acme", "connector": "postal", "postal_address": "1 Main St"},
+                {"id": "beta", "name": "Beta"},
+                {"id": "back", "name": "Back"},
+                {"id": "new", "name": "New"}]"#,
+        );
+        let diff = diff(&stored, merge(&[next]).unwrap().brokers);
+
+        let ids = |brokers: &[Broker]| brokers.iter().map(|b| b.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&diff.added), ["new"]);
+        assert_eq!(ids(&diff.removed), ["gone"]);
+        assert_eq!(ids(&diff.restored), ["back"]);
+        assert_eq!(diff.changed.len(), 1);
+        let (broker, changes) = &diff.changed[0];
+        assert_eq!(broker.id, "acme");
+        assert_eq!(changes[0].field, "connector");
+        assert_eq!(changes[0].old.as_deref(), Some("email"));
+        assert_eq!(changes[0].new.as_deref(), Some("postal"));
+        let fields: Vec<_> = changes.iter().map(|c| c.field).collect();
+        assert_eq!(fields, ["connector", "privacy_email", "postal_address"]);
+    }
+
+    #[test]
+    fn test_bundled_snapshot_is_valid() {
+        let layer = bundled().unwrap();
+        assert!(layer.rejected.is_empty(), "{:?}", layer.rejected);
+        let merged = merge(&[layer]).unwrap();
+        assert!(merged.rejected.is_empty(), "{:?}", merged.rejected);
+        let beenverified = merged
+            .brokers
+            .iter()
+            .find(|b| b.id == "beenverified")
+            .unwrap();
+        assert_eq!(
+            beenverified.registry_source.as_deref(),
+            Some(BUNDLED_SOURCE)
+        );
+    }
+
+    #[test]
+    fn test_broker_metadata_and_families() {
+        let public = layer(
+            PUBLIC_SOURCE,
+            r#"[{"id": "people-inc", "name": "People Inc", "opt_out_url": "https://people.test/optout",
+                 "request_methods": ["web_form", "email"], "verification": ["email_link"],
+                 "processing_days": 3, "relisting_days": 90, "jurisdictions": ["US", "US-CA"]},
+                {"id": "people-finder", "name": "People Finder", "parent": "people-inc"},
+                {"id": "stray", "name": "Stray", "parent": "nobody"},
+                {"id": "loop-a", "name": "A", "parent": "loop-b"},
+                {"id": "loop-b", "name": "B", "parent": "loop-a"}]"#,
+        );
+        let merged = merge(&[public]).unwrap();
+        let ids: Vec<_> = merged.brokers.iter().map(|b| b.id.as_str()).collect();
+        assert_eq!(ids, ["people-inc", "people-finder"]);
+        let parent = &merged.brokers[0];
+        assert_eq!(parent.request_methods, ["web_form", "email"]);
+        assert_eq!(parent.processing_days, Some(3));
+        assert_eq!(parent.jurisdictions, ["US", "US-CA"]);
+        assert_eq!(merged.brokers[1].parent_id.as_deref(), Some("people-inc"));
+        let rejected: Vec<_> = merged
+            .rejected
+            .iter()
+            .map(|r| r.id.as_deref().unwrap())
+            .collect();
+        assert_eq!(rejected, ["stray", "loop-a", "loop-b"]);
+
+        let read = entries(
+            br#"[{"id": "a", "name": "A", "request_methods": ["fax"]},
+                 {"id": "b", "name": "B", "jurisdictions": ["California"]},
+                 {"id": "c", "name": "C", "processing_days": -1}]"#,
+            "brokers.json",
+            false,
+        )
+        .unwrap();
+        assert!(read.valid.is_empty());
+        assert!(read.rejected[0].reason.contains("'fax'"));
+    }
 }
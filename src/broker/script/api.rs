@@ -0,0 +1,255 @@
+//! The Rhai engine scripts run in: resource limits, no module loading, and
+//! the host functions they may call.
+//!
+//! | function                              | returns                                   |
+//! |---------------------------------------|-------------------------------------------|
+//! | `http_get(url)` / `http_get(url, headers)` | `#{status, url, body, headers}`      |
+//! | `http_post(url, body, headers)`       | same                                      |
+//! | `http_post_form(url, fields)`         | same                                      |
+//! | `html_select(html, css)`              | array of `#{text, attrs}`                 |
+//! | `html_text(html, css)`                | array of trimmed element texts            |
+//! | `between(text, start, end)`           | the text between two markers, or `()`     |
+//! | `url_encode(text)`                    | percent-encoded text                      |
+//! | `session_get(key)`                    | value stored in the session, or `()`      |
+//! | `session_set(key, value)`             | `()`; storing `()` removes the key        |
+//! | `session_clear()`                     | `()`; also drops the session cookies      |
+//!
+//! `parse_json` and `map.to_json()` are Rhai built-ins. HTTP is limited to
+//! HTTPS on the connector's allowed domains (plain HTTP only to loopback
+//! hosts, for local test servers), including redirects. Cookies persist in
+//! the connector's session between calls and runs.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map};
+
+use crate::broker::http::{HttpClient, HttpRequest};
+use crate::broker::session::Session;
+
+/// Execution limits applied to every script call.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub max_operations: u64,
+    pub timeout: Duration,
+    pub max_requests: usize,
+}
+
+type RhaiResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Per-call state shared by the host functions.
+struct Sandbox {
+    script: String,
+    client: Arc<dyn HttpClient>,
+    deadline: Instant,
+    max_requests: usize,
+    requests: AtomicUsize,
+}
+
+impl Sandbox {
+    fn send(&self, mut request: HttpRequest) -> RhaiResult<Map> {
+        if self.requests.fetch_add(1, Ordering::Relaxed) >= self.max_requests {
+            return Err(format!("Script exceeded {} HTTP requests", self.max_requests).into());
+        }
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err("Script ran out of time".into());
+        }
+
+        request.timeout = Some(remaining);
+
+        // Host functions run on a blocking thread of the runtime.
+        let resp = tokio::runtime::Handle::current()
+            .block_on(self.client.send(request))
+            .map_err(|e| format!("[{}] HTTP error: {e}", self.script))?;
+        let mut map = Map::new();
+        map.insert("status".into(), (resp.status as i64).into());
+        map.insert("url".into(), resp.url.into());
+        let mut headers = Map::new();
+        for (name, value) in resp.headers {
+            headers.insert(name.into(), value.into());
+        }
+        map.insert("headers".into(), headers.into());
+        map.insert("body".into(), resp.body.into());
+        Ok(map)
+    }
+}
+
+fn apply_headers(mut request: HttpRequest, headers: &Map) -> HttpRequest {
+    for (name, value) in headers {
+        request = request.header(name.as_str(), &value.to_string());
+    }
+    request
+}
+
+/// Build an engine for one call of `script`, sending HTTP through `client`.
+pub fn engine(
+    script: &str,
+    client: Arc<dyn HttpClient>,
+    session: Arc<Session>,
+    limits: &Limits,
+) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(limits.max_operations)
+        .set_max_call_levels(64)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(8 * 1024 * 1024)
+        .set_max_array_size(100_000)
+        .set_max_map_size(10_000)
+        .set_max_modules(0)
+        .set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+
+    let deadline = Instant::now() + limits.timeout;
+    engine.on_progress(move |_| (Instant::now() > deadline).then(|| "timeout".into()));
+
+    let name = script.to_string();
+    engine.on_print(move |s| tracing::info!("[script {name}] {s}"));
+    let name = script.to_string();
+    engine.on_debug(move |s, _, _| tracing::debug!("[script {name}] {s}"));
+
+    let sandbox = Arc::new(Sandbox {
+        script: script.to_string(),
+        client,
+        deadline,
+        max_requests: limits.max_requests,
+        requests: AtomicUsize::new(0),
+    });
+
+    let sb = sandbox.clone();
+    engine.register_fn("http_get", move |url: &str| -> RhaiResult<Map> {
+        sb.send(HttpRequest::get(url))
+    });
+    let sb = sandbox.clone();
+    engine.register_fn(
+        "http_get",
+        move |url: &str, headers: Map| -> RhaiResult<Map> {
+            sb.send(apply_headers(HttpRequest::get(url), &headers))
+        },
+    );
+    let sb = sandbox.clone();
+    engine.register_fn(
+        "http_post",
+        move |url: &str, body: &str, headers: Map| -> RhaiResult<Map> {
+            sb.send(apply_headers(HttpRequest::post(url, body), &headers))
+        },
+    );
+    let sb = sandbox;
+    engine.register_fn(
+        "http_post_form",
+        move |url: &str, fields: Map| -> RhaiResult<Map> {
+            let form: Vec<(String, String)> = fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            let request = HttpRequest::post_form(url, &form).map_err(|e| e.to_string())?;
+            sb.send(request)
+        },
+    );
+
+    let s = session.clone();
+    engine.register_fn("session_get", move |key: &str| -> RhaiResult<Dynamic> {
+        match s.get(key) {
+            Some(value) => rhai::serde::to_dynamic(value),
+            None => Ok(Dynamic::UNIT),
+        }
+    });
+    let s = session.clone();
+    engine.register_fn(
+        "session_set",
+        move |key: &str, value: Dynamic| -> RhaiResult<()> {
+            let value: serde_json::Value = rhai::serde::from_dynamic(&value)?;
+            s.set(key, value).map_err(|e| e.to_string().into())
+        },
+    );
+    engine.register_fn("session_clear", move || -> RhaiResult<()> {
+        session.clear().map_err(|e| e.to_string().into())
+    });
+
+    engine.register_fn("html_select", html_select);
+    engine.register_fn("html_text", |html: &str, css: &str| -> RhaiResult<Array> {
+        Ok(html_select(html, css)?
+            .into_iter()
+            .filter_map(|el| el.cast::<Map>().remove("text"))
+            .collect())
+    });
+    engine.register_fn("between", between);
+    engine.register_fn("url_encode", url_encode);
+    engine
+}
+
+fn html_select(html: &str, css: &str) -> RhaiResult<Array> {
+    let selector =
+        scraper::Selector::parse(css).map_err(|e| format!("Invalid selector '{css}': {e}"))?;
+    let document = scraper::Html::parse_document(html);
+    Ok(document
+        .select(&selector)
+        .map(|el| {
+            let mut attrs = Map::new();
+            for (name, value) in el.value().attrs() {
+                attrs.insert(name.into(), value.to_string().into());
+            }
+            let text = el.text().collect::<String>();
+            let mut map = Map::new();
+            map.insert("text".into(), text.trim().to_string().into());
+            map.insert("attrs".into(), attrs.into());
+            Dynamic::from_map(map)
+        })
+        .collect())
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters.
+fn url_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{b:02X}"));
+        }
+    }
+    out
+}
+
+fn between(text: &str, start: &str, end: &str) -> Dynamic {
+    text.split_once(start)
+        .and_then(|(_, rest)| rest.split_once(end))
+        .map(|(inner, _)| Dynamic::from(inner.to_string()))
+        .unwrap_or(Dynamic::UNIT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_and_text_helpers() {
+        let limits = Limits {
+            max_operations: 100_000,
+            timeout: Duration::from_secs(5),
+            max_requests: 0,
+        };
+        let client = crate::broker::http::HttpService::default()
+            .client("test", &[])
+            .unwrap();
+        let session = Arc::new(Session::in_memory("test"));
+        let engine = engine("test", client, session, &limits);
+        let result: Array = engine
+            .eval(
+                r#"
+                let html = `<ul><li class="p"><a href="/p/1"> Jane Doe </a></li><li class="p">John</li></ul>`;
+                let links = html_select(html, "li.p a");
+                [html_text(html, "li.p"), links[0].attrs.href, between("token=abc;", "token=", ";"), url_encode("a b&c")]
+                "#,
+            )
+            .unwrap();
+        let names = result[0].clone().into_array().unwrap();
+        assert_eq!(names[0].clone().into_string().unwrap(), "Jane Doe");
+        assert_eq!(names[1].clone().into_string().unwrap(), "John");
+        assert_eq!(result[1].clone().into_string().unwrap(), "/p/1");
+        assert_eq!(result[2].clone().into_string().unwrap(), "abc");
+        assert_eq!(result[3].clone().into_string().unwrap(), "a%20b%26c");
+    }
+}
@@ -0,0 +1,601 @@
+//! Scripted connectors written in Rhai.
+//!
+//! A script defines any of these functions; the ones present decide the
+//! connector's capabilities:
+//!
+//! ```rhai
+//! fn info() { #{ name: "Acme People", domains: ["acme.example"], required_fields: ["state"] } }
+//! fn scan(query) { /* -> [#{data_type, data_value, profile_url}] */ }
+//! fn request_deletion(query, records) { /* -> #{external_ref, message, manual_steps} */ }
+//! fn check_deletion_status(external_ref) { /* -> #{status, completed_at, message} */ }
+//! fn resume_deletion(external_ref, step_key, input) { /* -> #{status, external_ref, ...} */ }
+//! fn run_step(external_ref, step, data) { /* -> #{goto, retry_secs, status, data, message} */ }
+//! ```
+//!
+//! Values cross the boundary in the same shape as the plugin protocol
+//! (see `broker::plugin`); `info()` may also describe the connector with
+//! any `ConnectorCapabilities` field past the `can_*` flags, and declare a
+//! `workflow` (see `broker::workflow`) whose `action` steps `run_step`
+//! performs. Top-level statements are not run. Scripts come
+//! from the registry (connector `script`) or from `*.rhai` files in the
+//! scripts directory, where the file stem is the broker ID.
+//!
+//! A script reaches only its broker's website and the domains configured
+//! for it in `[scripts.domains]`; the `domains` it declares in `info()` are
+//! checked against that list, never added to it.
+
+mod api;
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rhai::{AST, CallFnOptions, Dynamic, Engine, Scope};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use super::http::{HttpClient, HttpService};
+use super::session::Session;
+use super::workflow::{StepOutcome, Workflow};
+use super::{
+    BrokerConnector, ConnectorCapabilities, DeletionStatusCheck, DeletionSubmission, FoundRecord,
+    ManualStepOutcome, PersonQuery,
+};
+use crate::config::ScriptsConfig;
+use crate::db::Database;
+use crate::error::AppError;
+
+pub use api::Limits;
+
+/// Registry `connector` value for brokers driven by a script.
+pub const SCRIPT_CONNECTOR: &str = "script";
+
+#[derive(Debug, Default, Deserialize)]
+struct ScriptInfo {
+    name: Option<String>,
+    #[serde(default)]
+    domains: Vec<String>,
+    workflow: Option<Workflow>,
+    #[serde(flatten)]
+    details: ConnectorCapabilities,
+}
+
+pub struct ScriptConnector {
+    id: String,
+    name: String,
+    ast: Arc<AST>,
+    functions: HashSet<String>,
+    client: Arc<dyn HttpClient>,
+    session: Arc<Session>,
+    details: ConnectorCapabilities,
+    workflow: Option<Workflow>,
+    limits: Limits,
+}
+
+impl ScriptConnector {
+    /// Compile a script. `name` comes from the registry when known;
+    /// `allowed_domains` are all the script may reach, whatever its
+    /// `info()` declares.
+    pub async fn compile(
+        id: &str,
+        name: Option<&str>,
+        source: &str,
+        allowed_domains: Vec<String>,
+        limits: Limits,
+        http: &HttpService,
+    ) -> anyhow::Result<Self> {
+        let ast = Engine::new()
+            .compile(source)
+            .map_err(|e| anyhow::anyhow!("Script '{id}' does not compile: {e}"))?;
+        let functions: HashSet<String> = ast.iter_functions().map(|f| f.name.to_string()).collect();
+
+        let mut connector = Self {
+            id: id.to_string(),
+            name: name.unwrap_or(id).to_string(),
+            ast: Arc::new(ast),
+            functions,
+            client: http.client(id, &allowed_domains)?,
+            session: Arc::new(Session::in_memory(id)),
+            details: ConnectorCapabilities::default(),
+            workflow: None,
+            limits,
+        };
+
+        let info: ScriptInfo = if connector.functions.contains("info") {
+            connector.call("info", vec![]).await?
+        } else {
+            ScriptInfo::default()
+        };
+        if name.is_none()
+            && let Some(n) = info.name
+        {
+            connector.name = n;
+        }
+        for domain in &info.domains {
+            if !allowed_domains
+                .iter()
+                .any(|d| d.eq_ignore_ascii_case(domain))
+            {
+                tracing::warn!(
+                    "Script '{id}' declares domain '{domain}', which is not allowed for it; add it under [scripts.domains] to let it through"
+                );
+            }
+        }
+        let context = http.context(id, &allowed_domains)?;
+        connector.client = context.http;
+        connector.session = context.session;
+        connector.details = info.details;
+        if let Some(workflow) = &info.workflow {
+            workflow
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Script '{id}': {e}"))?;
+        }
+        connector.workflow = info.workflow;
+        Ok(connector)
+    }
+
+    /// Run a script function on a blocking thread under the call limits.
+    async fn call<T: DeserializeOwned + Send + 'static>(
+        &self,
+        function: &str,
+        args: Vec<Dynamic>,
+    ) -> anyhow::Result<T> {
+        if !self.functions.contains(function) {
+            return Err(AppError::Unsupported(format!(
+                "Script '{}' does not define {function}",
+                self.id
+            ))
+            .into());
+        }
+        let id = self.id.clone();
+        let ast = self.ast.clone();
+        let client = self.client.clone();
+        let session = self.session.clone();
+        let limits = self.limits.clone();
+        let function = function.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let engine = api::engine(&id, client, session, &limits);
+            let result: Dynamic = engine
+                .call_fn_with_options(
+                    CallFnOptions::new().eval_ast(false),
+                    &mut Scope::new(),
+                    &ast,
+                    &function,
+                    args,
+                )
+                .map_err(|e| match *e {
+                    rhai::EvalAltResult::ErrorTerminated(..) => anyhow::anyhow!(
+                        "Script '{id}' exceeded its {}s time limit in {function}",
+                        limits.timeout.as_secs()
+                    ),
+                    e => anyhow::anyhow!("Script '{id}' failed in {function}: {e}"),
+                })?;
+            rhai::serde::from_dynamic(&result).map_err(|e| {
+                anyhow::anyhow!("Script '{id}' returned an invalid {function} result: {e}")
+            })
+        })
+        .await?
+    }
+
+    fn to_dynamic<T: serde::Serialize>(value: &T) -> anyhow::Result<Dynamic> {
+        rhai::serde::to_dynamic(value).map_err(|e| anyhow::anyhow!("{e}"))
+    }
+}
+
+#[async_trait]
+impl BrokerConnector for ScriptConnector {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> ConnectorCapabilities {
+        ConnectorCapabilities {
+            can_scan: self.functions.contains("scan"),
+            can_delete: self.functions.contains("request_deletion"),
+            can_check_status: self.functions.contains("check_deletion_status"),
+            ..self.details.clone()
+        }
+    }
+
+    async fn scan(&self, query: &PersonQuery) -> anyhow::Result<Vec<FoundRecord>> {
+        self.capabilities().validate(&self.name, query)?;
+        self.call("scan", vec![Self::to_dynamic(query)?]).await
+    }
+
+    async fn request_deletion(
+        &self,
+        query: &PersonQuery,
+        records: &[FoundRecord],
+    ) -> anyhow::Result<DeletionSubmission> {
+        let args = vec![Self::to_dynamic(query)?, Self::to_dynamic(&records)?];
+        self.call("request_deletion", args).await
+    }
+
+    async fn check_deletion_status(
+        &self,
+        external_ref: &str,
+    ) -> anyhow::Result<DeletionStatusCheck> {
+        self.call("check_deletion_status", vec![external_ref.into()])
+            .await
+    }
+
+    async fn resume_deletion(
+        &self,
+        external_ref: &str,
+        step_key: &str,
+        input: Option<&str>,
+    ) -> anyhow::Result<ManualStepOutcome> {
+        if !self.functions.contains("resume_deletion") {
+            return Ok(ManualStepOutcome::default());
+        }
+        let input = input
+            .map(|s| Dynamic::from(s.to_string()))
+            .unwrap_or(Dynamic::UNIT);
+        self.call(
+            "resume_deletion",
+            vec![external_ref.into(), step_key.into(), input],
+        )
+        .await
+    }
+
+    fn workflow(&self) -> Option<Workflow> {
+        self.workflow.clone()
+    }
+
+    async fn run_step(
+        &self,
+        external_ref: &str,
+        step: &str,
+        data: &serde_json::Map<String, serde_json::Value>,
+    ) -> anyhow::Result<StepOutcome> {
+        let args = vec![external_ref.into(), step.into(), Self::to_dynamic(data)?];
+        self.call("run_step", args).await
+    }
+}
+
+fn website_domain(website: Option<&str>) -> Option<String> {
+    let url = reqwest::Url::parse(website?).ok()?;
+    let host = url.host_str()?;
+    Some(host.strip_prefix("www.").unwrap_or(host).to_string())
+}
+
+/// Register scripted connectors: local `*.rhai` files first (so a script
+/// under development can shadow the registry's), then registry scripts.
+/// Compiled-in connectors and plugins with the same ID take precedence;
+/// scripts that fail to compile are skipped with a warning.
+pub async fn add_script_connectors(
+    connectors: &mut HashMap<String, Arc<dyn BrokerConnector>>,
+    db: &Database,
+    config: &ScriptsConfig,
+    http: &HttpService,
+) -> anyhow::Result<()> {
+    let limits = Limits {
+        max_operations: config.max_operations,
+        timeout: Duration::from_secs(config.timeout_secs),
+        max_requests: config.max_requests,
+    };
+    let brokers: HashMap<String, _> = db
+        .list_brokers(None)?
+        .into_iter()
+        .map(|b| (b.id.clone(), b))
+        .collect();
+
+    let dir = match &config.dir {
+        Some(dir) => dir.clone(),
+        None => crate::config::scripts_dir()?,
+    };
+    let mut sources = local_scripts(&dir)?;
+    for broker in brokers.values() {
+        if broker.connector.as_deref() == Some(SCRIPT_CONNECTOR)
+            && let Some(source) = &broker.script
+            && !sources.iter().any(|(id, _)| id == &broker.id)
+        {
+            sources.push((broker.id.clone(), source.clone()));
+        }
+    }
+
+    for (id, source) in sources {
+        if connectors.contains_key(&id) {
+            tracing::warn!("Script '{id}' uses an ID that is already taken");
+            continue;
+        }
+        let broker = brokers.get(&id);
+        let domains = website_domain(broker.and_then(|b| b.website.as_deref()))
+            .into_iter()
+            .chain(config.domains.get(&id).into_iter().flatten().cloned())
+            .collect();
+        let name = broker.map(|b| b.name.as_str());
+        match ScriptConnector::compile(&id, name, &source, domains, limits.clone(), http).await {
+            Ok(script) => {
+                connectors.insert(id, Arc::new(script));
+            }
+            Err(e) => tracing::warn!("Skipping script: {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// `(broker id, source)` for every `*.rhai` file in `dir`.
+fn local_scripts(dir: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut scripts = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "rhai")
+            && let Some(stem) = path.file_stem()
+        {
+            scripts.push((
+                stem.to_string_lossy().into_owned(),
+                std::fs::read_to_string(&path)?,
+            ));
+        }
+    }
+    scripts.sort();
+    Ok(scripts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::{DeletionMethod, QueryField};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn limits() -> Limits {
+        Limits {
+            max_operations: 1_000_000,
+            timeout: Duration::from_secs(5),
+            max_requests: 5,
+        }
+    }
+
+    fn http() -> HttpService {
+        HttpService::default()
+    }
+
+    fn query() -> PersonQuery {
+        PersonQuery {
+            first_name: "Jane".into(),
+            last_name: "Doe".into(),
+            email: None,
+            phone: None,
+            city: None,
+            state: Some("NY".into()),
+        }
+    }
+
+    /// Serve one canned HTML page per connection.
+    async fn html_server(listener: TcpListener) {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let _ = stream.read(&mut buf).await;
+            let body = r#"<div class="person"><a href="/p/42">Jane Doe</a><span class="age">41</span></div>"#;
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(resp.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_script_scan_over_http() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(html_server(listener));
+
+        let source = format!(
+            r#"
+            fn info() {{ #{{ name: "Local People", domains: ["127.0.0.1"] }} }}
+            fn scan(query) {{
+                let resp = http_get("{base}/search?name=" + url_encode(query.first_name + " " + query.last_name));
+                if resp.status != 200 {{ throw "search failed"; }}
+                let out = [];
+                for el in html_select(resp.body, ".person a") {{
+                    out.push(#{{ data_type: "name", data_value: el.text, profile_url: "{base}" + el.attrs.href }});
+                }}
+                out
+            }}
+            "#
+        );
+        let script = ScriptConnector::compile(
+            "local",
+            None,
+            &source,
+            vec!["127.0.0.1".into()],
+            limits(),
+            &http(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(script.name(), "Local People");
+        let caps = script.capabilities();
+        assert!(caps.can_scan && !caps.can_delete && !caps.can_check_status);
+
+        let records = script.scan(&query()).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].data_value, "Jane Doe");
+        assert_eq!(
+            records[0].profile_url.as_deref(),
+            Some(format!("{base}/p/42").as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_declared_domains_grant_nothing() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(html_server(listener));
+
+        let source = format!(
+            r#"
+            fn info() {{ #{{ domains: ["127.0.0.1"] }} }}
+            fn scan(query) {{ http_get("{base}/search"); [] }}
+            "#
+        );
+        let script = ScriptConnector::compile(
+            "greedy",
+            None,
+            &source,
+            vec!["greedy.example".into()],
+            limits(),
+            &http(),
+        )
+        .await
+        .unwrap();
+        let err = script.scan(&query()).await.unwrap_err().to_string();
+        assert!(err.contains("may not access"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_script_sandbox() {
+        let source = r#"
+            fn scan(query) { http_get("https://not-allowed.example/") }
+            fn request_deletion(query, records) { loop { } }
+            fn check_deletion_status(r) { #{ status: "completed", message: "done " + r } }
+        "#;
+        let script = ScriptConnector::compile(
+            "sandboxed",
+            Some("Sandboxed"),
+            source,
+            vec!["allowed.example".into()],
+            Limits {
+                max_operations: 0,
+                timeout: Duration::from_millis(200),
+                max_requests: 5,
+            },
+            &http(),
+        )
+        .await
+        .unwrap();
+
+        let err = script.scan(&query()).await.unwrap_err().to_string();
+        assert!(err.contains("may not access"), "{err}");
+
+        let err = script.request_deletion(&query(), &[]).await.unwrap_err();
+        assert!(err.to_string().contains("time limit"), "{err}");
+
+        let status = script.check_deletion_status("R1").await.unwrap();
+        assert_eq!(status.status, "completed");
+        assert_eq!(status.message.as_deref(), Some("done R1"));
+
+        // No resume_deletion defined: nothing left to do.
+        let outcome = script.resume_deletion("R1", "k", None).await.unwrap();
+        assert!(outcome.status.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_info_describes_capabilities() {
+        let source = r#"
+            fn info() {
+                #{ required_fields: ["state"], deletion_method: "form", processing_days: 10 }
+            }
+            fn scan(query) { [] }
+        "#;
+        let script = ScriptConnector::compile("described", None, source, vec![], limits(), &http())
+            .await
+            .unwrap();
+        let caps = script.capabilities();
+        assert!(caps.can_scan && !caps.can_delete);
+        assert_eq!(caps.required_fields, vec![QueryField::State]);
+        assert_eq!(caps.deletion_method, Some(DeletionMethod::Form));
+        assert_eq!(caps.processing_days, Some(10));
+
+        let err = script
+            .scan(&PersonQuery {
+                state: None,
+                ..query()
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AppError>(),
+            Some(AppError::MissingField { field, .. }) if field == "state"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_session_state_between_calls() {
+        let source = r#"
+            fn scan(query) {
+                let step = session_get("step");
+                if step == () { step = 0; }
+                session_set("step", step + 1);
+                [#{ data_type: "step", data_value: `${step}` }]
+            }
+            fn check_deletion_status(r) { session_clear(); #{ status: "pending" } }
+        "#;
+        let script = ScriptConnector::compile("stepper", None, source, vec![], limits(), &http())
+            .await
+            .unwrap();
+        assert_eq!(script.scan(&query()).await.unwrap()[0].data_value, "0");
+        assert_eq!(script.scan(&query()).await.unwrap()[0].data_value, "1");
+        script.check_deletion_status("R1").await.unwrap();
+        assert_eq!(script.scan(&query()).await.unwrap()[0].data_value, "0");
+    }
+
+    #[tokio::test]
+    async fn test_script_workflow() {
+        let source = r#"
+            fn info() {
+                #{ workflow: #{ steps: [
+                    #{ name: "submit", type: "action" },
+                    #{ name: "processing", type: "timer", secs: 86400, next: "end" },
+                ] } }
+            }
+            fn request_deletion(query, records) { #{ external_ref: "R1" } }
+            fn run_step(external_ref, step, data) {
+                #{ data: #{ ticket: external_ref + "-" + step + "-" + data.n } }
+            }
+        "#;
+        let script = ScriptConnector::compile("flow", None, source, vec![], limits(), &http())
+            .await
+            .unwrap();
+        let workflow = script.workflow().unwrap();
+        assert_eq!(workflow.steps.len(), 2);
+
+        let mut data = serde_json::Map::new();
+        data.insert("n".into(), 7.into());
+        let outcome = script.run_step("R1", "submit", &data).await.unwrap();
+        assert_eq!(outcome.data["ticket"], "R1-submit-7");
+
+        let broken = r#"fn info() { #{ workflow: #{ steps: [#{ name: "a", type: "action", next: "b" }] } } }"#;
+        let err = ScriptConnector::compile("broken", None, broken, vec![], limits(), &http())
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("unknown step 'b'"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_operation_limit_and_no_modules() {
+        let script = ScriptConnector::compile(
+            "busy",
+            None,
+            r#"fn scan(q) { let x = 0; loop { x += 1; } }
+               fn check_deletion_status(r) { import "other" as o; o::f() }"#,
+            vec![],
+            Limits {
+                max_operations: 10_000,
+                timeout: Duration::from_secs(5),
+                max_requests: 0,
+            },
+            &http(),
+        )
+        .await
+        .unwrap();
+        let err = script.scan(&query()).await.unwrap_err().to_string();
+        assert!(err.contains("Too many operations"), "{err}");
+        assert!(script.check_deletion_status("r").await.is_err());
+    }
+}
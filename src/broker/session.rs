@@ -0,0 +1,186 @@
+//! Per-connector sessions: a cookie jar plus free-form key-value state.
+//!
+//! Multi-step opt-outs (search, select a listing, submit, confirm) need the
+//! same cookies on every request, and often span several CLI runs while the
+//! user works through manual steps. A connector gets its [`Session`] in the
+//! [`ConnectorContext`] it is built with; the context's HTTP client sends
+//! and stores the session's cookies by itself. Every change is written to
+//! the `broker_sessions` table straight away, and
+//! `data-breaker broker session clear <id>` starts over.
+
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+
+use cookie_store::CookieStore;
+use serde_json::{Map, Value};
+
+use super::http::HttpClient;
+use crate::db::Database;
+
+/// What a connector is handed when it is built.
+pub struct ConnectorContext {
+    /// Restricted to the connector's domains, carrying its session cookies.
+    pub http: Arc<dyn HttpClient>,
+    pub session: Arc<Session>,
+}
+
+struct SessionData {
+    cookies: CookieStore,
+    state: Map<String, Value>,
+}
+
+pub struct Session {
+    broker_id: String,
+    /// `None` keeps the session in memory only.
+    db: Option<Arc<Database>>,
+    data: Mutex<SessionData>,
+}
+
+impl Session {
+    /// The stored session of `broker_id`, or an empty one.
+    pub fn load(db: Arc<Database>, broker_id: &str) -> anyhow::Result<Self> {
+        let data = match db.get_broker_session(broker_id)? {
+            Some((cookies, state)) => SessionData {
+                // Expired cookies are dropped on load.
+                cookies: cookie_store::serde::json::load(BufReader::new(cookies.as_bytes()))
+                    .map_err(|e| anyhow::anyhow!("Corrupt cookies for '{broker_id}': {e}"))?,
+                state: serde_json::from_str(&state)?,
+            },
+            None => SessionData {
+                cookies: CookieStore::default(),
+                state: Map::new(),
+            },
+        };
+        Ok(Self {
+            broker_id: broker_id.to_string(),
+            db: Some(db),
+            data: Mutex::new(data),
+        })
+    }
+
+    pub fn in_memory(broker_id: &str) -> Self {
+        Self {
+            broker_id: broker_id.to_string(),
+            db: None,
+            data: Mutex::new(SessionData {
+                cookies: CookieStore::default(),
+                state: Map::new(),
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.data.lock().unwrap().state.get(key).cloned()
+    }
+
+    /// Store `value` under `key`; `null` removes the key.
+    pub fn set(&self, key: &str, value: Value) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if value.is_null() {
+            data.state.remove(key);
+        } else {
+            data.state.insert(key.to_string(), value);
+        }
+        self.save(&data)
+    }
+
+    /// Forget all cookies and state.
+    pub fn clear(&self) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.cookies.clear();
+        data.state.clear();
+        self.save(&data)
+    }
+
+    /// The `Cookie` header value for a request to `url`, if any apply.
+    pub fn cookie_header(&self, url: &reqwest::Url) -> Option<String> {
+        let data = self.data.lock().unwrap();
+        let pairs: Vec<String> = data
+            .cookies
+            .get_request_values(url)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+
+    /// Keep the `Set-Cookie` headers of a response from `url`.
+    pub fn store_cookies(
+        &self,
+        url: &reqwest::Url,
+        headers: &[(String, String)],
+    ) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let mut changed = false;
+        for (name, value) in headers {
+            if name.eq_ignore_ascii_case("set-cookie") {
+                match data.cookies.parse(value, url) {
+                    Ok(_) => changed = true,
+                    Err(e) => tracing::debug!("[{}] ignoring cookie: {e}", self.broker_id),
+                }
+            }
+        }
+        if changed {
+            self.save(&data)?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, data: &SessionData) -> anyhow::Result<()> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        // Session cookies are kept too: the next run continues the flow.
+        let mut cookies = Vec::new();
+        cookie_store::serde::json::save_incl_expired_and_nonpersistent(&data.cookies, &mut cookies)
+            .map_err(|e| anyhow::anyhow!("Cannot save cookies for '{}': {e}", self.broker_id))?;
+        db.save_broker_session(
+            &self.broker_id,
+            &String::from_utf8(cookies)?,
+            &serde_json::to_string(&data.state)?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_session_survives_reload_and_clear() {
+        let db = Arc::new(Database::open_in_memory().unwrap());
+        let url = reqwest::Url::parse("https://www.acme.example/optout").unwrap();
+
+        let session = Session::load(db.clone(), "acme").unwrap();
+        session
+            .store_cookies(
+                &url,
+                &[
+                    ("Set-Cookie".into(), "sid=abc; Path=/".into()),
+                    ("set-cookie".into(), "step=2; Path=/optout".into()),
+                    ("Content-Type".into(), "text/html".into()),
+                ],
+            )
+            .unwrap();
+        session.set("listing", json!("p-42")).unwrap();
+
+        let session = Session::load(db.clone(), "acme").unwrap();
+        assert_eq!(session.get("listing"), Some(json!("p-42")));
+        let header = session.cookie_header(&url).unwrap();
+        assert!(
+            header.contains("sid=abc") && header.contains("step=2"),
+            "{header}"
+        );
+        let other = reqwest::Url::parse("https://other.example/").unwrap();
+        assert!(session.cookie_header(&other).is_none());
+
+        session.set("listing", Value::Null).unwrap();
+        assert!(session.get("listing").is_none());
+
+        session.clear().unwrap();
+        let session = Session::load(db.clone(), "acme").unwrap();
+        assert!(session.cookie_header(&url).is_none());
+        assert!(db.delete_broker_session("acme").unwrap());
+        assert!(!db.delete_broker_session("acme").unwrap());
+    }
+}
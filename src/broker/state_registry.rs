@@ -0,0 +1,603 @@
+//! Official state data broker registries.
+//!
+//! California, Vermont, Texas and Oregon require data brokers to register and
+//! publish the registrants as a spreadsheet. Layouts differ per state and
+//! change between years, so columns are found by their header rather than
+//! by position. Spreadsheets must be exported to CSV first.
+
+use std::collections::HashSet;
+
+use crate::db::models::Broker;
+use crate::error::AppError;
+
+/// A state that publishes a data broker registry.
+#[derive(Debug, PartialEq, Eq)]
+pub struct State {
+    /// Two-letter postal code, as accepted on the command line.
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+pub const STATES: &[State] = &[
+    State {
+        code: "CA",
+        name: "California",
+    },
+    State {
+        code: "VT",
+        name: "Vermont",
+    },
+    State {
+        code: "TX",
+        name: "Texas",
+    },
+    State {
+        code: "OR",
+        name: "Oregon",
+    },
+];
+
+impl State {
+    pub fn find(code: &str) -> Option<&'static State> {
+        STATES.iter().find(|s| s.code.eq_ignore_ascii_case(code))
+    }
+
+    /// The `registry_source` of brokers imported from this state.
+    pub fn source(&self) -> String {
+        format!("state:{}", self.code)
+    }
+
+    /// Whether `broker` was imported from this state's list and is not in
+    /// the project's registry.
+    pub fn lists(&self, broker: &Broker) -> bool {
+        is_state_import(broker)
+            && broker
+                .registry_source
+                .as_deref()
+                .is_some_and(|s| s.split(", ").any(|s| s == self.source()))
+    }
+
+    fn jurisdiction(&self) -> String {
+        format!("US-{}", self.code)
+    }
+}
+
+/// One registrant as the state lists it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Registrant {
+    pub name: String,
+    /// "Doing business as" names.
+    pub aliases: Vec<String>,
+    pub website: Option<String>,
+    pub email: Option<String>,
+    pub address: Option<String>,
+}
+
+/// The registrants read from a state's file.
+#[derive(Debug)]
+pub struct Listing {
+    pub registrants: Vec<Registrant>,
+    /// Rows without a name.
+    pub skipped: usize,
+}
+
+/// Headers naming the registrant, most specific first.
+const NAME_HEADERS: &[&str] = &[
+    "databrokername",
+    "nameofdatabroker",
+    "brokername",
+    "legalname",
+    "businessname",
+    "entityname",
+    "companyname",
+    "name",
+];
+const ALIAS_HEADERS: &[&str] = &["dba", "doingbusinessas", "tradename", "assumedname"];
+const ADDRESS_PARTS: &[&str] = &["city", "state", "zip", "zipcode", "postalcode", "country"];
+
+/// How many leading rows may precede the header (titles, notes).
+const HEADER_SEARCH_ROWS: usize = 10;
+
+/// Columns of a state file, by index.
+#[derive(Debug, Default)]
+struct Columns {
+    name: usize,
+    aliases: Vec<usize>,
+    website: Option<usize>,
+    email: Option<usize>,
+    address: Vec<usize>,
+}
+
+impl Columns {
+    fn find(headers: &csv::StringRecord) -> Option<Columns> {
+        let headers: Vec<String> = headers.iter().map(normalize).collect();
+        let position = |wanted: &[&str]| {
+            wanted
+                .iter()
+                .find_map(|w| headers.iter().position(|h| h == w))
+        };
+        let matching = |pred: &dyn Fn(&str) -> bool| headers.iter().position(|h| pred(h));
+
+        let name = position(NAME_HEADERS).or_else(|| {
+            matching(&|h| {
+                h.ends_with("name")
+                    && !["contact", "agent", "officer", "dba", "person"]
+                        .iter()
+                        .any(|w| h.contains(w))
+            })
+        })?;
+        let aliases = (0..headers.len())
+            .filter(|&i| i != name && ALIAS_HEADERS.iter().any(|a| headers[i].contains(a)))
+            .collect();
+        let email = matching(&|h| h.contains("email"));
+        let website = matching(&|h| {
+            ["website", "webaddress", "internetaddress", "url", "webpage"]
+                .iter()
+                .any(|w| h.contains(w))
+        });
+        // The first full address, followed by any city/state/zip columns.
+        let mut address: Vec<usize> = matching(&|h| {
+            (h.contains("address") || h.contains("street"))
+                && !["email", "web", "internet"].iter().any(|w| h.contains(w))
+        })
+        .into_iter()
+        .collect();
+        address
+            .extend((0..headers.len()).filter(|&i| ADDRESS_PARTS.contains(&headers[i].as_str())));
+        Some(Columns {
+            name,
+            aliases,
+            website,
+            email,
+            address,
+        })
+    }
+}
+
+/// Read a state registry exported as CSV.
+pub fn parse(body: &[u8]) -> Result<Listing, AppError> {
+    let body = String::from_utf8_lossy(body);
+    let body = body.trim_start_matches('\u{feff}');
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(body.as_bytes());
+    let mut records = reader.records();
+
+    let mut columns = None;
+    for record in records.by_ref().take(HEADER_SEARCH_ROWS) {
+        let record = record.map_err(|e| AppError::Registry(format!("invalid CSV: {e}")))?;
+        if let Some(found) = Columns::find(&record) {
+            columns = Some(found);
+            break;
+        }
+    }
+    let columns = columns.ok_or_else(|| {
+        AppError::Registry("no column with the data broker's name was found".to_string())
+    })?;
+
+    let mut listing = Listing {
+        registrants: Vec::new(),
+        skipped: 0,
+    };
+    for record in records {
+        let record = record.map_err(|e| AppError::Registry(format!("invalid CSV: {e}")))?;
+        let cell = |i: usize| {
+            record
+                .get(i)
+                .map(str::trim)
+                .filter(|v| !v.is_empty() && !is_placeholder(v))
+        };
+        if record.iter().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+        let Some(name) = cell(columns.name) else {
+            listing.skipped += 1;
+            continue;
+        };
+        let address: Vec<&str> = columns.address.iter().filter_map(|&i| cell(i)).collect();
+        listing.registrants.push(Registrant {
+            name: name.to_string(),
+            aliases: columns
+                .aliases
+                .iter()
+                .filter_map(|&i| cell(i))
+                .flat_map(|v| v.split([';', '\n']))
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+                .collect(),
+            website: columns.website.and_then(cell).and_then(website),
+            email: columns
+                .email
+                .and_then(cell)
+                .filter(|v| v.contains('@') && !v.contains(char::is_whitespace))
+                .map(String::from),
+            address: (!address.is_empty()).then(|| address.join(", ")),
+        });
+    }
+    Ok(listing)
+}
+
+/// Lowercase letters and digits only.
+fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn is_placeholder(value: &str) -> bool {
+    matches!(normalize(value).as_str(), "na" | "none" | "null" | "nil")
+}
+
+/// An http(s) URL for a website as registrants write it ("www.example.com").
+fn website(value: &str) -> Option<String> {
+    let url = if value.contains("://") {
+        value.to_string()
+    } else {
+        format!("https://{value}")
+    };
+    let parsed = reqwest::Url::parse(&url).ok()?;
+    (matches!(parsed.scheme(), "http" | "https") && parsed.host_str()?.contains('.')).then_some(url)
+}
+
+/// Suffixes dropped when comparing company names.
+const COMPANY_SUFFIXES: &[&str] = &[
+    "inc",
+    "incorporated",
+    "llc",
+    "llp",
+    "lp",
+    "ltd",
+    "limited",
+    "corp",
+    "corporation",
+    "co",
+    "company",
+    "plc",
+];
+
+/// The words of a company name without its legal suffix.
+fn name_words(name: &str) -> Vec<String> {
+    let mut words: Vec<String> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    while words.len() > 1 && COMPANY_SUFFIXES.contains(&words[words.len() - 1].as_str()) {
+        words.pop();
+    }
+    words
+}
+
+/// Key for matching brokers by name: "Acme Data, LLC" and "ACME data" match.
+pub fn name_key(name: &str) -> String {
+    name_words(name).concat()
+}
+
+/// The registrable domain of a website: `https://optout.acme.com/x` gives
+/// `acme.com`.
+pub fn domain(website: &str) -> Option<String> {
+    let host = reqwest::Url::parse(website)
+        .ok()?
+        .host_str()?
+        .to_ascii_lowercase();
+    let labels: Vec<&str> = host.split('.').filter(|l| !l.is_empty()).collect();
+    // Keep three labels under second-level suffixes such as co.uk.
+    let keep = match labels.as_slice() {
+        [.., second, _]
+            if labels.len() > 2 && ["co", "com", "org", "net", "ac"].contains(second) =>
+        {
+            3
+        }
+        _ => 2,
+    };
+    (labels.len() >= 2).then(|| labels[labels.len().saturating_sub(keep)..].join("."))
+}
+
+/// Outcome of importing a state's listing against the known brokers.
+#[derive(Debug, Default)]
+pub struct Import {
+    /// Brokers not known before, to be stored.
+    pub added: Vec<Broker>,
+    /// Brokers imported from another state earlier, now listed here too.
+    pub updated: Vec<Broker>,
+    /// Registrants already in the project's registry, with the matching id.
+    pub known: Vec<(Registrant, String)>,
+    /// Registrants matching a broker imported from a state list, this one
+    /// included.
+    pub merged: usize,
+}
+
+/// Whether `broker` came from a state registry rather than the project's.
+pub fn is_state_import(broker: &Broker) -> bool {
+    broker.registry_updated_at.is_none()
+        && broker
+            .registry_source
+            .as_deref()
+            .is_some_and(|s| s.split(", ").any(|s| s.starts_with("state:")))
+}
+
+/// Map `listing` to brokers, matching registrants to `existing` brokers and
+/// to each other by name and website domain.
+pub fn import(state: &State, listing: Listing, existing: &[Broker]) -> Import {
+    let now = chrono::Utc::now().to_rfc3339();
+    let source = state.source();
+    let jurisdiction = state.jurisdiction();
+    let mut result = Import::default();
+    // State imports seen so far; matches among them are merged.
+    let mut imported: Vec<Broker> = existing
+        .iter()
+        .filter(|b| is_state_import(b))
+        .cloned()
+        .collect();
+    let mut changed: HashSet<String> = HashSet::new();
+    let mut ids: HashSet<String> = existing.iter().map(|b| b.id.clone()).collect();
+
+    for registrant in listing.registrants {
+        let names: HashSet<String> = std::iter::once(&registrant.name)
+            .chain(&registrant.aliases)
+            .map(|n| name_key(n))
+            .filter(|k| !k.is_empty())
+            .collect();
+        let registrant_domain = registrant.website.as_deref().and_then(domain);
+        let matches = |broker: &Broker| {
+            names.contains(&name_key(&broker.name))
+                || names.contains(&normalize(&broker.id))
+                || registrant_domain.is_some()
+                    && broker.website.as_deref().and_then(domain) == registrant_domain
+        };
+
+        if let Some(known) = existing.iter().find(|b| !is_state_import(b) && matches(b)) {
+            result.known.push((registrant, known.id.clone()));
+            continue;
+        }
+        if let Some(broker) = imported.iter_mut().find(|b| matches(b)) {
+            result.merged += 1;
+            let mut updated = false;
+            let sources = broker.registry_source.get_or_insert_default();
+            if !sources.split(", ").any(|s| s == source) {
+                sources.push_str(&format!(", {source}"));
+                updated = true;
+            }
+            if !broker.jurisdictions.contains(&jurisdiction) {
+                broker.jurisdictions.push(jurisdiction.clone());
+                updated = true;
+            }
+            for (field, value) in [
+                (&mut broker.website, registrant.website),
+                (&mut broker.privacy_email, registrant.email),
+                (&mut broker.postal_address, registrant.address),
+            ] {
+                if field.is_none() && value.is_some() {
+                    *field = value;
+                    updated = true;
+                }
+            }
+            if updated {
+                broker.updated_at = now.clone();
+                changed.insert(broker.id.clone());
+            }
+            continue;
+        }
+
+        let id = unique_id(&registrant.name, state, &ids);
+        ids.insert(id.clone());
+        imported.push(Broker {
+            id,
+            name: registrant.name,
+            website: registrant.website,
+            description: None,
+            category: None,
+            connector: None,
+            privacy_email: registrant.email,
+            postal_address: registrant.address,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![jurisdiction.clone()],
+            parent_id: None,
+            shared_database: false,
+            script: None,
+            wasm_manifest: None,
+            registry_source: Some(source.clone()),
+            registry_updated_at: None,
+            retired_at: None,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+        });
+        changed.insert(imported[imported.len() - 1].id.clone());
+    }
+
+    let existing_ids: HashSet<&str> = existing.iter().map(|b| b.id.as_str()).collect();
+    for broker in imported {
+        if !changed.contains(&broker.id) {
+            continue;
+        }
+        if existing_ids.contains(broker.id.as_str()) {
+            result.updated.push(broker);
+        } else {
+            result.added.push(broker);
+        }
+    }
+    result
+}
+
+/// A registry id for `name` that no broker uses yet.
+fn unique_id(name: &str, state: &State, taken: &HashSet<String>) -> String {
+    let mut base = name_words(name).join("-");
+    if base.is_empty() {
+        base = "broker".to_string();
+    }
+    let candidates = [
+        base.clone(),
+        format!("{base}-{}", state.code.to_ascii_lowercase()),
+    ];
+    candidates
+        .into_iter()
+        .chain((2..).map(|n| format!("{base}-{n}")))
+        .find(|id| !taken.contains(id))
+        .expect("an unused id")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry_broker(id: &str, name: &str, website: &str) -> Broker {
+        let now = chrono::Utc::now().to_rfc3339();
+        Broker {
+            id: id.into(),
+            name: name.into(),
+            website: Some(website.into()),
+            description: None,
+            category: None,
+            connector: None,
+            privacy_email: None,
+            postal_address: None,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: None,
+            shared_database: false,
+            script: None,
+            wasm_manifest: None,
+            registry_source: Some("public".into()),
+            registry_updated_at: Some(now.clone()),
+            retired_at: None,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_parse_state_layouts() {
+        // California: title rows above the header, one address column.
+        let ca = "\u{feff}Data Broker Registry\nExported 2026-01-05\n\
+            Data broker name:,Email address:,Website URL:,Physical address:\n\
+            \"Acme Data, LLC\",privacy@acme.example,www.acme.example,\"1 Main St, Sacramento, CA 95814\"\n\
+            ,nobody@nowhere.example,,\n\
+            \n\
+            Spokeo Inc.,N/A,https://www.spokeo.com,\n";
+        let listing = parse(ca.as_bytes()).unwrap();
+        assert_eq!(listing.skipped, 1);
+        assert_eq!(listing.registrants.len(), 2);
+        assert_eq!(
+            listing.registrants[0],
+            Registrant {
+                name: "Acme Data, LLC".into(),
+                aliases: vec![],
+                website: Some("https://www.acme.example".into()),
+                email: Some("privacy@acme.example".into()),
+                address: Some("1 Main St, Sacramento, CA 95814".into()),
+            }
+        );
+        assert_eq!(listing.registrants[1].email, None);
+
+        // Oregon-style: split address and a DBA column.
+        let or = "Business Name,DBA,Street Address,City,State,Zip,Email Address,Web Site\n\
+            PeopleConnect Inc,Intelius; TruthFinder,1 Pine St,Seattle,WA,98101,,peopleconnect.us\n";
+        let registrant = &parse(or.as_bytes()).unwrap().registrants[0];
+        assert_eq!(registrant.aliases, ["Intelius", "TruthFinder"]);
+        assert_eq!(
+            registrant.address.as_deref(),
+            Some("1 Pine St, Seattle, WA, 98101")
+        );
+        assert_eq!(
+            registrant.website.as_deref(),
+            Some("https://peopleconnect.us")
+        );
+
+        assert!(parse(b"Email,Phone\na@b.example,555\n").is_err());
+    }
+
+    #[test]
+    fn test_import_deduplicates() {
+        assert_eq!(name_key("Acme Data, LLC"), "acmedata");
+        assert_eq!(name_key("ACME data"), "acmedata");
+        assert_eq!(
+            domain("https://optout.acme.com/x").as_deref(),
+            Some("acme.com")
+        );
+        assert_eq!(
+            domain("https://www.acme.co.uk").as_deref(),
+            Some("acme.co.uk")
+        );
+
+        let existing = vec![
+            registry_broker("spokeo", "Spokeo", "https://www.spokeo.com"),
+            registry_broker("intelius", "Intelius", "https://www.intelius.com"),
+        ];
+        let ca = State::find("ca").unwrap();
+        let listing = Listing {
+            registrants: vec![
+                Registrant {
+                    name: "Spokeo, Inc.".into(),
+                    ..Default::default()
+                },
+                Registrant {
+                    name: "PeopleConnect".into(),
+                    aliases: vec!["Intelius".into()],
+                    ..Default::default()
+                },
+                Registrant {
+                    name: "Acme Data LLC".into(),
+                    website: Some("https://acme.example".into()),
+                    ..Default::default()
+                },
+                // Listed twice under a different name, same site.
+                Registrant {
+                    name: "Acme Data Holdings".into(),
+                    website: Some("https://optout.acme.example".into()),
+                    email: Some("privacy@acme.example".into()),
+                    ..Default::default()
+                },
+            ],
+            skipped: 0,
+        };
+        let result = import(ca, listing, &existing);
+        let known: Vec<&str> = result.known.iter().map(|(_, id)| id.as_str()).collect();
+        assert_eq!(known, ["spokeo", "intelius"]);
+        assert_eq!(result.added.len(), 1);
+        let acme = &result.added[0];
+        assert_eq!(acme.id, "acme-data");
+        assert_eq!(acme.registry_source.as_deref(), Some("state:CA"));
+        assert_eq!(acme.jurisdictions, ["US-CA"]);
+        assert_eq!(acme.privacy_email.as_deref(), Some("privacy@acme.example"));
+
+        // Vermont lists the same broker: the import gains the jurisdiction.
+        let mut existing = existing;
+        existing.push(acme.clone());
+        let vt = State::find("VT").unwrap();
+        let listing = Listing {
+            registrants: vec![
+                Registrant {
+                    name: "ACME DATA".into(),
+                    ..Default::default()
+                },
+                Registrant {
+                    name: "Acme Data".into(),
+                    website: Some("https://acme-data.example".into()),
+                    ..Default::default()
+                },
+            ],
+            skipped: 0,
+        };
+        let result = import(vt, listing, &existing);
+        assert!(result.added.is_empty());
+        assert_eq!(result.merged, 2);
+        assert_eq!(result.updated.len(), 1);
+        assert_eq!(
+            result.updated[0].registry_source.as_deref(),
+            Some("state:CA, state:VT")
+        );
+        assert_eq!(result.updated[0].jurisdictions, ["US-CA", "US-VT"]);
+    }
+}
@@ -0,0 +1,855 @@
+//! WebAssembly component connectors.
+//!
+//! A connector is a component implementing the `broker` world in
+//! `connector.wit`. It gets no WASI and no host access besides the `http`
+//! import, which only reaches the domains declared in its registry
+//! manifest and keeps the connector's session cookies between calls.
+//! Modules are downloaded on `registry update`, pinned by
+//! SHA-256, and re-verified before they are compiled. A module is compiled
+//! once to learn its capabilities and workflow, which are remembered next to
+//! it; after that it is only compiled when a command calls it. Each call runs
+//! in a fresh instance with a fuel budget and a memory cap.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+use wasmtime::component::{Component, HasSelf, Linker};
+use wasmtime::{Engine, Store, StoreLimits, StoreLimitsBuilder};
+
+use super::http::{HttpClient, HttpRequest, HttpService};
+use super::workflow::{StepOutcome, Workflow};
+use super::{
+    BrokerConnector, ConnectorCapabilities, DeletionMethod, DeletionStatusCheck,
+    DeletionSubmission, FoundRecord, ManualStep, ManualStepKind, ManualStepOutcome, PersonQuery,
+    QueryField,
+};
+use crate::config::WasmConfig;
+use crate::db::Database;
+
+mod bindings {
+    wasmtime::component::bindgen!({
+        path: "src/broker/wasm/connector.wit",
+        world: "broker",
+    });
+}
+
+use bindings::data_breaker::connector::{http, types};
+
+/// Registry `connector` value for WebAssembly connectors.
+pub const WASM_CONNECTOR: &str = "wasm";
+
+/// Registry entry describing where a module lives and what it may reach.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WasmManifest {
+    pub url: String,
+    /// Hex-encoded SHA-256 of the module; anything else is refused.
+    pub sha256: String,
+    /// Domains (and their subdomains) the module may send requests to.
+    #[serde(default)]
+    pub domains: Vec<String>,
+}
+
+/// What a module says about itself, remembered next to it so it does not
+/// have to be compiled to find out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModuleInfo {
+    pub capabilities: ConnectorCapabilities,
+    #[serde(default)]
+    pub workflow: Option<Workflow>,
+}
+
+/// Per-instance host state: the connector's HTTP client and resource limits.
+struct HostState {
+    client: Arc<dyn HttpClient>,
+    requests_left: usize,
+    limits: StoreLimits,
+}
+
+impl http::Host for HostState {
+    fn fetch(&mut self, req: http::Request) -> Result<http::Response, String> {
+        if self.requests_left == 0 {
+            return Err("request limit reached".to_string());
+        }
+        self.requests_left -= 1;
+
+        let request = HttpRequest {
+            method: req.method,
+            url: req.url,
+            headers: req.headers,
+            body: req.body,
+            timeout: None,
+        };
+        // Calls run on a blocking thread of the runtime.
+        let resp = tokio::runtime::Handle::current()
+            .block_on(self.client.send(request))
+            .map_err(|e| e.to_string())?;
+        Ok(http::Response {
+            status: resp.status,
+            url: resp.url,
+            headers: resp.headers,
+            body: resp.body,
+        })
+    }
+}
+
+impl types::Host for HostState {}
+
+/// A component compiled for instantiation.
+struct Compiled {
+    engine: Engine,
+    component: Component,
+    linker: Arc<Linker<HostState>>,
+}
+
+impl Compiled {
+    /// Compile a module after checking it against its pinned hash.
+    fn new(bytes: &[u8], sha256: &str) -> anyhow::Result<Self> {
+        verify_hash(bytes, sha256)?;
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config)?;
+        let component = Component::new(&engine, bytes)?;
+        let mut linker = Linker::new(&engine);
+        bindings::Broker::add_to_linker::<_, HasSelf<_>>(&mut linker, |state| state)?;
+        Ok(Self {
+            engine,
+            component,
+            linker: Arc::new(linker),
+        })
+    }
+}
+
+pub struct WasmConnector {
+    id: String,
+    name: String,
+    /// Cached module to compile on first use, for lazily loaded connectors.
+    module: Option<PathBuf>,
+    sha256: String,
+    compiled: OnceCell<Compiled>,
+    client: Arc<dyn HttpClient>,
+    config: WasmConfig,
+    capabilities: ConnectorCapabilities,
+    workflow: Option<Workflow>,
+}
+
+impl WasmConnector {
+    /// Compile a module after checking it against its pinned hash, and ask
+    /// it for its capabilities and workflow. Its requests may only reach the
+    /// manifest's domains.
+    pub async fn load(
+        id: &str,
+        name: &str,
+        bytes: &[u8],
+        manifest: &WasmManifest,
+        config: &WasmConfig,
+        http: &HttpService,
+    ) -> anyhow::Result<Self> {
+        let compiled = Compiled::new(bytes, &manifest.sha256)?;
+        let mut connector = Self::new(id, name, None, manifest, Default::default(), config, http)?;
+        connector.compiled = OnceCell::new_with(Some(compiled));
+        let caps = connector
+            .call(|broker, store| broker.call_capabilities(store).map(Ok))
+            .await?;
+        connector.capabilities = caps.into();
+        let workflow = connector
+            .call(|broker, store| broker.call_workflow(store).map(Ok))
+            .await?;
+        if let Some(json) = workflow {
+            let workflow: Workflow = serde_json::from_str(&json)
+                .map_err(|e| anyhow::anyhow!("WASM connector '{id}': invalid workflow: {e}"))?;
+            workflow
+                .validate()
+                .map_err(|e| anyhow::anyhow!("WASM connector '{id}': {e}"))?;
+            connector.workflow = Some(workflow);
+        }
+        Ok(connector)
+    }
+
+    /// A connector for the module cached at `path` whose `info` is already
+    /// known. The module is verified and compiled on the first call.
+    pub fn lazy(
+        id: &str,
+        name: &str,
+        path: &Path,
+        manifest: &WasmManifest,
+        info: ModuleInfo,
+        config: &WasmConfig,
+        http: &HttpService,
+    ) -> anyhow::Result<Self> {
+        Self::new(id, name, Some(path), manifest, info, config, http)
+    }
+
+    fn new(
+        id: &str,
+        name: &str,
+        module: Option<&Path>,
+        manifest: &WasmManifest,
+        info: ModuleInfo,
+        config: &WasmConfig,
+        http: &HttpService,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            module: module.map(Path::to_path_buf),
+            sha256: manifest.sha256.clone(),
+            compiled: OnceCell::new(),
+            client: http.context(id, &manifest.domains)?.http,
+            config: config.clone(),
+            capabilities: info.capabilities,
+            workflow: info.workflow,
+        })
+    }
+
+    /// The compiled component, compiling the cached module if needed.
+    async fn compiled(&self) -> anyhow::Result<&Compiled> {
+        self.compiled
+            .get_or_try_init(|| async {
+                let Some(path) = &self.module else {
+                    anyhow::bail!("WASM connector '{}' has no module", self.id);
+                };
+                let bytes = std::fs::read(path)?;
+                let sha256 = self.sha256.clone();
+                tokio::task::spawn_blocking(move || Compiled::new(&bytes, &sha256)).await?
+            })
+            .await
+    }
+
+    /// Instantiate a fresh component and run one export on a blocking thread.
+    async fn call<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(
+                &bindings::exports::data_breaker::connector::connector::Guest,
+                &mut Store<HostState>,
+            ) -> wasmtime::Result<Result<T, String>>
+            + Send
+            + 'static,
+    {
+        let compiled = self
+            .compiled()
+            .await
+            .map_err(|e| e.context(format!("WASM connector '{}'", self.id)))?;
+        let id = self.id.clone();
+        let engine = compiled.engine.clone();
+        let component = compiled.component.clone();
+        let linker = compiled.linker.clone();
+        let state = HostState {
+            client: self.client.clone(),
+            requests_left: self.config.max_requests,
+            limits: StoreLimitsBuilder::new()
+                .memory_size(self.config.max_memory_mb * 1024 * 1024)
+                .build(),
+        };
+        let fuel = self.config.fuel;
+
+        tokio::task::spawn_blocking(move || {
+            let mut store = Store::new(&engine, state);
+            store.limiter(|s| &mut s.limits);
+            store.set_fuel(fuel)?;
+            let broker = bindings::Broker::instantiate(&mut store, &component, &linker)?;
+            match f(broker.data_breaker_connector_connector(), &mut store) {
+                Ok(Ok(value)) => Ok(value),
+                Ok(Err(message)) => Err(anyhow::anyhow!("{message}")),
+                Err(trap) => {
+                    let out_of_fuel = matches!(
+                        trap.downcast_ref::<wasmtime::Trap>(),
+                        Some(wasmtime::Trap::OutOfFuel)
+                    );
+                    if out_of_fuel {
+                        Err(anyhow::anyhow!("WASM connector '{id}' ran out of fuel"))
+                    } else {
+                        Err(trap.context(format!("WASM connector '{id}' trapped")))
+                    }
+                }
+            }
+        })
+        .await?
+    }
+}
+
+fn verify_hash(bytes: &[u8], expected: &str) -> anyhow::Result<()> {
+    let actual = sha256_hex(bytes);
+    if !actual.eq_ignore_ascii_case(expected) {
+        anyhow::bail!("module hash {actual} does not match pinned {expected}");
+    }
+    Ok(())
+}
+
+pub(super) fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+impl From<&PersonQuery> for types::PersonQuery {
+    fn from(q: &PersonQuery) -> Self {
+        Self {
+            first_name: q.first_name.clone(),
+            last_name: q.last_name.clone(),
+            email: q.email.clone(),
+            phone: q.phone.clone(),
+            city: q.city.clone(),
+            state: q.state.clone(),
+        }
+    }
+}
+
+impl From<&FoundRecord> for types::FoundRecord {
+    fn from(r: &FoundRecord) -> Self {
+        Self {
+            data_type: r.data_type.clone(),
+            data_value: r.data_value.clone(),
+            profile_url: r.profile_url.clone(),
+            metadata: r.metadata.as_ref().map(|m| m.to_string()),
+        }
+    }
+}
+
+impl From<types::FoundRecord> for FoundRecord {
+    fn from(r: types::FoundRecord) -> Self {
+        Self {
+            data_type: r.data_type,
+            data_value: r.data_value,
+            profile_url: r.profile_url,
+            metadata: r.metadata.and_then(|m| serde_json::from_str(&m).ok()),
+        }
+    }
+}
+
+impl From<types::QueryField> for QueryField {
+    fn from(f: types::QueryField) -> Self {
+        match f {
+            types::QueryField::FirstName => QueryField::FirstName,
+            types::QueryField::LastName => QueryField::LastName,
+            types::QueryField::Email => QueryField::Email,
+            types::QueryField::Phone => QueryField::Phone,
+            types::QueryField::City => QueryField::City,
+            types::QueryField::State => QueryField::State,
+        }
+    }
+}
+
+impl From<types::ConnectorCapabilities> for ConnectorCapabilities {
+    fn from(c: types::ConnectorCapabilities) -> Self {
+        Self {
+            can_scan: c.can_scan,
+            can_delete: c.can_delete,
+            can_check_status: c.can_check_status,
+            required_fields: c.required_fields.into_iter().map(Into::into).collect(),
+            optional_fields: c.optional_fields.into_iter().map(Into::into).collect(),
+            data_types: c.data_types,
+            deletion_method: c.deletion_method.map(|m| match m {
+                types::DeletionMethod::Form => DeletionMethod::Form,
+                types::DeletionMethod::Email => DeletionMethod::Email,
+                types::DeletionMethod::Api => DeletionMethod::Api,
+                types::DeletionMethod::Manual => DeletionMethod::Manual,
+                types::DeletionMethod::Postal => DeletionMethod::Postal,
+            }),
+            needs_verification: c.needs_verification,
+            batch: c.batch,
+            processing_days: c.processing_days,
+        }
+    }
+}
+
+impl From<types::ManualStep> for ManualStep {
+    fn from(s: types::ManualStep) -> Self {
+        Self {
+            key: s.key,
+            kind: match s.kind {
+                types::ManualStepKind::OpenUrl => ManualStepKind::OpenUrl,
+                types::ManualStepKind::SolveCaptcha => ManualStepKind::SolveCaptcha,
+                types::ManualStepKind::PhoneVerification => ManualStepKind::PhoneVerification,
+                types::ManualStepKind::EmailLink => ManualStepKind::EmailLink,
+                types::ManualStepKind::PostalMail => ManualStepKind::PostalMail,
+                types::ManualStepKind::Other => ManualStepKind::Other,
+            },
+            instructions: s.instructions,
+            url: s.url,
+            prefill: s.prefill,
+        }
+    }
+}
+
+#[async_trait]
+impl BrokerConnector for WasmConnector {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn capabilities(&self) -> ConnectorCapabilities {
+        self.capabilities.clone()
+    }
+
+    async fn scan(&self, query: &PersonQuery) -> anyhow::Result<Vec<FoundRecord>> {
+        self.capabilities.validate(&self.name, query)?;
+        let query = types::PersonQuery::from(query);
+        let records = self
+            .call(move |broker, store| broker.call_scan(store, &query))
+            .await?;
+        Ok(records.into_iter().map(FoundRecord::from).collect())
+    }
+
+    async fn request_deletion(
+        &self,
+        query: &PersonQuery,
+        records: &[FoundRecord],
+    ) -> anyhow::Result<DeletionSubmission> {
+        let query = types::PersonQuery::from(query);
+        let records: Vec<types::FoundRecord> = records.iter().map(Into::into).collect();
+        let sub = self
+            .call(move |broker, store| broker.call_request_deletion(store, &query, &records))
+            .await?;
+        Ok(DeletionSubmission {
+            external_ref: sub.external_ref,
+            message: sub.message,
+            manual_steps: sub.manual_steps.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    async fn check_deletion_status(
+        &self,
+        external_ref: &str,
+    ) -> anyhow::Result<DeletionStatusCheck> {
+        let external_ref = external_ref.to_string();
+        let status = self
+            .call(move |broker, store| broker.call_check_deletion_status(store, &external_ref))
+            .await?;
+        Ok(DeletionStatusCheck {
+            status: status.status,
+            completed_at: status.completed_at,
+            message: status.message,
+        })
+    }
+
+    async fn resume_deletion(
+        &self,
+        external_ref: &str,
+        step_key: &str,
+        input: Option<&str>,
+    ) -> anyhow::Result<ManualStepOutcome> {
+        let (external_ref, step_key) = (external_ref.to_string(), step_key.to_string());
+        let input = input.map(str::to_string);
+        let outcome = self
+            .call(move |broker, store| {
+                broker.call_resume_deletion(store, &external_ref, &step_key, input.as_deref())
+            })
+            .await?;
+        Ok(ManualStepOutcome {
+            status: outcome.status,
+            external_ref: outcome.external_ref,
+            message: outcome.message,
+            next_steps: outcome.next_steps.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    fn workflow(&self) -> Option<Workflow> {
+        self.workflow.clone()
+    }
+
+    async fn run_step(
+        &self,
+        external_ref: &str,
+        step: &str,
+        data: &serde_json::Map<String, serde_json::Value>,
+    ) -> anyhow::Result<StepOutcome> {
+        let (external_ref, step) = (external_ref.to_string(), step.to_string());
+        let data = serde_json::to_string(data)?;
+        let outcome = self
+            .call(move |broker, store| broker.call_run_step(store, &external_ref, &step, &data))
+            .await?;
+        serde_json::from_str(&outcome).map_err(|e| {
+            anyhow::anyhow!(
+                "WASM connector '{}' returned an invalid step outcome: {e}",
+                self.id
+            )
+        })
+    }
+}
+
+/// Where a pinned module is cached once downloaded.
+fn module_path(dir: &Path, manifest: &WasmManifest) -> PathBuf {
+    dir.join(format!("{}.wasm", manifest.sha256.to_ascii_lowercase()))
+}
+
+/// Download the modules of all WASM brokers that are not cached yet.
+/// Returns how many were fetched; modules failing their hash are refused.
+pub async fn fetch_modules(db: &Database, http: &HttpService) -> anyhow::Result<usize> {
+    let dir = crate::config::wasm_dir()?;
+    let client = http.builder().build()?;
+    let mut fetched = 0;
+    for broker in db.list_brokers(None)? {
+        let Some(manifest) = broker_manifest(&broker)? else {
+            continue;
+        };
+        let path = module_path(&dir, &manifest);
+        if path.exists() {
+            continue;
+        }
+        let bytes = client
+            .get(&manifest.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        if let Err(e) = verify_hash(&bytes, &manifest.sha256) {
+            tracing::warn!("Refusing WASM module for '{}': {e}", broker.id);
+            continue;
+        }
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(&path, &bytes)?;
+        fetched += 1;
+    }
+    Ok(fetched)
+}
+
+fn broker_manifest(broker: &crate::db::models::Broker) -> anyhow::Result<Option<WasmManifest>> {
+    if broker.connector.as_deref() != Some(WASM_CONNECTOR) {
+        return Ok(None);
+    }
+    match &broker.wasm_manifest {
+        Some(json) => Ok(Some(serde_json::from_str(json)?)),
+        None => {
+            tracing::warn!("Broker '{}' uses WASM but has no manifest", broker.id);
+            Ok(None)
+        }
+    }
+}
+
+/// Where the `ModuleInfo` of a cached module is remembered.
+fn info_path(module: &Path) -> PathBuf {
+    module.with_extension("json")
+}
+
+/// Compile the module at `path` to learn its capabilities and workflow, and
+/// remember them so later runs can skip compiling it until it is called.
+async fn load_and_remember(
+    broker: &crate::db::models::Broker,
+    path: &Path,
+    manifest: &WasmManifest,
+    config: &WasmConfig,
+    http: &HttpService,
+) -> anyhow::Result<WasmConnector> {
+    let bytes = std::fs::read(path)?;
+    let connector =
+        WasmConnector::load(&broker.id, &broker.name, &bytes, manifest, config, http).await?;
+    let info = ModuleInfo {
+        capabilities: connector.capabilities.clone(),
+        workflow: connector.workflow.clone(),
+    };
+    let json = serde_json::to_string(&info)?;
+    if let Err(e) = std::fs::write(info_path(path), json) {
+        tracing::warn!("Could not remember capabilities of '{}': {e}", broker.id);
+    }
+    Ok(connector)
+}
+
+/// Register a `WasmConnector` for every registry broker whose module is
+/// cached. Modules are compiled on first use once their capabilities and
+/// workflow are known. Compiled-in connectors take precedence; broken modules are
+/// skipped with a warning.
+pub async fn add_wasm_connectors(
+    connectors: &mut HashMap<String, Arc<dyn BrokerConnector>>,
+    db: &Database,
+    config: &WasmConfig,
+    http: &HttpService,
+) -> anyhow::Result<()> {
+    let dir = crate::config::wasm_dir()?;
+    for broker in db.list_brokers(None)? {
+        if connectors.contains_key(&broker.id) {
+            continue;
+        }
+        let Some(manifest) = broker_manifest(&broker)? else {
+            continue;
+        };
+        let path = module_path(&dir, &manifest);
+        if !path.exists() {
+            tracing::warn!(
+                "WASM module for '{}' is not downloaded; run `data-breaker registry update`",
+                broker.id
+            );
+            continue;
+        }
+        let remembered = std::fs::read_to_string(info_path(&path))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+        let connector = match remembered {
+            Some(info) => WasmConnector::lazy(
+                &broker.id,
+                &broker.name,
+                &path,
+                &manifest,
+                info,
+                config,
+                http,
+            ),
+            None => load_and_remember(&broker, &path, &manifest, config, http).await,
+        };
+        match connector {
+            Ok(c) => {
+                connectors.insert(broker.id.clone(), Arc::new(c));
+            }
+            Err(e) => tracing::warn!("Skipping WASM connector '{}': {e:#}", broker.id),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Encode `test_connector.wat` as a component of the `broker` world.
+    fn component_bytes() -> Vec<u8> {
+        let mut module = wat::parse_str(include_str!("test_connector.wat")).unwrap();
+        let mut resolve = wit_parser::Resolve::default();
+        let package = resolve
+            .push_str("connector.wit", include_str!("connector.wit"))
+            .unwrap();
+        let world = resolve.select_world(&[package], Some("broker")).unwrap();
+        wit_component::embed_component_metadata(
+            &mut module,
+            &resolve,
+            world,
+            wit_component::StringEncoding::UTF8,
+        )
+        .unwrap();
+        wit_component::ComponentEncoder::default()
+            .module(&module)
+            .unwrap()
+            .validate(true)
+            .encode()
+            .unwrap()
+    }
+
+    fn manifest(bytes: &[u8], domains: &[&str]) -> WasmManifest {
+        WasmManifest {
+            url: "https://example.com/test.wasm".into(),
+            sha256: sha256_hex(bytes),
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    fn query(city: &str) -> PersonQuery {
+        PersonQuery {
+            first_name: "Jane".into(),
+            last_name: "Doe".into(),
+            email: None,
+            phone: None,
+            city: Some(city.into()),
+            state: None,
+        }
+    }
+
+    async fn text_server(listener: TcpListener) {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let _ = stream.read(&mut buf).await;
+            let body = "Jane Doe";
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(resp.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wasm_scan_over_allowed_http() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = format!("http://{}/search", listener.local_addr().unwrap());
+        tokio::spawn(text_server(listener));
+
+        let bytes = component_bytes();
+        let manifest = manifest(&bytes, &["127.0.0.1"]);
+        let connector = WasmConnector::load(
+            "wasm-test",
+            "Wasm Test",
+            &bytes,
+            &manifest,
+            &WasmConfig::default(),
+            &HttpService::default(),
+        )
+        .await
+        .unwrap();
+        let caps = connector.capabilities();
+        assert!(caps.can_scan);
+        assert!(!caps.can_delete);
+        assert_eq!(caps.required_fields, vec![QueryField::City]);
+        assert_eq!(caps.data_types, vec!["name".to_string()]);
+        assert_eq!(caps.deletion_method, None);
+        assert_eq!(caps.processing_days, Some(10));
+
+        let err = connector
+            .scan(&PersonQuery {
+                city: None,
+                ..query(&url)
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<crate::error::AppError>(),
+            Some(crate::error::AppError::MissingField { field, .. }) if field == "city"
+        ));
+
+        let records = connector.scan(&query(&url)).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].data_type, "name");
+        assert_eq!(records[0].data_value, "Jane Doe");
+        assert!(records[0].profile_url.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_wasm_fetch_outside_manifest_is_refused() {
+        let bytes = component_bytes();
+        let manifest = manifest(&bytes, &["example.com"]);
+        let connector = WasmConnector::load(
+            "wasm-test",
+            "Wasm Test",
+            &bytes,
+            &manifest,
+            &WasmConfig::default(),
+            &HttpService::default(),
+        )
+        .await
+        .unwrap();
+        let err = connector
+            .scan(&query("https://evil.example.net/"))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("wasm-test may not access"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wasm_hash_mismatch_is_refused() {
+        let bytes = component_bytes();
+        let mut manifest = manifest(&bytes, &[]);
+        manifest.sha256 = sha256_hex(b"something else");
+        let result = WasmConnector::load(
+            "wasm-test",
+            "Wasm Test",
+            &bytes,
+            &manifest,
+            &WasmConfig::default(),
+            &HttpService::default(),
+        )
+        .await;
+        assert!(result.is_err_and(|e| e.to_string().contains("does not match")));
+    }
+
+    #[tokio::test]
+    async fn test_lazy_connector_compiles_on_first_call() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = format!("http://{}/search", listener.local_addr().unwrap());
+        tokio::spawn(text_server(listener));
+
+        let bytes = component_bytes();
+        let manifest = manifest(&bytes, &["127.0.0.1"]);
+        let dir = std::env::temp_dir().join(format!("db-wasm-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = module_path(&dir, &manifest);
+        std::fs::write(&path, b"not a module").unwrap();
+
+        let info = ModuleInfo {
+            capabilities: ConnectorCapabilities {
+                can_scan: true,
+                ..Default::default()
+            },
+            workflow: None,
+        };
+        let lazy = |path: &Path| {
+            WasmConnector::lazy(
+                "wasm-test",
+                "Wasm Test",
+                path,
+                &manifest,
+                info.clone(),
+                &WasmConfig::default(),
+                &HttpService::default(),
+            )
+            .unwrap()
+        };
+        // Nothing is read or compiled until the connector is called.
+        let connector = lazy(&path);
+        assert!(connector.capabilities().can_scan);
+        let err = connector.scan(&query(&url)).await.unwrap_err();
+        assert!(format!("{err:#}").contains("does not match"), "{err:#}");
+
+        std::fs::write(&path, &bytes).unwrap();
+        let records = lazy(&path).scan(&query(&url)).await.unwrap();
+        assert_eq!(records[0].data_value, "Jane Doe");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wasm_workflow_and_run_step() {
+        let bytes = component_bytes();
+        let manifest = manifest(&bytes, &[]);
+        let connector = WasmConnector::load(
+            "wasm-test",
+            "Wasm Test",
+            &bytes,
+            &manifest,
+            &WasmConfig::default(),
+            &HttpService::default(),
+        )
+        .await
+        .unwrap();
+        let workflow = connector.workflow().unwrap();
+        assert_eq!(workflow.steps.len(), 1);
+        assert_eq!(workflow.steps[0].name, "submit");
+
+        let mut data = serde_json::Map::new();
+        data.insert("message".into(), "submitted".into());
+        let outcome = connector.run_step("R1", "submit", &data).await.unwrap();
+        assert_eq!(outcome.message.as_deref(), Some("submitted"));
+
+        data.insert("goto".into(), 7.into());
+        let err = connector.run_step("R1", "submit", &data).await.unwrap_err();
+        assert!(err.to_string().contains("invalid step outcome"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_wasm_runaway_call_runs_out_of_fuel() {
+        let bytes = component_bytes();
+        let manifest = manifest(&bytes, &[]);
+        let config = WasmConfig {
+            fuel: 1_000_000,
+            ..WasmConfig::default()
+        };
+        let connector = WasmConnector::load(
+            "wasm-test",
+            "Wasm Test",
+            &bytes,
+            &manifest,
+            &config,
+            &HttpService::default(),
+        )
+        .await
+        .unwrap();
+        let err = connector
+            .resume_deletion("ref", "step", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("ran out of fuel"));
+    }
+}
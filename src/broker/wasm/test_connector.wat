@@ -0,0 +1,96 @@
+;; Core module behind the test component. `scan` fetches the URL passed in
+;; `query.city` and returns the response body as a single `name` record, or
+;; the host's error string. It needs `city`, returns `name` records and takes
+;; 10 days to process a request. Its workflow is a single `submit` action,
+;; and `run-step` echoes `data` back as the step's outcome.
+(module
+  (import "data-breaker:connector/http@0.2.0" "fetch"
+    (func $fetch (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)))
+
+  (memory (export "memory") 2)
+  (global $heap (mut i32) (i32.const 1024))
+
+  (data (i32.const 16) "GET")
+  (data (i32.const 32) "name")
+  ;; capabilities record (40 bytes): can-scan, required-fields [city] at
+  ;; 560, data-types ["name"] at 568, processing-days some(10)
+  (data (i32.const 512)
+    "\01\00\00\00" "\30\02\00\00" "\01\00\00\00"
+    "\00\00\00\00" "\00\00\00\00"
+    "\38\02\00\00" "\01\00\00\00"
+    "\00\00\00\00"
+    "\01\00\00\00" "\0a\00\00\00")
+  (data (i32.const 560) "\04")
+  (data (i32.const 568) "\20\00\00\00" "\04\00\00\00")
+  ;; workflow: some(string at 640)
+  (data (i32.const 600) "\01\00\00\00" "\80\02\00\00" "\2d\00\00\00")
+  (data (i32.const 640) "{\"steps\":[{\"name\":\"submit\",\"type\":\"action\"}]}")
+
+  (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+    (local $ptr i32)
+    ;; align the bump pointer up to the requested alignment
+    (local.set $ptr
+      (i32.and
+        (i32.add (global.get $heap) (i32.sub (local.get 2) (i32.const 1)))
+        (i32.sub (i32.const 0) (local.get 2))))
+    (global.set $heap (i32.add (local.get $ptr) (local.get 3)))
+    (local.get $ptr))
+
+  (func (export "data-breaker:connector/connector@0.2.0#capabilities") (result i32)
+    (i32.const 512))
+
+  ;; 16 flat params: first, last, email, phone, city, state
+  (func (export "data-breaker:connector/connector@0.2.0#scan")
+    (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)
+    (result i32)
+    ;; GET city-url, no headers, no body; result written at 64
+    (call $fetch
+      (i32.const 16) (i32.const 3)
+      (local.get 11) (local.get 12)
+      (i32.const 0) (i32.const 0)
+      (i32.const 0) (i32.const 0) (i32.const 0)
+      (i32.const 64))
+    (if (i32.load8_u (i32.const 64))
+      (then
+        ;; err(string): pass the message through
+        (i32.store8 (i32.const 128) (i32.const 1))
+        (i32.store (i32.const 132) (i32.load (i32.const 68)))
+        (i32.store (i32.const 136) (i32.load (i32.const 72))))
+      (else
+        ;; ok([record]) with the record at 160
+        (i32.store8 (i32.const 128) (i32.const 0))
+        (i32.store (i32.const 132) (i32.const 160))
+        (i32.store (i32.const 136) (i32.const 1))
+        (i32.store (i32.const 160) (i32.const 32))
+        (i32.store (i32.const 164) (i32.const 4))
+        (i32.store (i32.const 168) (i32.load (i32.const 88)))
+        (i32.store (i32.const 172) (i32.load (i32.const 92)))
+        (i32.store8 (i32.const 176) (i32.const 0))
+        (i32.store8 (i32.const 188) (i32.const 0))))
+    (i32.const 128))
+
+  (func (export "data-breaker:connector/connector@0.2.0#request-deletion")
+    (param i32) (result i32)
+    unreachable)
+
+  (func (export "data-breaker:connector/connector@0.2.0#check-deletion-status")
+    (param i32 i32) (result i32)
+    unreachable)
+
+  (func (export "data-breaker:connector/connector@0.2.0#resume-deletion")
+    (param i32 i32 i32 i32 i32 i32 i32) (result i32)
+    ;; burn fuel forever
+    (loop $spin (br $spin))
+    unreachable)
+
+  (func (export "data-breaker:connector/connector@0.2.0#workflow") (result i32)
+    (i32.const 600))
+
+  ;; external-ref, step, data: ok(data)
+  (func (export "data-breaker:connector/connector@0.2.0#run-step")
+    (param i32 i32 i32 i32 i32 i32) (result i32)
+    (i32.store8 (i32.const 128) (i32.const 0))
+    (i32.store (i32.const 132) (local.get 4))
+    (i32.store (i32.const 136) (local.get 5))
+    (i32.const 128))
+)
@@ -0,0 +1,904 @@
+//! Multi-step deletion flows.
+//!
+//! `request_deletion` is a single call, but many opt-outs are not: submit a
+//! form, wait for the confirmation email, click its link, upload an ID, wait
+//! for processing. A connector describes what follows the submission as a
+//! [`Workflow`] of named steps and performs its `action` steps in
+//! `BrokerConnector::run_step`.
+//!
+//! The engine keeps the current step and the data collected so far in the
+//! `deletion_workflows` table, one row per submission, and moves flows along
+//! whenever `data-breaker status`, `tasks done` or the mailbox watcher runs.
+//! Advancing is idempotent: a step that is not ready is left alone, waits are
+//! stored as absolute times so they survive restarts, and every write checks
+//! the row's revision so two runs cannot both move the same flow.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{BrokerConnector, ManualStep, ManualStepKind};
+use crate::db::Database;
+use crate::db::models::DeletionWorkflow;
+use crate::mailbox::ReplyKind;
+
+/// Step name that finishes a flow.
+pub const END: &str = "end";
+
+/// Prefix of the `step_key` of manual tasks opened by a workflow.
+pub const TASK_KEY_PREFIX: &str = "workflow:";
+
+/// Runs of a failing action step before the flow gives up.
+const MAX_ATTEMPTS: i64 = 5;
+
+/// Steps taken in one go, so a cycle of actions cannot spin forever.
+const MAX_STEPS_PER_RUN: usize = 32;
+
+/// Request statuses the engine does not overwrite: a broker reply or a
+/// status check already settled the request.
+const SETTLED_STATUSES: &[&str] = &["completed", "not_found", "rejected", "failed"];
+
+/// The steps of a flow. The first one is entered right after
+/// `request_deletion`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    pub steps: Vec<WorkflowStep>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: StepKind,
+    /// Step to go to once this one is done. Defaults to the following step
+    /// in the list; [`END`] finishes the flow.
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StepKind {
+    /// Performed by the connector's `run_step`, e.g. submitting a form.
+    Action,
+    /// Wait a fixed time, e.g. the broker's processing period.
+    Timer { secs: u64 },
+    /// Wait for a broker reply classified as `reply` (see `mailbox`). Past
+    /// `timeout_secs` the flow goes to `on_timeout`, or fails without one.
+    Email {
+        reply: String,
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+        #[serde(default)]
+        on_timeout: Option<String>,
+    },
+    /// Open a manual task and wait for the user to finish it. What they
+    /// enter is kept in the data under the step's name.
+    UserTask {
+        kind: ManualStepKind,
+        instructions: String,
+        #[serde(default)]
+        url: Option<String>,
+    },
+}
+
+impl StepKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StepKind::Action => "action",
+            StepKind::Timer { .. } => "timer",
+            StepKind::Email { .. } => "email",
+            StepKind::UserTask { .. } => "user_task",
+        }
+    }
+}
+
+/// What an action step did.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StepOutcome {
+    /// Go to this step instead of the step's `next`.
+    pub goto: Option<String>,
+    /// Not done yet: run the step again after this many seconds.
+    pub retry_secs: Option<u64>,
+    /// Finish the flow with this deletion status, e.g. `rejected`.
+    pub status: Option<String>,
+    /// Merged into the flow's data.
+    pub data: Map<String, Value>,
+    pub message: Option<String>,
+}
+
+impl Workflow {
+    pub fn step(&self, name: &str) -> Option<&WorkflowStep> {
+        self.steps.iter().find(|s| s.name == name)
+    }
+
+    /// Where the flow goes after `step` when nothing says otherwise.
+    fn next_of(&self, step: &WorkflowStep) -> String {
+        if let Some(next) = &step.next {
+            return next.clone();
+        }
+        let position = self.steps.iter().position(|s| s.name == step.name);
+        position
+            .and_then(|i| self.steps.get(i + 1))
+            .map_or_else(|| END.to_string(), |s| s.name.clone())
+    }
+
+    /// Check step names are unique and every transition has a target.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.steps.is_empty() {
+            anyhow::bail!("workflow has no steps");
+        }
+        let mut names = HashSet::new();
+        for step in &self.steps {
+            if step.name == END || !names.insert(step.name.as_str()) {
+                anyhow::bail!("workflow step name '{}' is reserved or taken", step.name);
+            }
+        }
+        let known = |target: &str| target == END || names.contains(target);
+        for step in &self.steps {
+            let mut targets: Vec<&str> = step.next.iter().map(String::as_str).collect();
+            if let StepKind::Email {
+                reply, on_timeout, ..
+            } = &step.kind
+            {
+                if !ReplyKind::ALL.iter().any(|k| k.as_str() == reply) {
+                    anyhow::bail!(
+                        "workflow step '{}' waits for unknown reply '{reply}'",
+                        step.name
+                    );
+                }
+                targets.extend(on_timeout.as_deref());
+            }
+            if let Some(target) = targets.into_iter().find(|t| !known(t)) {
+                anyhow::bail!(
+                    "workflow step '{}' leads to unknown step '{target}'",
+                    step.name
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Start the workflow of a fresh submission, if the connector has one, and
+/// take its first steps. Starting twice leaves the existing flow alone.
+pub async fn start(
+    db: &Database,
+    connector: &dyn BrokerConnector,
+    external_ref: &str,
+) -> anyhow::Result<Option<DeletionWorkflow>> {
+    let Some(workflow) = connector.workflow() else {
+        return Ok(None);
+    };
+    workflow
+        .validate()
+        .map_err(|e| anyhow::anyhow!("{}: {e}", connector.name()))?;
+
+    let row = match db.get_deletion_workflow(connector.id(), external_ref)? {
+        Some(row) => row,
+        None => {
+            let now = Utc::now().to_rfc3339();
+            let row = DeletionWorkflow {
+                id: uuid::Uuid::new_v4().to_string(),
+                broker_id: connector.id().to_string(),
+                external_ref: external_ref.to_string(),
+                step: workflow.steps[0].name.clone(),
+                state: "active".to_string(),
+                data_json: "{}".to_string(),
+                entered_at: now.clone(),
+                wake_at: None,
+                attempts: 0,
+                last_error: None,
+                revision: 0,
+                created_at: now.clone(),
+                updated_at: now,
+            };
+            db.insert_deletion_workflow(&row)?;
+            sync_requests(db, &row, &workflow)?;
+            row
+        }
+    };
+    let mut engine = Engine::new(db, connector, &workflow, row)?;
+    engine.run().await?;
+    Ok(Some(engine.row))
+}
+
+/// Advance every active workflow as far as it can go right now. Returns
+/// how many moved; a flow that fails to advance is logged and skipped.
+pub async fn advance_all(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+) -> anyhow::Result<usize> {
+    let mut moved = 0;
+    for row in db.list_deletion_workflows(Some("active"))? {
+        match advance(db, connectors, row).await {
+            Ok(true) => moved += 1,
+            Ok(false) => {}
+            Err(e) => tracing::warn!("Could not advance a deletion workflow: {e}"),
+        }
+    }
+    Ok(moved)
+}
+
+/// Advance the workflow of one submission, if it has one. Returns whether
+/// it moved.
+pub async fn advance_ref(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    broker_id: &str,
+    external_ref: &str,
+) -> anyhow::Result<bool> {
+    match db.get_deletion_workflow(broker_id, external_ref)? {
+        Some(row) if row.state == "active" => advance(db, connectors, row).await,
+        _ => Ok(false),
+    }
+}
+
+async fn advance(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    row: DeletionWorkflow,
+) -> anyhow::Result<bool> {
+    let Some(connector) = connectors.get(&row.broker_id) else {
+        anyhow::bail!("No connector for broker '{}'", row.broker_id);
+    };
+    let Some(workflow) = connector.workflow() else {
+        anyhow::bail!("{} no longer has a workflow", connector.name());
+    };
+    let mut engine = Engine::new(db, connector.as_ref(), &workflow, row)?;
+    engine.run().await
+}
+
+/// Whether a manual task belongs to a workflow rather than to the
+/// connector's `resume_deletion`.
+pub fn owns_task(step_key: &str) -> bool {
+    step_key.starts_with(TASK_KEY_PREFIX)
+}
+
+/// A short description of where a flow stands, for the CLI.
+pub fn describe(db: &Database, row: &DeletionWorkflow, connector: &dyn BrokerConnector) -> String {
+    match row.state.as_str() {
+        "active" => {
+            let kind = connector
+                .workflow()
+                .and_then(|w| w.step(&row.step).map(|s| s.kind.as_str()))
+                .unwrap_or("unknown");
+            let mut text = format!("at step '{}' ({kind})", row.step);
+            if let Some(wake) = &row.wake_at {
+                text.push_str(&format!(", next look after {wake}"));
+            }
+            if kind == "user_task"
+                && db
+                    .count_open_manual_tasks(&row.broker_id, &row.external_ref)
+                    .unwrap_or(0)
+                    > 0
+            {
+                text.push_str(", see `data-breaker tasks list`");
+            }
+            text
+        }
+        state => state.to_string(),
+    }
+}
+
+/// One flow being advanced.
+struct Engine<'a> {
+    db: &'a Database,
+    connector: &'a dyn BrokerConnector,
+    workflow: &'a Workflow,
+    row: DeletionWorkflow,
+    data: Map<String, Value>,
+    /// Another run changed the flow; this one stops.
+    stale: bool,
+}
+
+impl<'a> Engine<'a> {
+    fn new(
+        db: &'a Database,
+        connector: &'a dyn BrokerConnector,
+        workflow: &'a Workflow,
+        row: DeletionWorkflow,
+    ) -> anyhow::Result<Self> {
+        let data = serde_json::from_str(&row.data_json)?;
+        Ok(Self {
+            db,
+            connector,
+            workflow,
+            row,
+            data,
+            stale: false,
+        })
+    }
+
+    /// Take steps until the flow has to wait. Returns whether it moved.
+    async fn run(&mut self) -> anyhow::Result<bool> {
+        let mut moved = false;
+        for _ in 0..MAX_STEPS_PER_RUN {
+            if self.stale || self.row.state != "active" {
+                break;
+            }
+            if let Some(status) = self.settled_status()? {
+                // Leave the request as the broker left it.
+                self.row.state = "finished".to_string();
+                self.row.wake_at = None;
+                tracing::info!("{}: request already {status}", self.row.broker_id);
+                return Ok(self.save()? || moved);
+            }
+            let now = Utc::now();
+            if self.row.wake_at.as_deref().and_then(parse_time) > Some(now) {
+                break;
+            }
+            let Some(step) = self.workflow.step(&self.row.step).cloned() else {
+                self.fail(&format!("unknown workflow step '{}'", self.row.step))?;
+                return Ok(true);
+            };
+            match self.take(&step, now).await? {
+                Some(next) => {
+                    if !self.enter(&next)? {
+                        break;
+                    }
+                    moved = true;
+                }
+                None => break,
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Work on the current step: the step to go to once it is done, or
+    /// `None` while it has to wait (the wait is already saved).
+    async fn take(
+        &mut self,
+        step: &WorkflowStep,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<String>> {
+        let entered = parse_time(&self.row.entered_at).unwrap_or(now);
+        match &step.kind {
+            StepKind::Action => {
+                // Claim the run first, so a concurrent run does not repeat it.
+                self.row.attempts += 1;
+                if !self.save()? {
+                    return Ok(None);
+                }
+                let ext_ref = self.row.external_ref.clone();
+                match self
+                    .connector
+                    .run_step(&ext_ref, &step.name, &self.data)
+                    .await
+                {
+                    Ok(outcome) => {
+                        self.data.extend(outcome.data);
+                        self.row.attempts = 0;
+                        self.row.last_error = None;
+                        if let Some(status) = outcome.status {
+                            self.finish(&status, outcome.message.as_deref())?;
+                            return Ok(None);
+                        }
+                        if let Some(secs) = outcome.retry_secs {
+                            self.wait_until(now + seconds(secs))?;
+                            return Ok(None);
+                        }
+                        Ok(Some(
+                            outcome.goto.unwrap_or_else(|| self.workflow.next_of(step)),
+                        ))
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "{}: workflow step '{}' failed: {e}",
+                            self.row.broker_id,
+                            step.name
+                        );
+                        self.row.last_error = Some(e.to_string());
+                        if self.row.attempts >= MAX_ATTEMPTS {
+                            self.fail(&format!("Step '{}' failed: {e}", step.name))?;
+                        } else {
+                            // 2, 4, 8, 16 minutes.
+                            self.wait_until(now + seconds(60 << self.row.attempts))?;
+                        }
+                        Ok(None)
+                    }
+                }
+            }
+            StepKind::Timer { secs } => {
+                let due = entered + seconds(*secs);
+                if now < due {
+                    self.wait_until(due)?;
+                    return Ok(None);
+                }
+                Ok(Some(self.workflow.next_of(step)))
+            }
+            StepKind::Email {
+                reply,
+                timeout_secs,
+                on_timeout,
+            } => {
+                if self.db.has_email_reply(
+                    &self.row.broker_id,
+                    &self.row.external_ref,
+                    reply,
+                    &self.row.entered_at,
+                )? {
+                    return Ok(Some(self.workflow.next_of(step)));
+                }
+                let Some(deadline) = timeout_secs.map(|s| entered + seconds(s)) else {
+                    // Replies are seen whenever the mailbox is checked.
+                    return Ok(None);
+                };
+                if now < deadline {
+                    self.wait_until(deadline)?;
+                    return Ok(None);
+                }
+                match on_timeout {
+                    Some(target) => Ok(Some(target.clone())),
+                    None => {
+                        self.fail(&format!("No '{reply}' reply from the broker in time"))?;
+                        Ok(None)
+                    }
+                }
+            }
+            StepKind::UserTask {
+                kind,
+                instructions,
+                url,
+            } => {
+                let key = format!("{TASK_KEY_PREFIX}{}", step.name);
+                let tasks: Vec<_> = self
+                    .db
+                    .list_manual_tasks_for_ref(&self.row.broker_id, &self.row.external_ref)?
+                    .into_iter()
+                    .filter(|t| t.step_key == key && t.created_at >= self.row.entered_at)
+                    .collect();
+                if tasks.is_empty() {
+                    // Opened here rather than on entry, so a run that stopped
+                    // in between still gets its task.
+                    crate::cli::tasks::record_manual_steps(
+                        self.db,
+                        &self.row.broker_id,
+                        &self.row.external_ref,
+                        &[ManualStep {
+                            key,
+                            kind: *kind,
+                            instructions: instructions.clone(),
+                            url: url.clone(),
+                            prefill: vec![],
+                        }],
+                    )?;
+                    return Ok(None);
+                }
+                if tasks.iter().any(|t| t.status == "open") {
+                    return Ok(None);
+                }
+                if let Some(response) = tasks.iter().rev().find_map(|t| t.response.clone()) {
+                    self.data.insert(step.name.clone(), Value::String(response));
+                }
+                Ok(Some(self.workflow.next_of(step)))
+            }
+        }
+    }
+
+    /// Move to `next`. Returns `false` if another run got there first.
+    fn enter(&mut self, next: &str) -> anyhow::Result<bool> {
+        if next == END {
+            self.finish("submitted", None)?;
+            return Ok(true);
+        }
+        self.row.step = next.to_string();
+        self.row.entered_at = Utc::now().to_rfc3339();
+        self.row.wake_at = None;
+        self.row.attempts = 0;
+        self.row.last_error = None;
+        if !self.save()? {
+            return Ok(false);
+        }
+        tracing::info!("{}: workflow entered step '{next}'", self.row.broker_id);
+        sync_requests(self.db, &self.row, self.workflow)?;
+        Ok(true)
+    }
+
+    fn finish(&mut self, status: &str, message: Option<&str>) -> anyhow::Result<()> {
+        self.row.state = "finished".to_string();
+        self.row.wake_at = None;
+        if self.save()? {
+            self.update_requests(status, message)?;
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, message: &str) -> anyhow::Result<()> {
+        self.row.state = "failed".to_string();
+        self.row.wake_at = None;
+        self.row.last_error = Some(message.to_string());
+        if self.save()? {
+            self.update_requests("failed", Some(message))?;
+        }
+        Ok(())
+    }
+
+    fn wait_until(&mut self, time: DateTime<Utc>) -> anyhow::Result<()> {
+        self.row.wake_at = Some(time.to_rfc3339());
+        self.save()?;
+        Ok(())
+    }
+
+    fn update_requests(&self, status: &str, message: Option<&str>) -> anyhow::Result<()> {
+        self.db.update_deletion_requests_by_ref(
+            &self.row.broker_id,
+            &self.row.external_ref,
+            status,
+            &self.row.external_ref,
+            message,
+            &Utc::now().to_rfc3339(),
+        )?;
+        Ok(())
+    }
+
+    /// The status all of the submission's requests already reached, if it
+    /// is final.
+    fn settled_status(&self) -> anyhow::Result<Option<String>> {
+        let statuses: HashSet<String> = self
+            .db
+            .list_deletion_requests(Some(&self.row.broker_id))?
+            .into_iter()
+            .filter(|r| r.external_ref.as_deref() == Some(self.row.external_ref.as_str()))
+            .map(|r| r.status)
+            .collect();
+        Ok(match statuses.into_iter().collect::<Vec<_>>().as_slice() {
+            [status] if SETTLED_STATUSES.contains(&status.as_str()) => Some(status.clone()),
+            _ => None,
+        })
+    }
+
+    /// Write the row back. `false` means another run changed it first and
+    /// this run stops.
+    fn save(&mut self) -> anyhow::Result<bool> {
+        if self.stale {
+            return Ok(false);
+        }
+        self.row.data_json = serde_json::to_string(&self.data)?;
+        self.row.updated_at = Utc::now().to_rfc3339();
+        if self.db.update_deletion_workflow(&self.row)? {
+            self.row.revision += 1;
+            return Ok(true);
+        }
+        tracing::debug!("{}: workflow changed by another run", self.row.broker_id);
+        self.stale = true;
+        Ok(false)
+    }
+}
+
+/// Show the step in the requests' status: the user's turn or the broker's.
+fn sync_requests(db: &Database, row: &DeletionWorkflow, workflow: &Workflow) -> anyhow::Result<()> {
+    let user_task = matches!(
+        workflow.step(&row.step).map(|s| &s.kind),
+        Some(StepKind::UserTask { .. })
+    );
+    let status = if user_task || db.count_open_manual_tasks(&row.broker_id, &row.external_ref)? > 0
+    {
+        "awaiting_user"
+    } else {
+        "submitted"
+    };
+    db.update_deletion_requests_by_ref(
+        &row.broker_id,
+        &row.external_ref,
+        status,
+        &row.external_ref,
+        None,
+        &Utc::now().to_rfc3339(),
+    )?;
+    Ok(())
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+fn seconds(secs: u64) -> chrono::Duration {
+    chrono::Duration::seconds(secs.min(i64::MAX as u64) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    use super::*;
+    use crate::broker::{
+        ConnectorCapabilities, DeletionStatusCheck, DeletionSubmission, FoundRecord, PersonQuery,
+    };
+    use crate::db::models::{Broker, DeletionRequest, EmailEvidence};
+
+    /// Submits, has the user confirm, waits for the broker's reply and then
+    /// for its processing period.
+    struct FlowBroker {
+        runs: AtomicUsize,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl BrokerConnector for FlowBroker {
+        fn id(&self) -> &str {
+            "acme"
+        }
+        fn name(&self) -> &str {
+            "Acme Data"
+        }
+        fn capabilities(&self) -> ConnectorCapabilities {
+            ConnectorCapabilities {
+                can_delete: true,
+                ..Default::default()
+            }
+        }
+        async fn scan(&self, _: &PersonQuery) -> anyhow::Result<Vec<FoundRecord>> {
+            Ok(vec![])
+        }
+        async fn request_deletion(
+            &self,
+            _: &PersonQuery,
+            _: &[FoundRecord],
+        ) -> anyhow::Result<DeletionSubmission> {
+            anyhow::bail!("not used in this test")
+        }
+        async fn check_deletion_status(&self, _: &str) -> anyhow::Result<DeletionStatusCheck> {
+            anyhow::bail!("not used in this test")
+        }
+        fn workflow(&self) -> Option<Workflow> {
+            Some(
+                serde_json::from_value(json!({ "steps": [
+                    { "name": "submit", "type": "action" },
+                    { "name": "confirm", "type": "user_task", "kind": "email_link",
+                      "instructions": "Click the link Acme sent you" },
+                    { "name": "reply", "type": "email", "reply": "acknowledged" },
+                    { "name": "processing", "type": "timer", "secs": 3600 },
+                ]}))
+                .unwrap(),
+            )
+        }
+        async fn run_step(
+            &self,
+            _: &str,
+            step: &str,
+            _: &Map<String, Value>,
+        ) -> anyhow::Result<StepOutcome> {
+            assert_eq!(step, "submit");
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                anyhow::bail!("form not found");
+            }
+            let mut data = Map::new();
+            data.insert("ticket".into(), json!("T-1"));
+            Ok(StepOutcome {
+                data,
+                ..Default::default()
+            })
+        }
+    }
+
+    fn setup() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        let now = Utc::now().to_rfc3339();
+        db.upsert_broker(&Broker {
+            id: "acme".into(),
+            name: "Acme Data".into(),
+            website: Some("https://www.acme.example".into()),
+            description: None,
+            category: None,
+            connector: None,
+            privacy_email: None,
+            postal_address: None,
+            script: None,
+            wasm_manifest: None,
+            registry_source: None,
+            registry_updated_at: None,
+            retired_at: None,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: None,
+            shared_database: false,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+        })
+        .unwrap();
+        db.insert_deletion_request(&DeletionRequest {
+            id: "del-1".into(),
+            broker_id: "acme".into(),
+            personal_record_id: None,
+            status: "submitted".into(),
+            submitted_at: Some(now.clone()),
+            completed_at: None,
+            error_message: None,
+            external_ref: Some("R1".into()),
+            created_at: now.clone(),
+            updated_at: now,
+            covered_by: None,
+        })
+        .unwrap();
+        db
+    }
+
+    fn status(db: &Database) -> String {
+        db.list_deletion_requests(None).unwrap()[0].status.clone()
+    }
+
+    fn flow(db: &Database) -> DeletionWorkflow {
+        db.get_deletion_workflow("acme", "R1").unwrap().unwrap()
+    }
+
+    /// Pretend the current step was entered, and last looked at, long ago.
+    fn backdate(db: &Database) {
+        let mut row = flow(db);
+        let past = (Utc::now() - chrono::Duration::days(2)).to_rfc3339();
+        row.entered_at = past.clone();
+        row.wake_at = Some(past);
+        assert!(db.update_deletion_workflow(&row).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_workflow_runs_to_the_end() {
+        let db = setup();
+        let broker = Arc::new(FlowBroker {
+            runs: AtomicUsize::new(0),
+            fail: false,
+        });
+        let mut connectors: HashMap<String, Arc<dyn BrokerConnector>> = HashMap::new();
+        connectors.insert("acme".into(), broker.clone());
+
+        let row = start(&db, broker.as_ref(), "R1").await.unwrap().unwrap();
+        assert_eq!(row.step, "confirm");
+        assert_eq!(status(&db), "awaiting_user");
+        let tasks = db.list_manual_tasks_for_ref("acme", "R1").unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].step_key, "workflow:confirm");
+        assert!(owns_task(&tasks[0].step_key));
+
+        // Nothing to do until the user acts, however often this runs.
+        assert_eq!(advance_all(&db, &connectors).await.unwrap(), 0);
+        start(&db, broker.as_ref(), "R1").await.unwrap();
+        assert_eq!(db.list_manual_tasks_for_ref("acme", "R1").unwrap().len(), 1);
+
+        let mut task = tasks[0].clone();
+        task.status = "done".into();
+        task.response = Some("clicked".into());
+        db.update_manual_task(&task).unwrap();
+        assert!(advance_ref(&db, &connectors, "acme", "R1").await.unwrap());
+        let row = flow(&db);
+        assert_eq!(row.step, "reply");
+        assert_eq!(status(&db), "submitted");
+        let data: Map<String, Value> = serde_json::from_str(&row.data_json).unwrap();
+        assert_eq!(data["ticket"], "T-1");
+        assert_eq!(data["confirm"], "clicked");
+
+        // A reply from before the step does not count; a new one does.
+        let reply = |id: &str, created_at: String| EmailEvidence {
+            id: id.into(),
+            message_id: format!("{id}@acme.example"),
+            broker_id: "acme".into(),
+            external_ref: Some("R1".into()),
+            sender: None,
+            subject: None,
+            classification: "acknowledged".into(),
+            followed_link: None,
+            raw_message: String::new(),
+            received_at: None,
+            created_at,
+        };
+        let old = (Utc::now() - chrono::Duration::days(1)).to_rfc3339();
+        db.insert_email_evidence(&reply("e1", old)).unwrap();
+        assert_eq!(advance_all(&db, &connectors).await.unwrap(), 0);
+        db.insert_email_evidence(&reply("e2", Utc::now().to_rfc3339()))
+            .unwrap();
+        assert_eq!(advance_all(&db, &connectors).await.unwrap(), 1);
+        let row = flow(&db);
+        assert_eq!(row.step, "processing");
+        assert!(row.wake_at.is_some());
+        assert_eq!(advance_all(&db, &connectors).await.unwrap(), 0);
+
+        // The timer is stored, so a later run picks up where this one left.
+        backdate(&db);
+        assert_eq!(advance_all(&db, &connectors).await.unwrap(), 1);
+        assert_eq!(flow(&db).state, "finished");
+        assert_eq!(status(&db), "submitted");
+        assert_eq!(broker.runs.load(Ordering::SeqCst), 1);
+        assert!(
+            db.list_deletion_workflows(Some("active"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failing_action_retries_then_fails() {
+        let db = setup();
+        let broker = Arc::new(FlowBroker {
+            runs: AtomicUsize::new(0),
+            fail: true,
+        });
+        let mut connectors: HashMap<String, Arc<dyn BrokerConnector>> = HashMap::new();
+        connectors.insert("acme".into(), broker.clone());
+
+        let row = start(&db, broker.as_ref(), "R1").await.unwrap().unwrap();
+        assert_eq!(row.step, "submit");
+        assert_eq!(row.attempts, 1);
+        assert_eq!(row.last_error.as_deref(), Some("form not found"));
+        // Backing off: not retried straight away.
+        advance_all(&db, &connectors).await.unwrap();
+        assert_eq!(broker.runs.load(Ordering::SeqCst), 1);
+
+        for _ in 1..MAX_ATTEMPTS {
+            backdate(&db);
+            advance_all(&db, &connectors).await.unwrap();
+        }
+        assert_eq!(broker.runs.load(Ordering::SeqCst), MAX_ATTEMPTS as usize);
+        assert_eq!(flow(&db).state, "failed");
+        assert_eq!(status(&db), "failed");
+    }
+
+    #[tokio::test]
+    async fn test_stale_runs_do_not_write() {
+        let db = setup();
+        let broker = FlowBroker {
+            runs: AtomicUsize::new(0),
+            fail: false,
+        };
+        start(&db, &broker, "R1").await.unwrap();
+
+        let stale = flow(&db);
+        let mut other = stale.clone();
+        other.step = "reply".into();
+        assert!(db.update_deletion_workflow(&other).unwrap());
+        assert!(!db.update_deletion_workflow(&stale).unwrap());
+        assert_eq!(flow(&db).step, "reply");
+
+        // A request settled by a broker reply ends the flow as it is.
+        db.update_deletion_requests_by_ref("acme", "R1", "completed", "R1", None, "now")
+            .unwrap();
+        let mut connectors: HashMap<String, Arc<dyn BrokerConnector>> = HashMap::new();
+        connectors.insert("acme".into(), Arc::new(broker));
+        assert_eq!(advance_all(&db, &connectors).await.unwrap(), 1);
+        assert_eq!(flow(&db).state, "finished");
+        assert_eq!(status(&db), "completed");
+    }
+
+    #[test]
+    fn test_validate_workflow() {
+        let parse = |steps: Value| -> Workflow {
+            serde_json::from_value(json!({ "steps": steps })).unwrap()
+        };
+        assert!(parse(json!([])).validate().is_err());
+        assert!(
+            parse(json!([{ "name": "a", "type": "action", "next": "b" }]))
+                .validate()
+                .is_err()
+        );
+        assert!(
+            parse(json!([{ "name": "a", "type": "action" }, { "name": "a", "type": "action" }]))
+                .validate()
+                .is_err()
+        );
+        assert!(
+            parse(json!([{ "name": "wait", "type": "email", "reply": "thanks" }]))
+                .validate()
+                .is_err()
+        );
+        let ok = parse(json!([
+            { "name": "wait", "type": "email", "reply": "completed",
+              "timeout_secs": 60, "on_timeout": "resend" },
+            { "name": "resend", "type": "action", "next": "wait" },
+        ]));
+        assert!(ok.validate().is_ok());
+        assert_eq!(ok.next_of(&ok.steps[0]), "resend");
+        assert_eq!(ok.next_of(&ok.steps[1]), "wait");
+    }
+}
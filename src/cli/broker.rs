@@ -1,5 +1,9 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use comfy_table::{Cell, Table};
 
+use crate::broker::{BrokerConnector, ConnectorCapabilities, QueryField};
 use crate::db::Database;
 
 pub fn list_brokers(db: &Database, category: Option<&str>) -> anyhow::Result<()> {
@@ -28,8 +32,13 @@ pub fn list_brokers(db: &Database, category: Option<&str>) -> anyhow::Result<()>
     Ok(())
 }
 
-pub fn broker_info(db: &Database, id: &str) -> anyhow::Result<()> {
+pub fn broker_info(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    id: &str,
+) -> anyhow::Result<()> {
     let broker = db.get_broker(id)?;
+    let connector = connectors.get(id);
     match broker {
         Some(b) => {
             println!("ID:          {}", b.id);
@@ -46,11 +55,120 @@ pub fn broker_info(db: &Database, id: &str) -> anyhow::Result<()> {
             if let Some(conn) = &b.connector {
                 println!("Connector:   {conn}");
             }
+            if let Some(email) = &b.privacy_email {
+                println!("Privacy:     {email}");
+            }
+            if let Some(addr) = &b.postal_address {
+                println!("Postal:      {}", addr.replace('\n', ", "));
+            }
+            if let Some(url) = &b.opt_out_url {
+                println!("Opt-out:     {url}");
+            }
+            if !b.request_methods.is_empty() {
+                println!("Methods:     {}", b.request_methods.join(", "));
+            }
+            if !b.verification.is_empty() {
+                println!("Verifies:    {}", b.verification.join(", "));
+            }
+            if let Some(days) = b.processing_days {
+                println!("Processing:  ~{days} day(s)");
+            }
+            if let Some(days) = b.relisting_days {
+                println!("Relisting:   re-check every {days} day(s)");
+            }
+            if !b.jurisdictions.is_empty() {
+                println!("Covered by:  {}", b.jurisdictions.join(", "));
+            }
+            if let Some(parent) = &b.parent_id {
+                let name = db.get_broker(parent)?.map(|p| p.name);
+                let shared = if b.shared_database {
+                    ", shared database: removed through it"
+                } else {
+                    ""
+                };
+                match name {
+                    Some(name) => println!("Parent:      {name} ({parent}{shared})"),
+                    None => println!("Parent:      {parent}{shared}"),
+                }
+            }
+            let subsidiaries: Vec<_> = db
+                .list_brokers(None)?
+                .into_iter()
+                .filter(|s| s.parent_id.as_deref() == Some(&b.id))
+                .map(|s| s.id)
+                .collect();
+            if !subsidiaries.is_empty() {
+                println!("Brands:      {}", subsidiaries.join(", "));
+            }
             println!("Updated:     {}", b.updated_at);
+            if let Some(retired) = &b.retired_at {
+                println!("Retired:     {retired} (no longer in the registry)");
+            }
+            if let Some(c) = connector {
+                print_capabilities(&c.capabilities());
+            }
+
+            let evidence = db.list_email_evidence(&b.id)?;
+            if !evidence.is_empty() {
+                println!("\nBroker emails:");
+                for e in &evidence {
+                    println!(
+                        "  {}  {:<22} {}",
+                        e.received_at.as_deref().unwrap_or(&e.created_at),
+                        e.classification,
+                        e.subject.as_deref().unwrap_or("(no subject)")
+                    );
+                }
+            }
         }
-        None => {
-            anyhow::bail!("Broker '{}' not found", id);
-        }
+        None => match connector {
+            // Compiled-in connectors are only stored once scanned.
+            Some(c) => {
+                println!("ID:          {id}");
+                println!("Name:        {}", c.name());
+                print_capabilities(&c.capabilities());
+            }
+            None => anyhow::bail!("Broker '{}' not found", id),
+        },
+    }
+    Ok(())
+}
+
+pub fn clear_session(db: &Database, id: &str) -> anyhow::Result<()> {
+    if db.delete_broker_session(id)? {
+        println!("Cleared the session of '{id}'.");
+    } else {
+        println!("No session stored for '{id}'.");
     }
     Ok(())
 }
+
+fn print_capabilities(caps: &ConnectorCapabilities) {
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    let fields = |fields: &[QueryField]| match fields {
+        [] => "-".to_string(),
+        _ => fields
+            .iter()
+            .map(|f| f.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+
+    println!("\nConnector capabilities:");
+    println!("  Scan:          {}", yes_no(caps.can_scan));
+    println!("  Delete:        {}", yes_no(caps.can_delete));
+    println!("  Status check:  {}", yes_no(caps.can_check_status));
+    println!("  Requires:      {}", fields(&caps.required_fields));
+    println!("  Optional:      {}", fields(&caps.optional_fields));
+    if !caps.data_types.is_empty() {
+        println!("  Data types:    {}", caps.data_types.join(", "));
+    }
+    if let Some(method) = caps.deletion_method {
+        println!("  Method:        {}", method.as_str());
+    }
+    println!("  Verification:  {}", yes_no(caps.needs_verification));
+    println!("  Batch:         {}", yes_no(caps.batch));
+    if let Some(days) = caps.processing_days {
+        println!("  Processing:    ~{days} day(s)");
+    }
+}
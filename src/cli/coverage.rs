@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use comfy_table::{Cell, Table};
+
+use crate::broker::BrokerConnector;
+use crate::broker::coverage::{self, Coverage, Level};
+use crate::db::Database;
+
+pub fn coverage(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    list: bool,
+) -> anyhow::Result<()> {
+    let brokers = coverage::known_brokers(db, connectors)?;
+    if brokers.is_empty() {
+        println!(
+            "No brokers found. Run `data-breaker registry update` to fetch the broker registry."
+        );
+        return Ok(());
+    }
+    let assessed: Vec<Coverage> = brokers
+        .iter()
+        .map(|b| coverage::assess(b, connectors.get(&b.id)))
+        .collect();
+
+    if list {
+        let mut table = Table::new();
+        table.set_header(vec!["ID", "Name", "Scan", "Deletion", "Status"]);
+        for (broker, c) in brokers.iter().zip(&assessed) {
+            table.add_row(vec![
+                Cell::new(&broker.id),
+                Cell::new(&broker.name),
+                Cell::new(c.scan.as_str()),
+                Cell::new(c.deletion.as_str()),
+                Cell::new(c.status.as_str()),
+            ]);
+        }
+        println!("{table}");
+    }
+
+    let total = brokers.len();
+    let mut table = Table::new();
+    table.set_header(vec!["Operation", "Automated", "Manual", "Not covered"]);
+    for (operation, level) in [
+        ("Scan", (|c: &Coverage| c.scan) as fn(&Coverage) -> Level),
+        ("Deletion", |c| c.deletion),
+        ("Status", |c| c.status),
+    ] {
+        let count = |wanted: Level| assessed.iter().filter(|c| level(c) == wanted).count();
+        let automated = count(Level::Automated);
+        table.add_row(vec![
+            Cell::new(operation),
+            Cell::new(format!("{automated} ({}%)", automated * 100 / total)),
+            Cell::new(count(Level::Manual)),
+            Cell::new(count(Level::None)),
+        ]);
+    }
+    println!("Known brokers: {total}");
+    println!("{table}");
+    Ok(())
+}
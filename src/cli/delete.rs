@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::broker::{BrokerConnector, PersonQuery};
+use crate::broker::{BrokerConnector, PersonQuery, family, workflow};
 use crate::db::Database;
 use crate::db::models::DeletionRequest;
 
@@ -12,6 +12,7 @@ pub async fn delete(
     all: bool,
     broker_id: Option<&str>,
     record_id: Option<&str>,
+    handoff: bool,
 ) -> anyhow::Result<()> {
     let records = if let Some(rid) = record_id {
         let r = db
@@ -20,7 +21,12 @@ pub async fn delete(
         vec![r]
     } else if let Some(bid) = broker_id {
         let records = db.list_personal_records(Some(bid))?;
-        if records.is_empty() {
+        // Brokers that cannot be scanned (email- or mail-only) take a
+        // request without specific records.
+        let unscannable = connectors
+            .get(bid)
+            .is_some_and(|c| !c.capabilities().can_scan);
+        if records.is_empty() && !unscannable {
             anyhow::bail!("No records found for broker '{}'", bid);
         }
         records
@@ -42,26 +48,66 @@ pub async fn delete(
             .or_default()
             .push(r.clone());
     }
+    if let Some(bid) = broker_id {
+        by_broker.entry(bid.to_string()).or_default();
+    }
+
+    // Brands on a shared database are removed through the broker that
+    // controls it: one submission there, linked requests for the brands.
+    let mut brands: HashMap<String, Vec<(String, Vec<String>)>> = HashMap::new();
+    for bid in by_broker.keys().cloned().collect::<Vec<_>>() {
+        let controller = family::controller(db, &bid)?;
+        let can_delete = connectors
+            .get(&controller)
+            .is_some_and(|c| c.capabilities().can_delete);
+        if controller == bid || !can_delete {
+            continue;
+        }
+        let record_ids = by_broker
+            .remove(&bid)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        brands
+            .entry(controller.clone())
+            .or_default()
+            .push((bid, record_ids));
+        by_broker.entry(controller).or_default();
+    }
 
     let mut submitted = 0usize;
+    let mut awaiting = 0usize;
     let mut failed = 0usize;
 
     for (bid, broker_records) in &by_broker {
+        let family = brands.get(bid).map(Vec::as_slice).unwrap_or_default();
+        // Requests tracked for the brands, counted along with the broker's.
+        let linked: usize = family.iter().map(|(_, ids)| ids.len().max(1)).sum();
         let connector = match connectors.get(bid.as_str()) {
             Some(c) => c,
             None => {
                 println!("No connector for broker '{}', skipping.", bid);
-                failed += broker_records.len();
+                failed += broker_records.len().max(1);
                 continue;
             }
         };
 
-        if !connector.capabilities().can_delete {
+        let caps = connector.capabilities();
+        if !caps.can_delete {
             println!(
                 "Connector '{}' does not support deletion, skipping.",
                 connector.name()
             );
-            failed += broker_records.len();
+            failed += broker_records.len().max(1) + linked;
+            continue;
+        }
+        // Without records the request is built from the query alone.
+        if broker_records.is_empty()
+            && let Err(e) = caps.validate(connector.name(), query)
+        {
+            println!("Skipping {}: {e}", connector.name());
+            failed += 1 + linked;
             continue;
         }
 
@@ -75,36 +121,126 @@ pub async fn delete(
             })
             .collect();
 
-        println!("Requesting deletion from {}...", connector.name());
+        if family.is_empty() {
+            println!("Requesting deletion from {}...", connector.name());
+        } else {
+            let covered: Vec<_> = family.iter().map(|(brand, _)| brand.as_str()).collect();
+            println!(
+                "Requesting deletion from {} (also covers {})...",
+                connector.name(),
+                covered.join(", ")
+            );
+        }
         match connector.request_deletion(query, &found_records).await {
             Ok(submission) => {
                 let now = chrono::Utc::now().to_rfc3339();
-                for r in broker_records {
+                let needs_user = !submission.manual_steps.is_empty();
+                // One request per record, or a single one when there are none.
+                let record_ids: Vec<Option<String>> = if broker_records.is_empty() {
+                    vec![None]
+                } else {
+                    broker_records.iter().map(|r| Some(r.id.clone())).collect()
+                };
+                let mut covering = None;
+                for record_id in record_ids {
                     let deletion = DeletionRequest {
                         id: uuid::Uuid::new_v4().to_string(),
                         broker_id: bid.clone(),
-                        personal_record_id: Some(r.id.clone()),
-                        status: "submitted".to_string(),
-                        submitted_at: Some(now.clone()),
+                        personal_record_id: record_id,
+                        status: if needs_user {
+                            "awaiting_user".to_string()
+                        } else {
+                            "submitted".to_string()
+                        },
+                        submitted_at: (!needs_user).then(|| now.clone()),
                         completed_at: None,
                         error_message: None,
                         external_ref: Some(submission.external_ref.clone()),
                         created_at: now.clone(),
                         updated_at: now.clone(),
+                        covered_by: None,
                     };
                     db.insert_deletion_request(&deletion)?;
+                    covering.get_or_insert(deletion);
+                }
+                if let Some(covering) = &covering {
+                    for (brand, record_ids) in family {
+                        family::link(db, covering, brand, record_ids)?;
+                        println!("  Linked {brand}: removed through {bid}");
+                    }
+                }
+                super::tasks::record_manual_steps(
+                    db,
+                    bid,
+                    &submission.external_ref,
+                    &submission.manual_steps,
+                )?;
+                match workflow::start(db, connector.as_ref(), &submission.external_ref).await {
+                    Ok(Some(flow)) => println!(
+                        "  Workflow {}",
+                        workflow::describe(db, &flow, connector.as_ref())
+                    ),
+                    Ok(None) => {}
+                    Err(e) => println!("  Workflow error: {e}"),
+                }
+                // The connector's steps plus any task a workflow opened.
+                let open_tasks = db.count_open_manual_tasks(bid, &submission.external_ref)?;
+                if open_tasks > 0 {
+                    awaiting += broker_records.len().max(1) + linked;
+                    if handoff {
+                        let handed_off = super::tasks::handoff_submission(
+                            db,
+                            connectors,
+                            bid,
+                            &submission.external_ref,
+                            true,
+                            std::time::Duration::from_secs(900),
+                        )
+                        .await;
+                        // The tasks stay open; the other brokers still get their turn.
+                        if let Err(e) = handed_off {
+                            tracing::error!("Handoff for {} failed: {}", bid, e);
+                            println!("  Handoff error: {e}");
+                            record_error(db, bid, &submission.external_ref, &e)?;
+                        }
+                    } else {
+                        println!(
+                            "  {open_tasks} manual step(s) required — run `data-breaker tasks list`"
+                        );
+                    }
+                } else {
+                    submitted += broker_records.len().max(1) + linked;
+                    println!("  Submitted (ref: {})", submission.external_ref);
                 }
-                submitted += broker_records.len();
-                println!("  Submitted (ref: {})", submission.external_ref);
             }
             Err(e) => {
                 tracing::error!("Error deleting from {}: {}", bid, e);
                 println!("  Error: {e}");
-                failed += broker_records.len();
+                failed += broker_records.len().max(1) + linked;
             }
         }
     }
 
-    println!("\nDeletion requests: {submitted} submitted, {failed} failed");
+    println!(
+        "\nDeletion requests: {submitted} submitted, {awaiting} awaiting manual steps, {failed} failed"
+    );
+    Ok(())
+}
+
+/// Note a failure on the requests of a submission still waiting on the user.
+fn record_error(
+    db: &Database,
+    broker_id: &str,
+    external_ref: &str,
+    error: &anyhow::Error,
+) -> anyhow::Result<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    for mut req in db.list_deletion_requests(Some(broker_id))? {
+        if req.external_ref.as_deref() == Some(external_ref) && req.status == "awaiting_user" {
+            req.error_message = Some(format!("Handoff failed: {error}"));
+            req.updated_at = now.clone();
+            db.update_deletion_request(&req)?;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::broker::http::HttpService;
+use crate::broker::{BrokerConnector, workflow};
+use crate::config::{Config, MailboxConfig, MailboxKind};
+use crate::db::Database;
+
+pub async fn check_mailbox(
+    db: &Database,
+    config: &Config,
+    http: &HttpService,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    maildir: Option<&Path>,
+    mbox: Option<&Path>,
+    watch: Option<u64>,
+) -> anyhow::Result<()> {
+    let mailbox = match (maildir, mbox) {
+        (Some(path), _) => local_mailbox(config, MailboxKind::Maildir, path),
+        (None, Some(path)) => local_mailbox(config, MailboxKind::Mbox, path),
+        (None, None) => config.mailbox.clone().ok_or_else(|| {
+            anyhow::anyhow!("No mailbox configured. Add a [mailbox] section to config.toml or pass --maildir/--mbox.")
+        })?,
+    };
+
+    loop {
+        match check_once(db, http, connectors, &mailbox).await {
+            Ok(()) => {}
+            // A watch outlives flaky connections and files being rewritten.
+            Err(e) if watch.is_some() => {
+                tracing::error!("Mailbox check failed: {e:#}");
+                println!("Mailbox check failed: {e}. Retrying on the next poll.");
+            }
+            Err(e) => return Err(e),
+        }
+
+        match watch {
+            Some(secs) => tokio::time::sleep(Duration::from_secs(secs)).await,
+            None => return Ok(()),
+        }
+    }
+}
+
+async fn check_once(
+    db: &Database,
+    http: &HttpService,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    mailbox: &MailboxConfig,
+) -> anyhow::Result<()> {
+    let messages = crate::mailbox::fetch_messages(mailbox).await?;
+    let summary =
+        crate::mailbox::process_messages(db, http, &messages, &mailbox.allowed_link_domains)
+            .await?;
+    println!(
+        "Checked {} message(s): {} broker repl(ies), {} request(s) updated, {} confirmation link(s) followed.",
+        summary.scanned, summary.matched, summary.updated, summary.links_followed
+    );
+    // Replies and elapsed timers move multi-step flows along
+    let moved = workflow::advance_all(db, connectors).await?;
+    if moved > 0 {
+        println!("Advanced {moved} deletion workflow(s).");
+    }
+    Ok(())
+}
+
+/// A file-based mailbox from the command line, keeping the configured
+/// link allowlist.
+fn local_mailbox(config: &Config, kind: MailboxKind, path: &Path) -> MailboxConfig {
+    MailboxConfig {
+        kind,
+        path: Some(path.to_path_buf()),
+        host: None,
+        port: None,
+        tls: true,
+        username: None,
+        password: None,
+        folder: "INBOX".to_string(),
+        lookback_days: 0,
+        allowed_link_domains: config
+            .mailbox
+            .as_ref()
+            .map(|m| m.allowed_link_domains.clone())
+            .unwrap_or_default(),
+    }
+}
@@ -1,9 +1,15 @@
 pub mod broker;
+pub mod coverage;
 pub mod delete;
+pub mod mailbox;
+pub mod postal;
 pub mod registry;
 pub mod report;
 pub mod scan;
 pub mod status;
+pub mod tasks;
+
+use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 
@@ -69,16 +75,58 @@ pub enum Command {
         /// Delete a specific record by ID
         #[arg(long)]
         record: Option<String>,
+        /// Walk through manual steps in the browser right away
+        #[arg(long)]
+        handoff: bool,
+        /// First name, for brokers that take requests without scan records
+        #[arg(long)]
+        first_name: Option<String>,
+        /// Last name, for brokers that take requests without scan records
+        #[arg(long)]
+        last_name: Option<String>,
+        /// Email address to include in requests
+        #[arg(long)]
+        email: Option<String>,
+        /// Phone number to include in requests
+        #[arg(long)]
+        phone: Option<String>,
+        /// City to include in requests
+        #[arg(long)]
+        city: Option<String>,
+        /// State to include in requests
+        #[arg(long)]
+        state: Option<String>,
     },
     /// Check the status of deletion requests
     Status {
         /// Filter by broker ID
         #[arg(long)]
         broker: Option<String>,
-        /// Filter by status (pending, submitted, in_progress, completed, failed, rejected)
+        /// Filter by status (pending, awaiting_user, submitted, in_progress, completed, not_found, failed, rejected)
         #[arg(long)]
         filter: Option<String>,
     },
+    /// Manage manual steps (CAPTCHAs, phone codes, email links)
+    Tasks {
+        #[command(subcommand)]
+        command: TasksCommand,
+    },
+    /// Read broker replies and update deletion requests
+    Mailbox {
+        #[command(subcommand)]
+        command: MailboxCommand,
+    },
+    /// Track deletion requests sent by postal mail
+    Postal {
+        #[command(subcommand)]
+        command: PostalCommand,
+    },
+    /// Show how many known brokers data-breaker automates
+    Coverage {
+        /// List every broker and how each operation is covered
+        #[arg(long)]
+        list: bool,
+    },
     /// Generate a report of findings and deletion status
     Report {
         /// Output format
@@ -92,10 +140,41 @@ pub enum Command {
 
 #[derive(Subcommand)]
 pub enum RegistryCommand {
-    /// Fetch the latest broker registry from GitHub
-    Update,
+    /// Fetch and merge the configured broker registry sources
+    Update {
+        /// Import a registry JSON copied from another machine instead of
+        /// downloading the public registry
+        #[arg(long)]
+        from_file: Option<PathBuf>,
+        /// Import the file even without a valid `.minisig` from a trusted
+        /// key, as a separate source on top of the public registry until
+        /// the next update
+        #[arg(long, requires = "from_file")]
+        trust_unsigned: bool,
+    },
     /// Show registry metadata
     Info,
+    /// Show registry sources and where each broker came from
+    Sources,
+    /// Check a registry file against the schema before submitting it
+    Validate {
+        /// Registry JSON file
+        file: PathBuf,
+    },
+    /// Import a state's official data broker registry, exported as CSV
+    ImportState {
+        /// State publishing the registry
+        #[arg(value_parser = ["CA", "VT", "TX", "OR"], ignore_case = true)]
+        state: String,
+        /// CSV export of the state's registry
+        file: PathBuf,
+        /// Report what the import would change without saving it
+        #[arg(long)]
+        dry_run: bool,
+        /// Write the brokers missing from the registry to a registry JSON file
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -111,4 +190,93 @@ pub enum BrokerCommand {
         /// Broker ID (slug)
         id: String,
     },
+    /// Manage the cookies and state a connector keeps between runs
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SessionCommand {
+    /// Forget a connector's cookies and state, to restart its flows
+    Clear {
+        /// Broker ID (slug)
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MailboxCommand {
+    /// Match replies to deletion requests, follow confirmation links and update statuses
+    Check {
+        /// Read a Maildir directory instead of the configured mailbox
+        #[arg(long, conflicts_with = "mbox")]
+        maildir: Option<PathBuf>,
+        /// Read an mbox file instead of the configured mailbox
+        #[arg(long)]
+        mbox: Option<PathBuf>,
+        /// Keep checking every N seconds, advancing deletion workflows too
+        #[arg(long, value_name = "SECS")]
+        watch: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PostalCommand {
+    /// List letters waiting to be mailed and the mail log
+    List,
+    /// Record that a letter was mailed
+    Log {
+        /// Letter task ID (or unique prefix), from `postal list`
+        task: String,
+        /// Date the letter was sent (YYYY-MM-DD, default today)
+        #[arg(long)]
+        sent_on: Option<String>,
+        /// Postal tracking number
+        #[arg(long)]
+        tracking: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TasksCommand {
+    /// List manual tasks (open ones by default)
+    List {
+        /// Include done and skipped tasks
+        #[arg(long)]
+        all: bool,
+        /// Filter by broker ID
+        #[arg(long)]
+        broker: Option<String>,
+    },
+    /// Show instructions and values to enter for a task
+    Show {
+        /// Task ID (or unique prefix)
+        id: String,
+    },
+    /// Mark a task as done and resume the deletion flow
+    Done {
+        /// Task ID (or unique prefix)
+        id: String,
+        /// Value requested by the task (e.g. a verification code)
+        #[arg(long)]
+        value: Option<String>,
+    },
+    /// Skip a task that does not apply
+    Skip {
+        /// Task ID (or unique prefix)
+        id: String,
+    },
+    /// Open the task's opt-out page in the browser and capture the confirmation number
+    Handoff {
+        /// Task ID (or unique prefix)
+        id: String,
+        /// Only print instructions; don't launch a browser
+        #[arg(long)]
+        no_browser: bool,
+        /// Seconds to wait for the confirmation before giving up
+        #[arg(long, default_value_t = 900)]
+        timeout: u64,
+    },
 }
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use comfy_table::{Cell, Table};
+
+use crate::broker::BrokerConnector;
+use crate::broker::postal::MAIL_STEP;
+use crate::db::Database;
+use crate::db::models::PostalMailing;
+
+pub fn list_letters(db: &Database) -> anyhow::Result<()> {
+    let mut pending = db.list_manual_tasks(Some("open"))?;
+    pending.retain(|t| t.step_key == MAIL_STEP);
+    let mailings = db.list_postal_mailings()?;
+
+    if pending.is_empty() && mailings.is_empty() {
+        println!("No postal requests found.");
+        return Ok(());
+    }
+
+    if !pending.is_empty() {
+        println!("Letters to mail:");
+        let mut table = Table::new();
+        table.set_header(vec!["Task", "Broker", "Letter"]);
+        for t in &pending {
+            table.add_row(vec![
+                Cell::new(&t.id[..8]),
+                Cell::new(&t.broker_id),
+                Cell::new(t.url.as_deref().map(letter_path).unwrap_or_default()),
+            ]);
+        }
+        println!("{table}");
+        println!("After mailing, run `data-breaker postal log <task> --tracking <number>`.\n");
+    }
+
+    if !mailings.is_empty() {
+        println!("Mail log:");
+        let mut table = Table::new();
+        table.set_header(vec!["Broker", "Sent On", "Tracking", "Reference"]);
+        for m in &mailings {
+            table.add_row(vec![
+                Cell::new(&m.broker_id),
+                Cell::new(&m.sent_on),
+                Cell::new(m.tracking_number.as_deref().unwrap_or("-")),
+                Cell::new(&m.external_ref),
+            ]);
+        }
+        println!("{table}");
+    }
+    Ok(())
+}
+
+/// Record that a letter was mailed and mark its request submitted.
+pub async fn log_mailing(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    task_id: &str,
+    sent_on: Option<&str>,
+    tracking: Option<&str>,
+) -> anyhow::Result<()> {
+    let task = super::tasks::find_open_task(db, task_id)?;
+    if task.step_key != MAIL_STEP {
+        anyhow::bail!("Task '{}' is not a postal letter", task_id);
+    }
+
+    let sent_on = match sent_on {
+        Some(d) => chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d")
+            .map_err(|_| anyhow::anyhow!("Invalid date '{}', expected YYYY-MM-DD", d))?,
+        None => chrono::Local::now().date_naive(),
+    };
+
+    // Completing the task first keeps a failed resume from logging the same
+    // letter twice when the command is run again.
+    super::tasks::complete_task(db, connectors, &task.id, tracking).await?;
+    db.insert_postal_mailing(&PostalMailing {
+        id: uuid::Uuid::new_v4().to_string(),
+        broker_id: task.broker_id.clone(),
+        external_ref: task.external_ref.clone(),
+        letter_path: task.url.as_deref().map(letter_path).unwrap_or_default(),
+        sent_on: sent_on.to_string(),
+        tracking_number: tracking.map(str::to_string),
+        created_at: chrono::Utc::now().to_rfc3339(),
+    })?;
+    println!("Logged letter to {} sent on {sent_on}.", task.broker_id);
+    Ok(())
+}
+
+/// Letter tasks carry a `file://` URL; show it as a plain path.
+fn letter_path(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.to_file_path().ok())
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| url.to_string())
+}
@@ -1,29 +1,497 @@
+use std::path::Path;
+
+use comfy_table::{Cell, Table};
+
+use crate::broker::http::HttpService;
+use crate::broker::registry;
+use crate::broker::state_registry::{self, State};
+use crate::config::RegistryConfig;
 use crate::db::Database;
+use crate::db::models::Broker;
+use crate::error::AppError;
 
-pub async fn update_registry(db: &Database) -> anyhow::Result<()> {
-    println!("Fetching broker registry...");
-    let brokers = crate::broker::registry::fetch_registry().await?;
-    let count = brokers.len();
+/// What applying a set of layers did.
+struct Applied {
+    brokers: usize,
+    diff: registry::Diff,
+    rejected: Vec<registry::Rejected>,
+}
 
-    for broker in brokers {
-        db.upsert_broker(&broker)?;
-    }
+/// Merge `layers` into the brokers table, retiring brokers that are gone,
+/// and remember per-source versions, signatures and caches.
+fn apply(db: &Database, layers: &[registry::Layer]) -> anyhow::Result<Applied> {
+    let merged = registry::merge(layers)?;
+    let brokers = merged.brokers.len();
+    let mut diff = registry::diff(&db.list_registry_brokers()?, merged.brokers);
+    let rejected: Vec<_> = layers
+        .iter()
+        .flat_map(|l| l.rejected.iter().cloned())
+        .chain(merged.rejected)
+        .collect();
+    // A broker whose entry is broken for now has not left the registry.
+    diff.removed
+        .retain(|b| !rejected.iter().any(|r| r.id.as_deref() == Some(&b.id)));
 
     let now = chrono::Utc::now().to_rfc3339();
-    db.set_registry_meta("last_fetched_at", &now)?;
+    for broker in diff
+        .added
+        .iter()
+        .chain(diff.changed.iter().map(|(b, _)| b))
+        .chain(&diff.restored)
+    {
+        db.upsert_broker(broker)?;
+    }
+    for broker in &diff.removed {
+        db.retire_broker(&broker.id, &now)?;
+    }
+    if !diff.is_empty() {
+        db.set_registry_meta("last_changed_at", &now)?;
+    }
 
-    println!("Registry updated: {count} broker(s) synced.");
+    for layer in layers {
+        let set = |key: &str, value: &str| {
+            db.set_registry_meta(&format!("{key}:{}", layer.source), value)
+        };
+        set("version", &layer.version)?;
+        let key = layer.verified.as_ref().map(|v| v.key.as_str());
+        set("signing_key", key.unwrap_or_default())?;
+        if let Some(signed_at) = layer.verified.as_ref().and_then(|v| v.signed_at) {
+            set("signed_at", &signed_at.to_string())?;
+        }
+        if let Some(cached) = &layer.cached {
+            set("cache", &cached.body)?;
+            set("cache_sig", &cached.signature)?;
+            set("etag", cached.etag.as_deref().unwrap_or_default())?;
+            set(
+                "last_modified",
+                cached.last_modified.as_deref().unwrap_or_default(),
+            )?;
+        }
+    }
+    Ok(Applied {
+        brokers,
+        diff,
+        rejected,
+    })
+}
+
+/// Load the bundled snapshot until the registry has been updated once, so a
+/// fresh install knows the common brokers without network access.
+pub fn seed_bundled(db: &Database) -> anyhow::Result<()> {
+    if db.get_registry_meta("last_fetched_at")?.is_some() {
+        return Ok(());
+    }
+    let layer = registry::bundled()?;
+    let key = format!("version:{}", registry::BUNDLED_SOURCE);
+    if db.get_registry_meta(&key)?.as_deref() == Some(&layer.version) {
+        return Ok(());
+    }
+    let applied = apply(db, &[layer])?;
+    tracing::info!(
+        "Loaded the bundled registry snapshot ({} broker(s))",
+        applied.brokers
+    );
     Ok(())
 }
 
-pub fn registry_info(db: &Database) -> anyhow::Result<()> {
+pub async fn update_registry(
+    db: &Database,
+    http: &HttpService,
+    config: &RegistryConfig,
+    from_file: Option<&Path>,
+    trust_unsigned: bool,
+) -> anyhow::Result<()> {
+    let sources = registry::sources(config)?;
+    if sources.is_empty() {
+        anyhow::bail!("No registry sources configured and the public registry is disabled");
+    }
+    if from_file.is_some() && !config.public {
+        anyhow::bail!(
+            "The public registry is disabled; add the file as a `[[registry.sources]]` path instead"
+        );
+    }
+    let trust = registry::trust(config);
+
+    let mut layers = Vec::new();
+    let mut fetched = true;
+    for source in &sources {
+        let meta = |key: &str| {
+            db.get_registry_meta(&format!("{key}:{}", source.name))
+                .map(|v| v.filter(|v| !v.is_empty()))
+        };
+        let not_before = meta("signed_at")?.and_then(|t| t.parse().ok());
+        let public = source.name == registry::PUBLIC_SOURCE;
+        let cached = match meta("cache")? {
+            Some(body) => Some(registry::Cached {
+                etag: meta("etag")?,
+                last_modified: meta("last_modified")?,
+                body,
+                signature: meta("cache_sig")?.unwrap_or_default(),
+            }),
+            None => None,
+        }
+        .filter(|c| !trust.required || !c.signature.is_empty());
+        if let Some(path) = from_file.filter(|_| public) {
+            println!("Importing the public registry from {}...", path.display());
+            let layer = registry::load_file(path, &trust, not_before, trust_unsigned)?;
+            if layer.source == registry::IMPORTED_SOURCE {
+                // Keep what the public source last provided underneath.
+                println!(
+                    "  Not signed by a trusted key; applying it as source '{}' on top of the public registry.",
+                    registry::IMPORTED_SOURCE
+                );
+                layers.push(match cached {
+                    Some(cached) => registry::from_cache(&source.name, cached, &trust)?,
+                    None => registry::bundled()?,
+                });
+            }
+            layers.push(layer);
+            continue;
+        }
+
+        println!(
+            "Fetching registry source '{}' ({})...",
+            source.name, source.location
+        );
+        match registry::load(http, source, &trust, not_before, cached.as_ref()).await {
+            Ok(layer) => layers.push(layer),
+            // Never fetched and offline: fall back to the snapshot rather
+            // than retiring everything it provides.
+            Err(e)
+                if public
+                    && meta("version")?.is_none()
+                    && e.downcast_ref::<reqwest::Error>().is_some() =>
+            {
+                println!("  Public registry unreachable ({e}); using the bundled snapshot.");
+                layers.push(registry::bundled()?);
+                fetched = false;
+            }
+            Err(e) => anyhow::bail!("Registry source '{}': {e}", source.name),
+        }
+    }
+
+    let applied = apply(db, &layers)?;
+    if fetched {
+        db.set_registry_meta("last_fetched_at", &chrono::Utc::now().to_rfc3339())?;
+    }
+    for layer in &layers {
+        let status = if layer.not_modified {
+            " (not modified)"
+        } else {
+            ""
+        };
+        println!(
+            "  {}: {} definition(s), {} skipped{status}",
+            layer.source,
+            layer.entries.len(),
+            layer.rejected.len()
+        );
+    }
+    for rejected in &applied.rejected {
+        tracing::warn!("Skipped broker definition: {rejected}");
+        println!("  Skipped {rejected}");
+    }
+
+    print_diff(&applied.diff);
+    println!("Registry updated: {} broker(s) known.", applied.brokers);
+    let modules = crate::broker::wasm::fetch_modules(db, http).await?;
+    if modules > 0 {
+        println!("Downloaded {modules} WASM connector module(s).");
+    }
+    Ok(())
+}
+
+fn print_diff(diff: &registry::Diff) {
+    if diff.is_empty() {
+        println!("No changes.");
+        return;
+    }
+    let sections = [
+        ("Added", &diff.added),
+        ("Restored", &diff.restored),
+        ("Retired", &diff.removed),
+    ];
+    for (title, brokers) in sections {
+        if brokers.is_empty() {
+            continue;
+        }
+        println!("{title} ({}):", brokers.len());
+        for broker in brokers {
+            println!("  {:<20} {}", broker.id, broker.name);
+        }
+    }
+    if !diff.changed.is_empty() {
+        println!("Changed ({}):", diff.changed.len());
+        for (broker, changes) in &diff.changed {
+            println!("  {}", broker.id);
+            for change in changes {
+                match change.field {
+                    // Code and manifests are too long to show inline.
+                    "script" | "wasm" => println!("    {}: changed", change.field),
+                    field => println!(
+                        "    {field}: {} -> {}",
+                        change.old.as_deref().unwrap_or("-"),
+                        change.new.as_deref().unwrap_or("-")
+                    ),
+                }
+            }
+        }
+    }
+}
+
+pub fn registry_info(db: &Database, config: &RegistryConfig) -> anyhow::Result<()> {
     let last_fetched = db.get_registry_meta("last_fetched_at")?;
-    let broker_count = db.list_brokers(None)?.len();
+    let brokers = db.list_brokers(None)?;
 
     match last_fetched {
         Some(ts) => println!("Last updated:  {ts}"),
         None => println!("Last updated:  never (run `data-breaker registry update`)"),
     }
-    println!("Brokers known: {broker_count}");
+    if let Some(ts) = db.get_registry_meta("last_changed_at")? {
+        println!("Last changed:  {ts}");
+    }
+    for source in registry::sources(config)? {
+        let Some(version) = db.get_registry_meta(&format!("version:{}", source.name))? else {
+            continue;
+        };
+        println!("Source:        {}", source.name);
+        println!("  Version:     {}", &version[..12.min(version.len())]);
+        match db.get_registry_meta(&format!("signing_key:{}", source.name))? {
+            Some(key) if !key.is_empty() => println!("  Signed by:   {key}"),
+            _ if matches!(source.location, registry::Location::Url(_)) => {
+                println!("  Signed by:   (not verified)")
+            }
+            _ => println!("  Signed by:   (unsigned, local)"),
+        }
+        if let Some(signed_at) = signed_at(db, &source.name)? {
+            println!("  Signed at:   {signed_at}");
+        }
+    }
+    let bundled = brokers
+        .iter()
+        .filter(|b| b.registry_source.as_deref() == Some(registry::BUNDLED_SOURCE))
+        .count();
+    if bundled > 0 {
+        println!(
+            "Bundled:       {bundled} broker(s) from the snapshot shipped with data-breaker {} (run `data-breaker registry update` for the latest)",
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+    let imported = brokers
+        .iter()
+        .filter(|b| {
+            b.registry_source
+                .as_deref()
+                .is_some_and(|s| s.split(", ").any(|s| s == registry::IMPORTED_SOURCE))
+        })
+        .count();
+    if imported > 0 {
+        println!(
+            "Imported:      {imported} broker(s) from an unsigned file (until the next `data-breaker registry update`)"
+        );
+    }
+    for state in state_registry::STATES {
+        let Some(imported_at) = db.get_registry_meta(&format!("imported_at:{}", state.source()))?
+        else {
+            continue;
+        };
+        let listed = brokers.iter().filter(|b| state.lists(b)).count();
+        println!(
+            "State list:    {} (imported {imported_at}, {listed} broker(s) not in the registry)",
+            state.name
+        );
+    }
+    println!("Brokers known: {}", brokers.len());
+    let retired = db
+        .list_registry_brokers()?
+        .iter()
+        .filter(|b| b.retired_at.is_some())
+        .count();
+    if retired > 0 {
+        println!("Retired:       {retired}");
+    }
+    Ok(())
+}
+
+pub fn registry_sources(db: &Database, config: &RegistryConfig) -> anyhow::Result<()> {
+    let brokers = db.list_brokers(None)?;
+    let defined_by = |name: &str, broker: &crate::db::models::Broker| {
+        broker
+            .registry_source
+            .as_deref()
+            .is_some_and(|s| s.split(", ").any(|s| s == name))
+    };
+
+    let mut table = Table::new();
+    table.set_header(vec!["Name", "Location", "Priority", "Brokers", "Signed at"]);
+    for source in registry::sources(config)? {
+        let count = brokers
+            .iter()
+            .filter(|b| defined_by(&source.name, b))
+            .count();
+        let signed = match source.location {
+            registry::Location::Url(_) => {
+                signed_at(db, &source.name)?.unwrap_or_else(|| "-".to_string())
+            }
+            registry::Location::Path(_) => "local".to_string(),
+        };
+        table.add_row(vec![
+            Cell::new(&source.name),
+            Cell::new(source.location.to_string()),
+            Cell::new(source.priority),
+            Cell::new(count),
+            Cell::new(signed),
+        ]);
+    }
+    println!("{table}");
+
+    let mut table = Table::new();
+    table.set_header(vec!["Broker", "Defined by"]);
+    for broker in brokers.iter().filter(|b| b.registry_source.is_some()) {
+        table.add_row(vec![
+            Cell::new(&broker.id),
+            Cell::new(broker.registry_source.as_deref().unwrap_or_default()),
+        ]);
+    }
+    println!("{table}");
+    Ok(())
+}
+
+fn signed_at(db: &Database, source: &str) -> anyhow::Result<Option<String>> {
+    Ok(db
+        .get_registry_meta(&format!("signed_at:{source}"))?
+        .and_then(|t| t.parse().ok())
+        .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+        .map(|t| t.to_rfc3339()))
+}
+
+pub fn validate_registry(file: &Path) -> anyhow::Result<()> {
+    let entries = registry::validate_file(file)?;
+    for rejected in &entries.rejected {
+        println!("  {rejected}");
+    }
+    println!(
+        "{}: {} valid, {} invalid broker definition(s) (schema version {}).",
+        file.display(),
+        entries.valid.len(),
+        entries.rejected.len(),
+        registry::SCHEMA_VERSION
+    );
+    if !entries.rejected.is_empty() {
+        anyhow::bail!("{} has invalid broker definitions", file.display());
+    }
+    Ok(())
+}
+
+pub fn import_state(
+    db: &Database,
+    code: &str,
+    file: &Path,
+    dry_run: bool,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    let state = State::find(code)
+        .ok_or_else(|| AppError::Config(format!("no data broker registry known for {code}")))?;
+    let listing = state_registry::parse(&std::fs::read(file)?)
+        .map_err(|e| anyhow::anyhow!("{}: {e}", file.display()))?;
+    let (registrants, skipped) = (listing.registrants.len(), listing.skipped);
+
+    let mut existing = db.list_registry_brokers()?;
+    for broker in db.list_brokers(None)? {
+        if !existing.iter().any(|b| b.id == broker.id) {
+            existing.push(broker);
+        }
+    }
+    let import = state_registry::import(state, listing, &existing);
+
+    println!(
+        "{} data broker registry: {registrants} registrant(s) in {}.",
+        state.name,
+        file.display()
+    );
+    if skipped > 0 {
+        println!("  Skipped {skipped} row(s) without a name.");
+    }
+    println!(
+        "  {} already in the registry, {} new, {} matching brokers imported from state lists.",
+        import.known.len(),
+        import.added.len(),
+        import.merged
+    );
+    if !import.added.is_empty() || !import.updated.is_empty() {
+        let mut table = Table::new();
+        table.set_header(vec!["Broker", "Name", "Website", "Email", "Listed by"]);
+        for broker in import.added.iter().chain(&import.updated) {
+            table.add_row(vec![
+                Cell::new(&broker.id),
+                Cell::new(&broker.name),
+                Cell::new(broker.website.as_deref().unwrap_or("-")),
+                Cell::new(broker.privacy_email.as_deref().unwrap_or("-")),
+                Cell::new(broker.registry_source.as_deref().unwrap_or_default()),
+            ]);
+        }
+        println!("{table}");
+    }
+
+    // Everything this state lists that the registry lacks, for contributing.
+    let missing: Vec<&Broker> = existing
+        .iter()
+        .filter(|b| !import.updated.iter().any(|u| u.id == b.id))
+        .chain(&import.added)
+        .chain(&import.updated)
+        .filter(|b| state.lists(b))
+        .collect();
+    if let Some(output) = output {
+        let brokers: Vec<serde_json::Value> = missing
+            .iter()
+            .map(|b| {
+                let mut entry = serde_json::json!({ "id": b.id, "name": b.name });
+                for (field, value) in [
+                    ("website", &b.website),
+                    ("privacy_email", &b.privacy_email),
+                    ("postal_address", &b.postal_address),
+                ] {
+                    if let Some(value) = value {
+                        entry[field] = value.clone().into();
+                    }
+                }
+                entry["jurisdictions"] = b.jurisdictions.clone().into();
+                entry
+            })
+            .collect();
+        let document = serde_json::json!({
+            "schema_version": registry::SCHEMA_VERSION,
+            "brokers": brokers,
+        });
+        std::fs::write(output, serde_json::to_string_pretty(&document)? + "\n")?;
+        println!(
+            "Wrote {} broker(s) missing from the registry to {}.",
+            missing.len(),
+            output.display()
+        );
+    }
+
+    println!(
+        "{} broker(s) listed by {} are missing from the registry{}.",
+        missing.len(),
+        state.name,
+        if output.is_none() && !missing.is_empty() {
+            " (use --output to write them out for contributing)"
+        } else {
+            ""
+        }
+    );
+
+    if dry_run {
+        println!("Dry run: nothing was saved.");
+        return Ok(());
+    }
+    for broker in import.added.iter().chain(&import.updated) {
+        db.upsert_broker(broker)?;
+    }
+    db.set_registry_meta(
+        &format!("imported_at:{}", state.source()),
+        &chrono::Utc::now().to_rfc3339(),
+    )?;
     Ok(())
 }
@@ -3,9 +3,10 @@ use std::sync::Arc;
 
 use comfy_table::{Cell, Table};
 
-use crate::broker::{BrokerConnector, PersonQuery};
+use crate::broker::{BrokerConnector, KNOWN_DATA_TYPES, PersonQuery, coverage, family};
+use crate::cli::tasks;
 use crate::db::Database;
-use crate::db::models::{Broker, PersonalRecord};
+use crate::db::models::PersonalRecord;
 
 pub async fn scan(
     db: &Database,
@@ -13,56 +14,80 @@ pub async fn scan(
     query: &PersonQuery,
     broker_filter: &[String],
 ) -> anyhow::Result<()> {
-    let active_connectors: Vec<_> = if broker_filter.is_empty() {
-        connectors.iter().collect()
-    } else {
-        connectors
+    let mut brokers = coverage::known_brokers(db, connectors)?;
+    if !broker_filter.is_empty() {
+        let unknown: Vec<&str> = broker_filter
             .iter()
-            .filter(|(id, _)| broker_filter.contains(id))
-            .collect()
-    };
-
-    if active_connectors.is_empty() {
-        println!("No matching connectors found. Available connectors:");
-        for id in connectors.keys() {
-            println!("  - {id}");
+            .filter(|id| !brokers.iter().any(|b| &&b.id == id))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            println!(
+                "Unknown broker(s): {}. Run `data-breaker broker list` to see known brokers.",
+                unknown.join(", ")
+            );
         }
+        brokers.retain(|b| broker_filter.contains(&b.id));
+    }
+
+    if brokers.is_empty() {
+        println!(
+            "No brokers to scan. Run `data-breaker registry update` to fetch the broker registry."
+        );
         return Ok(());
     }
 
     let mut total_found = 0usize;
+    let mut manual = 0usize;
+    let mut unsearchable = 0usize;
 
-    for (id, connector) in &active_connectors {
-        if !connector.capabilities().can_scan {
-            tracing::info!("Skipping {} (no scan capability)", id);
+    for broker in &brokers {
+        let id = &broker.id;
+        let Some(connector) = connectors.get(id).filter(|c| c.capabilities().can_scan) else {
+            // People-search sites without a scanning connector are searched
+            // by hand.
+            match coverage::manual_scan_step(broker, query) {
+                Some(step) => {
+                    if db.count_open_manual_tasks(id, coverage::MANUAL_SCAN_REF)? == 0 {
+                        tasks::record_manual_steps(db, id, coverage::MANUAL_SCAN_REF, &[step])?;
+                    }
+                    manual += 1;
+                }
+                None => {
+                    tracing::info!("Skipping {} (no listings to search)", id);
+                    unsearchable += 1;
+                }
+            }
+            continue;
+        };
+        let caps = connector.capabilities();
+        if let Err(e) = caps.validate(connector.name(), query) {
+            println!("Skipping {}: {e}", connector.name());
             continue;
         }
 
-        // Ensure broker exists in DB (for FK constraint)
+        // Connectors the registry does not know (plugins, local scripts)
+        // still need a broker row for the FK.
         if db.get_broker(id)?.is_none() {
-            let now = chrono::Utc::now().to_rfc3339();
-            db.upsert_broker(&Broker {
-                id: id.to_string(),
-                name: connector.name().to_string(),
-                website: None,
-                description: None,
-                category: None,
-                connector: Some(id.to_string()),
-                registry_updated_at: None,
-                created_at: now.clone(),
-                updated_at: now,
-            })?;
+            db.upsert_broker(broker)?;
         }
 
         println!("Scanning {}...", connector.name());
         match connector.scan(query).await {
             Ok(records) => {
+                let verified = family::verify_scan(db, id, &records)?;
+                if verified > 0 {
+                    println!("  Verified {verified} removal(s) made through a parent broker.");
+                }
                 if records.is_empty() {
                     println!("  No records found.");
                     continue;
                 }
 
                 for record in &records {
+                    if !KNOWN_DATA_TYPES.contains(&record.data_type.as_str()) {
+                        tracing::warn!("{id} reported unknown data type '{}'", record.data_type);
+                    }
                     let personal_record = PersonalRecord {
                         id: uuid::Uuid::new_v4().to_string(),
                         broker_id: id.to_string(),
@@ -108,5 +133,13 @@ pub async fn scan(
     }
 
     println!("\nTotal new records found this scan: {total_found}");
+    if manual > 0 {
+        println!(
+            "{manual} broker(s) cannot be scanned automatically; search them by hand with `data-breaker tasks list`."
+        );
+    }
+    if unsearchable > 0 {
+        println!("{unsearchable} broker(s) publish no listings to search.");
+    }
     Ok(())
 }
@@ -3,7 +3,7 @@ use std::sync::Arc;
 
 use comfy_table::{Cell, Table};
 
-use crate::broker::BrokerConnector;
+use crate::broker::{BrokerConnector, family, workflow};
 use crate::db::Database;
 
 pub async fn status(
@@ -12,20 +12,31 @@ pub async fn status(
     broker_filter: Option<&str>,
     status_filter: Option<&str>,
 ) -> anyhow::Result<()> {
-    let mut requests = db.list_deletion_requests(broker_filter)?;
-
-    if let Some(filter) = status_filter {
-        requests.retain(|r| r.status == filter);
+    // Multi-step flows first: they may change the statuses listed below
+    let moved = workflow::advance_all(db, connectors).await?;
+    if moved > 0 {
+        println!("Advanced {moved} deletion workflow(s).");
     }
 
+    let load = || -> anyhow::Result<Vec<_>> {
+        let mut requests = db.list_deletion_requests(broker_filter)?;
+        if let Some(filter) = status_filter {
+            requests.retain(|r| r.status == filter);
+        }
+        Ok(requests)
+    };
+    let mut requests = load()?;
+
     if requests.is_empty() {
         println!("No deletion requests found.");
         return Ok(());
     }
 
-    // Check for status updates on in-flight requests
+    // Check for status updates on in-flight requests. Linked requests are
+    // left to `family::sync`, which copies their parent's status.
     for req in &mut requests {
         if matches!(req.status.as_str(), "submitted" | "in_progress")
+            && req.covered_by.is_none()
             && let Some(ext_ref) = &req.external_ref
             && let Some(connector) = connectors.get(&req.broker_id)
             && connector.capabilities().can_check_status
@@ -48,13 +59,25 @@ pub async fn status(
         }
     }
 
+    // Brands removed through a parent follow its request
+    if family::sync(db, connectors)? > 0 {
+        requests = load()?;
+    }
+
     let mut table = Table::new();
     table.set_header(vec!["ID", "Broker", "Status", "Submitted", "External Ref"]);
 
     for req in &requests {
+        let broker = match req.covered_by.as_deref() {
+            Some(covering) => match db.get_deletion_request(covering)? {
+                Some(c) => format!("{} (via {})", req.broker_id, c.broker_id),
+                None => req.broker_id.clone(),
+            },
+            None => req.broker_id.clone(),
+        };
         table.add_row(vec![
             Cell::new(&req.id[..8]),
-            Cell::new(&req.broker_id),
+            Cell::new(broker),
             Cell::new(&req.status),
             Cell::new(req.submitted_at.as_deref().unwrap_or("-")),
             Cell::new(req.external_ref.as_deref().unwrap_or("-")),
@@ -62,5 +85,21 @@ pub async fn status(
     }
 
     println!("{table}");
+
+    let flows: Vec<_> = db
+        .list_deletion_workflows(Some("active"))?
+        .into_iter()
+        .filter(|f| broker_filter.is_none_or(|b| b == f.broker_id))
+        .collect();
+    for flow in &flows {
+        if let Some(connector) = connectors.get(&flow.broker_id) {
+            println!(
+                "{} ({}): workflow {}",
+                flow.broker_id,
+                flow.external_ref,
+                workflow::describe(db, flow, connector.as_ref())
+            );
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use comfy_table::{Cell, Table};
+
+use crate::broker::{BrokerConnector, ManualStep, ManualStepOutcome, coverage, workflow};
+use crate::db::Database;
+use crate::db::models::{ManualTask, PersonalRecord};
+use crate::handoff::{self, HandoffPage, HandoffStep};
+
+/// Store the manual steps a connector returned as open tasks.
+pub fn record_manual_steps(
+    db: &Database,
+    broker_id: &str,
+    external_ref: &str,
+    steps: &[ManualStep],
+) -> anyhow::Result<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    for step in steps {
+        let prefill_json = if step.prefill.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&step.prefill)?)
+        };
+        db.insert_manual_task(&ManualTask {
+            id: uuid::Uuid::new_v4().to_string(),
+            broker_id: broker_id.to_string(),
+            external_ref: external_ref.to_string(),
+            step_key: step.key.clone(),
+            kind: step.kind.as_str().to_string(),
+            instructions: step.instructions.clone(),
+            url: step.url.clone(),
+            prefill_json,
+            status: "open".to_string(),
+            response: None,
+            completed_at: None,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+        })?;
+    }
+    Ok(())
+}
+
+pub fn list_tasks(db: &Database, all: bool, broker_filter: Option<&str>) -> anyhow::Result<()> {
+    let mut tasks = db.list_manual_tasks(if all { None } else { Some("open") })?;
+    if let Some(bid) = broker_filter {
+        tasks.retain(|t| t.broker_id == bid);
+    }
+
+    if tasks.is_empty() {
+        println!("No manual tasks found.");
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_header(vec!["ID", "Broker", "Kind", "Status", "Instructions"]);
+
+    for t in &tasks {
+        table.add_row(vec![
+            Cell::new(&t.id[..8]),
+            Cell::new(&t.broker_id),
+            Cell::new(&t.kind),
+            Cell::new(&t.status),
+            Cell::new(&t.instructions),
+        ]);
+    }
+
+    println!("{table}");
+    Ok(())
+}
+
+pub fn show_task(db: &Database, id: &str) -> anyhow::Result<()> {
+    let task = find_task(db, id)?;
+
+    println!("ID:           {}", task.id);
+    println!("Broker:       {}", task.broker_id);
+    println!("Kind:         {}", task.kind);
+    println!("Status:       {}", task.status);
+    println!("Request ref:  {}", task.external_ref);
+    if let Some(url) = &task.url {
+        println!("URL:          {url}");
+    }
+    println!("Instructions: {}", task.instructions);
+
+    let prefill = task_prefill(&task)?;
+    if !prefill.is_empty() {
+        println!("Values to enter:");
+        for (label, value) in &prefill {
+            println!("  {label}: {value}");
+        }
+    }
+    if let Some(resp) = &task.response {
+        println!("Response:     {resp}");
+    }
+    if task.status == "open" {
+        println!(
+            "\nWhen finished, run `data-breaker tasks done {}`.",
+            &task.id[..8]
+        );
+    }
+    Ok(())
+}
+
+/// Mark a task done and let the connector continue its deletion flow.
+pub async fn complete_task(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    id: &str,
+    value: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut task = find_open_task(db, id)?;
+    let outcome = resolve_task(db, connectors, &mut task, value).await?;
+    println!("Task {} marked done.", &task.id[..8]);
+    after_task(db, connectors, &task, outcome).await
+}
+
+/// Hand the open tasks of a submission over to the browser and record the
+/// confirmation number the user reports back.
+pub async fn handoff_task(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    id: &str,
+    open_browser: bool,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let task = find_open_task(db, id)?;
+    handoff_submission(
+        db,
+        connectors,
+        &task.broker_id,
+        &task.external_ref,
+        open_browser,
+        timeout,
+    )
+    .await
+}
+
+pub async fn handoff_submission(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    broker_id: &str,
+    external_ref: &str,
+    open_browser: bool,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let mut tasks = db.list_manual_tasks_for_ref(broker_id, external_ref)?;
+    tasks.retain(|t| t.status == "open");
+    if tasks.is_empty() {
+        anyhow::bail!("No open tasks for request '{}'", external_ref);
+    }
+
+    let broker_name = match connectors.get(broker_id) {
+        Some(c) => c.name().to_string(),
+        None => db
+            .get_broker(broker_id)?
+            .map(|b| b.name)
+            .unwrap_or_else(|| broker_id.to_string()),
+    };
+    let mut steps = Vec::new();
+    for t in &tasks {
+        steps.push(HandoffStep {
+            instructions: t.instructions.clone(),
+            url: t.url.clone(),
+            prefill: task_prefill(t)?,
+        });
+    }
+    let page = HandoffPage { broker_name, steps };
+
+    let Some(confirmation) = handoff::run(&page, open_browser, timeout).await? else {
+        println!("Handoff timed out; the tasks remain open.");
+        return Ok(());
+    };
+    let input = (!confirmation.is_empty()).then_some(confirmation.as_str());
+
+    // The page lists the steps in order and asks for the confirmation the
+    // last one ends with; the earlier steps were just done.
+    let last = tasks.len() - 1;
+    let mut outcome = ManualStepOutcome::default();
+    for (i, task) in tasks.iter_mut().enumerate() {
+        let value = input.filter(|_| i == last);
+        let step = resolve_task(db, connectors, task, value).await?;
+        outcome.status = step.status.or(outcome.status);
+        outcome.external_ref = step.external_ref.or(outcome.external_ref);
+        outcome.message = step.message.or(outcome.message);
+        outcome.next_steps.extend(step.next_steps);
+    }
+    if outcome.external_ref.is_none() {
+        outcome.external_ref = input.map(str::to_string);
+    }
+
+    println!("Recorded {} completed task(s).", tasks.len());
+    after_task(db, connectors, &tasks[0], outcome).await
+}
+
+/// Mark a task done and ask its connector how the flow continues.
+async fn resolve_task(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    task: &mut ManualTask,
+    value: Option<&str>,
+) -> anyhow::Result<ManualStepOutcome> {
+    // Workflow tasks are picked up by the workflow itself, and manual
+    // scans belong to no deletion flow.
+    let outcome =
+        if workflow::owns_task(&task.step_key) || task.step_key == coverage::MANUAL_SCAN_STEP {
+            ManualStepOutcome::default()
+        } else if let Some(connector) = connectors.get(&task.broker_id) {
+            // A failed resume leaves the task open so it can be retried.
+            connector
+                .resume_deletion(&task.external_ref, &task.step_key, value)
+                .await?
+        } else {
+            tracing::warn!(
+                "No connector for broker '{}', cannot resume its flow",
+                task.broker_id
+            );
+            ManualStepOutcome::default()
+        };
+
+    let now = chrono::Utc::now().to_rfc3339();
+    task.status = "done".to_string();
+    task.response = value.map(str::to_string);
+    task.completed_at = Some(now.clone());
+    task.updated_at = now;
+    db.update_manual_task(task)?;
+    Ok(outcome)
+}
+
+/// Mark a task as not applicable. The connector is not consulted.
+pub async fn skip_task(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    id: &str,
+) -> anyhow::Result<()> {
+    let mut task = find_open_task(db, id)?;
+    let now = chrono::Utc::now().to_rfc3339();
+    task.status = "skipped".to_string();
+    task.completed_at = Some(now.clone());
+    task.updated_at = now;
+    db.update_manual_task(&task)?;
+
+    println!("Task {} skipped.", &task.id[..8]);
+    after_task(db, connectors, &task, ManualStepOutcome::default()).await
+}
+
+/// Let the submission's workflow continue after one of its tasks, or apply
+/// the connector's outcome to a task outside any workflow.
+async fn after_task(
+    db: &Database,
+    connectors: &HashMap<String, Arc<dyn BrokerConnector>>,
+    task: &ManualTask,
+    outcome: ManualStepOutcome,
+) -> anyhow::Result<()> {
+    if task.step_key == coverage::MANUAL_SCAN_STEP {
+        return record_manual_scan(db, task);
+    }
+    if !workflow::owns_task(&task.step_key) {
+        return advance_deletion(db, task, outcome);
+    }
+    workflow::advance_ref(db, connectors, &task.broker_id, &task.external_ref).await?;
+    if let Some(flow) = db.get_deletion_workflow(&task.broker_id, &task.external_ref)?
+        && let Some(connector) = connectors.get(&task.broker_id)
+    {
+        println!(
+            "Workflow for {} {}.",
+            task.broker_id,
+            workflow::describe(db, &flow, connector.as_ref())
+        );
+    }
+    Ok(())
+}
+
+/// Apply a step outcome to the deletion requests of the task's submission.
+fn advance_deletion(
+    db: &Database,
+    task: &ManualTask,
+    outcome: ManualStepOutcome,
+) -> anyhow::Result<()> {
+    let old_ref = task.external_ref.as_str();
+    let new_ref = outcome.external_ref.as_deref().unwrap_or(old_ref);
+    if new_ref != old_ref {
+        db.rename_manual_task_ref(&task.broker_id, old_ref, new_ref)?;
+        db.rename_deletion_workflow_ref(&task.broker_id, old_ref, new_ref)?;
+    }
+
+    if !outcome.next_steps.is_empty() {
+        record_manual_steps(db, &task.broker_id, new_ref, &outcome.next_steps)?;
+    }
+
+    let open = db.count_open_manual_tasks(&task.broker_id, new_ref)?;
+    let status = match outcome.status {
+        Some(s) => s,
+        None if open == 0 => "submitted".to_string(),
+        None => "awaiting_user".to_string(),
+    };
+
+    // Only a failure explains itself in `error_message`; other messages are
+    // progress notes for the user.
+    let failed = matches!(status.as_str(), "failed" | "rejected");
+    if let Some(message) = outcome.message.as_deref().filter(|_| !failed) {
+        println!("{}: {message}", task.broker_id);
+    }
+    let now = chrono::Utc::now().to_rfc3339();
+    db.update_deletion_requests_by_ref(
+        &task.broker_id,
+        old_ref,
+        &status,
+        new_ref,
+        outcome.message.as_deref().filter(|_| failed),
+        &now,
+    )?;
+
+    if open > 0 {
+        println!("{open} task(s) remaining for this request.");
+    } else {
+        println!("Deletion request for {} is now {status}.", task.broker_id);
+    }
+    Ok(())
+}
+
+/// Store the listing the user found while searching a broker by hand.
+fn record_manual_scan(db: &Database, task: &ManualTask) -> anyhow::Result<()> {
+    let Some(url) = task.response.as_deref().filter(|_| task.status == "done") else {
+        if task.status == "done" {
+            println!("No listing on {}.", task.broker_id);
+        }
+        return Ok(());
+    };
+    let prefill = task_prefill(task)?;
+    let field = |label: &str| {
+        prefill
+            .iter()
+            .find(|(l, _)| l == label)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_default()
+    };
+    let now = chrono::Utc::now().to_rfc3339();
+    db.upsert_personal_record(&PersonalRecord {
+        id: uuid::Uuid::new_v4().to_string(),
+        broker_id: task.broker_id.clone(),
+        data_type: "name".to_string(),
+        data_value: format!("{} {}", field("First name"), field("Last name")),
+        profile_url: Some(url.to_string()),
+        raw_json: None,
+        found_at: now,
+    })?;
+    println!(
+        "Recorded a listing on {0}. See `data-breaker broker info {0}` for how to remove it.",
+        task.broker_id
+    );
+    Ok(())
+}
+
+fn find_task(db: &Database, id: &str) -> anyhow::Result<ManualTask> {
+    let mut matches = db.find_manual_tasks(id)?;
+    match matches.len() {
+        0 => anyhow::bail!("Task '{}' not found", id),
+        1 => Ok(matches.remove(0)),
+        n => anyhow::bail!("Task ID '{}' is ambiguous ({} matches)", id, n),
+    }
+}
+
+pub fn find_open_task(db: &Database, id: &str) -> anyhow::Result<ManualTask> {
+    let task = find_task(db, id)?;
+    if task.status != "open" {
+        anyhow::bail!("Task '{}' is already {}", id, task.status);
+    }
+    Ok(task)
+}
+
+fn task_prefill(task: &ManualTask) -> anyhow::Result<Vec<(String, String)>> {
+    match &task.prefill_json {
+        Some(json) => Ok(serde_json::from_str(json)?),
+        None => Ok(vec![]),
+    }
+}
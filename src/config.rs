@@ -1,10 +1,22 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use directories::ProjectDirs;
+use serde::Deserialize;
 
 pub const REGISTRY_URL: &str =
     "https://raw.githubusercontent.com/bombfork/data-breaker-registry/main/brokers.json";
 
+/// Minisign public keys the registry may be signed with. Pinning the next
+/// key alongside the current one lets the maintainers rotate keys without
+/// waiting for users to upgrade; a retired key is dropped in a release.
+///
+/// Empty until the registry has a release key. Signatures are required by
+/// default all the same, so until then the public registry is only applied
+/// once its key is added to `[registry] trusted_keys` or
+/// `[registry] require_signature` is turned off.
+pub const REGISTRY_KEYS: &[&str] = &[];
+
 pub fn project_dirs() -> anyhow::Result<ProjectDirs> {
     ProjectDirs::from("", "bombfork", "data-breaker")
         .ok_or_else(|| anyhow::anyhow!("Could not determine home directory"))
@@ -16,3 +28,418 @@ pub fn db_path() -> anyhow::Result<PathBuf> {
     std::fs::create_dir_all(data_dir)?;
     Ok(data_dir.join("data-breaker.db"))
 }
+
+/// Where generated postal letters are written. Created on first use.
+pub fn letters_dir() -> anyhow::Result<PathBuf> {
+    Ok(project_dirs()?.data_dir().join("letters"))
+}
+
+/// Default directory scanned for connector plugin executables.
+pub fn plugins_dir() -> anyhow::Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().join("plugins"))
+}
+
+/// Default directory scanned for local `*.rhai` connector scripts.
+pub fn scripts_dir() -> anyhow::Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().join("scripts"))
+}
+
+/// Cache of downloaded WebAssembly connector modules, named by hash.
+pub fn wasm_dir() -> anyhow::Result<PathBuf> {
+    Ok(project_dirs()?.data_dir().join("wasm"))
+}
+
+pub fn config_path() -> anyhow::Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().join("config.toml"))
+}
+
+/// User configuration, read from `config.toml` in the config directory.
+/// Every section is optional; a missing file means defaults everywhere.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub smtp: Option<SmtpConfig>,
+    #[serde(default)]
+    pub email: EmailConfig,
+    #[serde(default)]
+    pub mailbox: Option<MailboxConfig>,
+    #[serde(default)]
+    pub postal: Option<PostalConfig>,
+    #[serde(default)]
+    pub plugins: PluginsConfig,
+    #[serde(default)]
+    pub scripts: ScriptsConfig,
+    #[serde(default)]
+    pub wasm: WasmConfig,
+    #[serde(default)]
+    pub dummy: DummyConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub registry: RegistryConfig,
+}
+
+impl Config {
+    pub fn load() -> anyhow::Result<Self> {
+        let path = config_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path)?;
+        toml::from_str(&text)
+            .map_err(|e| crate::error::AppError::Config(format!("{}: {e}", path.display())).into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text; only sensible for a local relay or test sink.
+    None,
+    #[default]
+    Starttls,
+    /// Implicit TLS (SMTPS, usually port 465).
+    Tls,
+}
+
+/// Outgoing mail server used for email opt-out requests.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    /// Falls back to the `DATA_BREAKER_SMTP_PASSWORD` environment variable.
+    pub password: Option<String>,
+    /// Sender address; brokers reply here.
+    pub from: String,
+    pub from_name: Option<String>,
+}
+
+impl SmtpConfig {
+    pub fn password(&self) -> Option<String> {
+        self.password
+            .clone()
+            .or_else(|| std::env::var("DATA_BREAKER_SMTP_PASSWORD").ok())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailConfig {
+    /// Path to a custom request template replacing the built-in one.
+    pub template: Option<PathBuf>,
+}
+
+/// Out-of-process connector plugins.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginsConfig {
+    /// Directory of plugin executables; defaults to `plugins/` next to
+    /// `config.toml`.
+    pub dir: Option<PathBuf>,
+    /// Deadline for a single plugin call.
+    #[serde(default = "default_plugin_timeout")]
+    pub timeout_secs: u64,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            timeout_secs: default_plugin_timeout(),
+        }
+    }
+}
+
+/// Scripted connectors and the limits they run under.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptsConfig {
+    /// Directory of local `*.rhai` scripts; defaults to `scripts/` next to
+    /// `config.toml`.
+    pub dir: Option<PathBuf>,
+    /// Wall-clock limit for a single script call.
+    #[serde(default = "default_script_timeout")]
+    pub timeout_secs: u64,
+    /// Rhai operation budget per call (0 means unlimited).
+    #[serde(default = "default_script_operations")]
+    pub max_operations: u64,
+    /// HTTP requests a single call may make.
+    #[serde(default = "default_script_requests")]
+    pub max_requests: usize,
+    /// Domains each script may reach besides its broker's website, by broker
+    /// ID. Local scripts without a registry entry need one here.
+    #[serde(default)]
+    pub domains: HashMap<String, Vec<String>>,
+}
+
+impl Default for ScriptsConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            timeout_secs: default_script_timeout(),
+            max_operations: default_script_operations(),
+            max_requests: default_script_requests(),
+            domains: HashMap::new(),
+        }
+    }
+}
+
+/// How every outgoing HTTP request is made.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Deadline for a whole request, body included.
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_http_connect_timeout")]
+    pub connect_timeout_secs: u64,
+    /// `http://`, `https://`, `socks5://` or `socks5h://` proxy URL.
+    pub proxy: Option<String>,
+    /// Send everything through the local Tor SOCKS port
+    /// (`socks5h://127.0.0.1:9050`, so DNS goes through Tor too).
+    #[serde(default)]
+    pub tor: bool,
+    /// Replaces the default `data-breaker/<version>` user agent.
+    pub user_agent: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_http_timeout(),
+            connect_timeout_secs: default_http_connect_timeout(),
+            proxy: None,
+            tor: false,
+            user_agent: None,
+        }
+    }
+}
+
+/// Where broker definitions come from and which signing keys are trusted.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistryConfig {
+    /// Minisign public keys trusted in addition to the built-in ones, e.g.
+    /// for a self-hosted registry.
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    /// Refuse remote registries that are unsigned or signed by a key not
+    /// trusted here. Turning it off applies them with a warning.
+    #[serde(default = "default_true")]
+    pub require_signature: bool,
+    /// Use the public registry at `REGISTRY_URL` as source `public` with
+    /// priority 0.
+    #[serde(default = "default_true")]
+    pub public: bool,
+    /// Further sources, e.g. a private overlay of internal brokers.
+    #[serde(default)]
+    pub sources: Vec<RegistrySource>,
+}
+
+impl Default for RegistryConfig {
+    fn default() -> Self {
+        Self {
+            trusted_keys: vec![],
+            require_signature: true,
+            public: true,
+            sources: vec![],
+        }
+    }
+}
+
+/// One place to read broker definitions from: a `url` or a `path`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistrySource {
+    pub name: String,
+    /// Remote `brokers.json`, signed like the public registry.
+    pub url: Option<String>,
+    /// Local JSON file, or a directory of `*.json` files. Trusted as is.
+    pub path: Option<PathBuf>,
+    /// When sources define the same broker, the higher priority wins.
+    #[serde(default = "default_source_priority")]
+    pub priority: i32,
+}
+
+/// Resource limits for WebAssembly connectors.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WasmConfig {
+    /// Fuel (roughly, instructions) a single call may burn.
+    #[serde(default = "default_wasm_fuel")]
+    pub fuel: u64,
+    /// Linear memory cap per instance.
+    #[serde(default = "default_wasm_memory")]
+    pub max_memory_mb: usize,
+    /// HTTP requests a single call may make.
+    #[serde(default = "default_script_requests")]
+    pub max_requests: usize,
+}
+
+impl Default for WasmConfig {
+    fn default() -> Self {
+        Self {
+            fuel: default_wasm_fuel(),
+            max_memory_mb: default_wasm_memory(),
+            max_requests: default_script_requests(),
+        }
+    }
+}
+
+/// Simulated behaviour of the dummy connector, for demos and tests.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DummyConfig {
+    /// Presets to combine; `DATA_BREAKER_DUMMY_SCENARIO` (comma-separated)
+    /// adds more.
+    #[serde(default)]
+    pub scenarios: Vec<DummyScenario>,
+    /// Seconds per status step: submitted, then in_progress, then done.
+    pub progress_secs: Option<u64>,
+    /// Chance (0.0 to 1.0) that a call fails or a request is rejected.
+    pub failure_rate: Option<f64>,
+    /// Other people with the same name returned by every scan.
+    pub namesakes: Option<u32>,
+    /// Seconds after removal before a record shows up again.
+    pub relist_secs: Option<u64>,
+    /// Delay before every response.
+    pub delay_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DummyScenario {
+    /// Requests move through submitted and in_progress to completed.
+    Lifecycle,
+    /// Some calls fail and some requests are rejected.
+    Flaky,
+    /// Scans also find people who merely share the name.
+    Namesakes,
+    /// Removed records come back after a while.
+    Relisting,
+    /// Every call takes a few seconds.
+    Slow,
+}
+
+impl std::str::FromStr for DummyScenario {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "lifecycle" => Ok(Self::Lifecycle),
+            "flaky" => Ok(Self::Flaky),
+            "namesakes" => Ok(Self::Namesakes),
+            "relisting" => Ok(Self::Relisting),
+            "slow" => Ok(Self::Slow),
+            other => Err(format!("unknown dummy scenario '{other}'")),
+        }
+    }
+}
+
+/// Sender details printed on postal deletion requests.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostalConfig {
+    pub name: String,
+    /// Return address, one entry per line.
+    pub address: Vec<String>,
+    /// Email address the broker may use to confirm the request.
+    pub email: Option<String>,
+    /// Path to a custom letter template replacing the built-in one.
+    pub template: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MailboxKind {
+    Imap,
+    Maildir,
+    Mbox,
+}
+
+/// Inbox watched for broker replies to deletion requests.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MailboxConfig {
+    pub kind: MailboxKind,
+    /// Maildir directory or mbox file.
+    pub path: Option<PathBuf>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    /// IMAP over implicit TLS; disable only for a local test server.
+    #[serde(default = "default_true")]
+    pub tls: bool,
+    pub username: Option<String>,
+    /// Falls back to the `DATA_BREAKER_IMAP_PASSWORD` environment variable.
+    pub password: Option<String>,
+    #[serde(default = "default_folder")]
+    pub folder: String,
+    /// How far back to look for replies.
+    #[serde(default = "default_lookback_days")]
+    pub lookback_days: u32,
+    /// Extra domains whose confirmation links may be followed, on top of
+    /// the broker's own website and privacy email domains.
+    #[serde(default)]
+    pub allowed_link_domains: Vec<String>,
+}
+
+impl MailboxConfig {
+    pub fn password(&self) -> Option<String> {
+        self.password
+            .clone()
+            .or_else(|| std::env::var("DATA_BREAKER_IMAP_PASSWORD").ok())
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_source_priority() -> i32 {
+    10
+}
+
+fn default_plugin_timeout() -> u64 {
+    60
+}
+
+fn default_script_timeout() -> u64 {
+    60
+}
+
+fn default_script_operations() -> u64 {
+    10_000_000
+}
+
+fn default_script_requests() -> usize {
+    20
+}
+
+fn default_http_timeout() -> u64 {
+    30
+}
+
+fn default_http_connect_timeout() -> u64 {
+    10
+}
+
+fn default_wasm_fuel() -> u64 {
+    5_000_000_000
+}
+
+fn default_wasm_memory() -> usize {
+    64
+}
+
+fn default_folder() -> String {
+    "INBOX".to_string()
+}
+
+fn default_lookback_days() -> u32 {
+    60
+}
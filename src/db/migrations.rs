@@ -42,6 +42,97 @@ const MIGRATIONS: &[&str] = &[
         key TEXT PRIMARY KEY,
         value TEXT NOT NULL
     );",
+    // Migration 2: Manual tasks (CAPTCHA, phone verification, email links)
+    "CREATE TABLE IF NOT EXISTS manual_tasks (
+        id TEXT PRIMARY KEY,
+        broker_id TEXT NOT NULL REFERENCES brokers(id),
+        external_ref TEXT NOT NULL,
+        step_key TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        instructions TEXT NOT NULL,
+        url TEXT,
+        prefill_json TEXT,
+        status TEXT NOT NULL DEFAULT 'open',
+        response TEXT,
+        completed_at TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_manual_tasks_ref ON manual_tasks(broker_id, external_ref);",
+    // Migration 3: Email opt-out address
+    "ALTER TABLE brokers ADD COLUMN privacy_email TEXT;",
+    // Migration 4: Broker emails kept as evidence for deletion requests
+    "CREATE TABLE IF NOT EXISTS email_evidence (
+        id TEXT PRIMARY KEY,
+        message_id TEXT NOT NULL UNIQUE,
+        broker_id TEXT NOT NULL REFERENCES brokers(id),
+        external_ref TEXT,
+        sender TEXT,
+        subject TEXT,
+        classification TEXT NOT NULL,
+        followed_link TEXT,
+        raw_message TEXT NOT NULL,
+        received_at TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );",
+    // Migration 5: Postal requests and the log of mailed letters
+    "ALTER TABLE brokers ADD COLUMN postal_address TEXT;
+
+    CREATE TABLE IF NOT EXISTS postal_mailings (
+        id TEXT PRIMARY KEY,
+        broker_id TEXT NOT NULL REFERENCES brokers(id),
+        external_ref TEXT NOT NULL,
+        letter_path TEXT NOT NULL,
+        sent_on TEXT NOT NULL,
+        tracking_number TEXT,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+    );",
+    // Migration 6: Scripted connectors shipped with the registry
+    "ALTER TABLE brokers ADD COLUMN script TEXT;",
+    // Migration 7: WebAssembly connector manifests
+    "ALTER TABLE brokers ADD COLUMN wasm_manifest TEXT;",
+    // Migration 8: Connector sessions (cookies and state between runs)
+    "CREATE TABLE IF NOT EXISTS broker_sessions (
+        broker_id TEXT PRIMARY KEY,
+        cookies TEXT NOT NULL,
+        state TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );",
+    // Migration 9: Multi-step deletion workflows, one per submission
+    "CREATE TABLE IF NOT EXISTS deletion_workflows (
+        id TEXT PRIMARY KEY,
+        broker_id TEXT NOT NULL REFERENCES brokers(id),
+        external_ref TEXT NOT NULL,
+        step TEXT NOT NULL,
+        state TEXT NOT NULL DEFAULT 'active',
+        data_json TEXT NOT NULL,
+        entered_at TEXT NOT NULL,
+        wake_at TEXT,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT,
+        revision INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+        UNIQUE(broker_id, external_ref)
+    );",
+    // Migration 10: Registry sources each broker definition came from;
+    // signing metadata is now kept per source
+    "ALTER TABLE brokers ADD COLUMN registry_source TEXT;
+    UPDATE registry_meta SET key = key || ':public' WHERE key IN ('signing_key', 'signed_at');",
+    // Migration 11: Brokers that disappeared from the registry
+    "ALTER TABLE brokers ADD COLUMN retired_at TEXT;",
+    // Migration 12: Opt-out details and broker families from the registry
+    "ALTER TABLE brokers ADD COLUMN opt_out_url TEXT;
+    ALTER TABLE brokers ADD COLUMN request_methods TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE brokers ADD COLUMN verification TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE brokers ADD COLUMN processing_days INTEGER;
+    ALTER TABLE brokers ADD COLUMN relisting_days INTEGER;
+    ALTER TABLE brokers ADD COLUMN jurisdictions TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE brokers ADD COLUMN parent_id TEXT;",
+    // Migration 13: Brands removed through their parent's shared database
+    "ALTER TABLE brokers ADD COLUMN shared_database INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE deletion_requests ADD COLUMN covered_by TEXT REFERENCES deletion_requests(id);",
 ];
 
 pub fn run_migrations(conn: &Connection) -> rusqlite::Result<()> {
@@ -35,7 +35,7 @@ impl Database {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::db::models::{Broker, DeletionRequest, PersonalRecord};
+    use crate::db::models::{Broker, DeletionRequest, ManualTask, PersonalRecord, PostalMailing};
 
     fn test_db() -> Database {
         Database::open_in_memory().expect("Failed to create test database")
@@ -63,15 +63,30 @@ mod tests {
             description: Some("A test broker".into()),
             category: Some("people-search".into()),
             connector: None,
+            privacy_email: None,
+            postal_address: None,
+            script: None,
+            wasm_manifest: None,
+            registry_source: None,
             registry_updated_at: None,
+            retired_at: None,
+            opt_out_url: None,
+            request_methods: vec!["web_form".into(), "email".into()],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: None,
+            shared_database: false,
             created_at: now.clone(),
-            updated_at: now,
+            updated_at: now.clone(),
         };
 
         db.upsert_broker(&broker).unwrap();
 
         let fetched = db.get_broker("test-broker").unwrap().unwrap();
         assert_eq!(fetched.name, "Test Broker");
+        assert_eq!(fetched.request_methods, ["web_form", "email"]);
 
         let all = db.list_brokers(None).unwrap();
         assert_eq!(all.len(), 1);
@@ -81,6 +96,12 @@ mod tests {
 
         let empty = db.list_brokers(Some("nonexistent")).unwrap();
         assert!(empty.is_empty());
+
+        // Retired brokers drop out of listings but stay on record.
+        db.retire_broker("test-broker", &now).unwrap();
+        assert!(db.list_brokers(None).unwrap().is_empty());
+        let fetched = db.get_broker("test-broker").unwrap().unwrap();
+        assert_eq!(fetched.retired_at.as_deref(), Some(now.as_str()));
     }
 
     #[test]
@@ -96,7 +117,21 @@ mod tests {
             description: None,
             category: None,
             connector: None,
+            privacy_email: None,
+            postal_address: None,
+            script: None,
+            wasm_manifest: None,
+            registry_source: None,
             registry_updated_at: None,
+            retired_at: None,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: None,
+            shared_database: false,
             created_at: now.clone(),
             updated_at: now.clone(),
         };
@@ -135,7 +170,21 @@ mod tests {
             description: None,
             category: None,
             connector: None,
+            privacy_email: None,
+            postal_address: None,
+            script: None,
+            wasm_manifest: None,
+            registry_source: None,
             registry_updated_at: None,
+            retired_at: None,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: None,
+            shared_database: false,
             created_at: now.clone(),
             updated_at: now.clone(),
         };
@@ -152,6 +201,7 @@ mod tests {
             external_ref: Some("ref-123".into()),
             created_at: now.clone(),
             updated_at: now.clone(),
+            covered_by: None,
         };
         db.insert_deletion_request(&req).unwrap();
 
@@ -160,6 +210,159 @@ mod tests {
         assert_eq!(all[0].status, "submitted");
     }
 
+    #[test]
+    fn test_manual_tasks_advance_deletion() {
+        let db = test_db();
+        let now = chrono::Utc::now().to_rfc3339();
+
+        let broker = Broker {
+            id: "test-broker".into(),
+            name: "Test".into(),
+            website: None,
+            description: None,
+            category: None,
+            connector: None,
+            privacy_email: None,
+            postal_address: None,
+            script: None,
+            wasm_manifest: None,
+            registry_source: None,
+            registry_updated_at: None,
+            retired_at: None,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: None,
+            shared_database: false,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+        };
+        db.upsert_broker(&broker).unwrap();
+
+        let req = DeletionRequest {
+            id: "del-1".into(),
+            broker_id: "test-broker".into(),
+            personal_record_id: None,
+            status: "awaiting_user".into(),
+            submitted_at: None,
+            completed_at: None,
+            error_message: None,
+            external_ref: Some("LOCAL-1".into()),
+            created_at: now.clone(),
+            updated_at: now.clone(),
+            covered_by: None,
+        };
+        db.insert_deletion_request(&req).unwrap();
+
+        let mut task = ManualTask {
+            id: "0123abcd-task".into(),
+            broker_id: "test-broker".into(),
+            external_ref: "LOCAL-1".into(),
+            step_key: "captcha".into(),
+            kind: "solve_captcha".into(),
+            instructions: "Solve it".into(),
+            url: Some("https://example.com/optout".into()),
+            prefill_json: None,
+            status: "open".into(),
+            response: None,
+            completed_at: None,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+        };
+        db.insert_manual_task(&task).unwrap();
+
+        assert_eq!(db.find_manual_tasks("0123").unwrap().len(), 1);
+        assert!(db.find_manual_tasks("ffff").unwrap().is_empty());
+        assert_eq!(
+            db.count_open_manual_tasks("test-broker", "LOCAL-1")
+                .unwrap(),
+            1
+        );
+
+        task.status = "done".into();
+        db.update_manual_task(&task).unwrap();
+        assert_eq!(
+            db.count_open_manual_tasks("test-broker", "LOCAL-1")
+                .unwrap(),
+            0
+        );
+        assert_eq!(db.list_manual_tasks(Some("open")).unwrap().len(), 0);
+
+        let n = db
+            .update_deletion_requests_by_ref(
+                "test-broker",
+                "LOCAL-1",
+                "submitted",
+                "CONF-42",
+                None,
+                &now,
+            )
+            .unwrap();
+        assert_eq!(n, 1);
+        let all = db.list_deletion_requests(None).unwrap();
+        assert_eq!(all[0].status, "submitted");
+        assert_eq!(all[0].external_ref.as_deref(), Some("CONF-42"));
+        assert!(all[0].submitted_at.is_some());
+    }
+
+    #[test]
+    fn test_postal_mailings() {
+        let db = test_db();
+        let now = chrono::Utc::now().to_rfc3339();
+        db.upsert_broker(&Broker {
+            id: "mail-only".into(),
+            name: "Mail Only".into(),
+            website: None,
+            description: None,
+            category: None,
+            connector: Some("postal".into()),
+            privacy_email: None,
+            postal_address: Some("PO Box 1\nAnytown, CA 90000".into()),
+            script: None,
+            wasm_manifest: None,
+            registry_source: None,
+            registry_updated_at: None,
+            retired_at: None,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: None,
+            shared_database: false,
+            created_at: now.clone(),
+            updated_at: now.clone(),
+        })
+        .unwrap();
+        let broker = db.get_broker("mail-only").unwrap().unwrap();
+        assert_eq!(
+            broker.postal_address.as_deref(),
+            Some("PO Box 1\nAnytown, CA 90000")
+        );
+
+        for (sent_on, tracking) in [("2024-05-01", None), ("2024-06-01", Some("9400"))] {
+            db.insert_postal_mailing(&PostalMailing {
+                id: uuid::Uuid::new_v4().to_string(),
+                broker_id: "mail-only".into(),
+                external_ref: format!("POSTAL-{sent_on}"),
+                letter_path: "/tmp/letter.pdf".into(),
+                sent_on: sent_on.into(),
+                tracking_number: tracking.map(str::to_string),
+                created_at: now.clone(),
+            })
+            .unwrap();
+        }
+
+        let mailings = db.list_postal_mailings().unwrap();
+        assert_eq!(mailings.len(), 2);
+        assert_eq!(mailings[0].sent_on, "2024-06-01");
+        assert_eq!(mailings[0].tracking_number.as_deref(), Some("9400"));
+    }
+
     #[test]
     fn test_registry_meta() {
         let db = test_db();
@@ -8,7 +8,35 @@ pub struct Broker {
     pub description: Option<String>,
     pub category: Option<String>,
     pub connector: Option<String>,
+    /// Address accepting deletion requests by email.
+    pub privacy_email: Option<String>,
+    /// Mailing address for postal requests, one line per address line.
+    pub postal_address: Option<String>,
+    /// Page where the broker takes opt-out requests.
+    pub opt_out_url: Option<String>,
+    /// Ways the broker accepts requests: `web_form`, `email`, `postal`, `phone`.
+    pub request_methods: Vec<String>,
+    /// What the broker asks for to verify a request, e.g. `email_link`.
+    pub verification: Vec<String>,
+    /// Typical days until a request is processed.
+    pub processing_days: Option<i64>,
+    /// Days after which removed listings tend to reappear.
+    pub relisting_days: Option<i64>,
+    /// Jurisdictions whose privacy laws cover the broker, e.g. `US-CA`.
+    pub jurisdictions: Vec<String>,
+    /// Broker this one is a brand or subsidiary of.
+    pub parent_id: Option<String>,
+    /// Runs on the parent's database, so a removal there covers it too.
+    pub shared_database: bool,
+    /// Source of a scripted connector (connector `script`).
+    pub script: Option<String>,
+    /// JSON `WasmManifest` of a WebAssembly connector (connector `wasm`).
+    pub wasm_manifest: Option<String>,
+    /// Registry sources that defined the broker, highest priority first.
+    pub registry_source: Option<String>,
     pub registry_updated_at: Option<String>,
+    /// Set when the broker disappeared from the registry.
+    pub retired_at: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -36,4 +64,75 @@ pub struct DeletionRequest {
     pub external_ref: Option<String>,
     pub created_at: String,
     pub updated_at: String,
+    /// Request at the controlling broker whose removal this one rides on.
+    pub covered_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManualTask {
+    pub id: String,
+    pub broker_id: String,
+    /// Reference of the deletion submission this task belongs to.
+    pub external_ref: String,
+    pub step_key: String,
+    pub kind: String,
+    pub instructions: String,
+    pub url: Option<String>,
+    pub prefill_json: Option<String>,
+    pub status: String,
+    pub response: Option<String>,
+    pub completed_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailEvidence {
+    pub id: String,
+    pub message_id: String,
+    pub broker_id: String,
+    pub external_ref: Option<String>,
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+    pub classification: String,
+    pub followed_link: Option<String>,
+    pub raw_message: String,
+    pub received_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostalMailing {
+    pub id: String,
+    pub broker_id: String,
+    pub external_ref: String,
+    pub letter_path: String,
+    pub sent_on: String,
+    pub tracking_number: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletionWorkflow {
+    pub id: String,
+    pub broker_id: String,
+    /// Reference of the deletion submission the flow continues.
+    pub external_ref: String,
+    /// Name of the current step.
+    pub step: String,
+    /// `active`, `finished` or `failed`.
+    pub state: String,
+    /// Values collected by earlier steps, as a JSON object.
+    pub data_json: String,
+    /// When the current step was entered.
+    pub entered_at: String,
+    /// Not worth looking at the flow again before this time.
+    pub wake_at: Option<String>,
+    /// Failed runs of the current step.
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    /// Bumped on every write, so concurrent runs cannot both advance a flow.
+    pub revision: i64,
+    pub created_at: String,
+    pub updated_at: String,
 }
@@ -1,7 +1,10 @@
 use rusqlite::params;
 
 use super::Database;
-use super::models::{Broker, DeletionRequest, PersonalRecord};
+use super::models::{
+    Broker, DeletionRequest, DeletionWorkflow, EmailEvidence, ManualTask, PersonalRecord,
+    PostalMailing,
+};
 
 impl Database {
     // --- Brokers ---
@@ -9,16 +12,30 @@ impl Database {
     pub fn upsert_broker(&self, broker: &Broker) -> anyhow::Result<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO brokers (id, name, website, description, category, connector, registry_updated_at, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "INSERT INTO brokers (id, name, website, description, category, connector, privacy_email, postal_address, script, wasm_manifest, registry_source, registry_updated_at, retired_at, created_at, updated_at, opt_out_url, request_methods, verification, processing_days, relisting_days, jurisdictions, parent_id, shared_database)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)
              ON CONFLICT(id) DO UPDATE SET
                 name = excluded.name,
                 website = excluded.website,
                 description = excluded.description,
                 category = excluded.category,
                 connector = excluded.connector,
+                privacy_email = excluded.privacy_email,
+                postal_address = excluded.postal_address,
+                script = excluded.script,
+                wasm_manifest = excluded.wasm_manifest,
+                registry_source = excluded.registry_source,
                 registry_updated_at = excluded.registry_updated_at,
-                updated_at = excluded.updated_at",
+                retired_at = excluded.retired_at,
+                updated_at = excluded.updated_at,
+                opt_out_url = excluded.opt_out_url,
+                request_methods = excluded.request_methods,
+                verification = excluded.verification,
+                processing_days = excluded.processing_days,
+                relisting_days = excluded.relisting_days,
+                jurisdictions = excluded.jurisdictions,
+                parent_id = excluded.parent_id,
+                shared_database = excluded.shared_database",
             params![
                 broker.id,
                 broker.name,
@@ -26,9 +43,23 @@ impl Database {
                 broker.description,
                 broker.category,
                 broker.connector,
+                broker.privacy_email,
+                broker.postal_address,
+                broker.script,
+                broker.wasm_manifest,
+                broker.registry_source,
                 broker.registry_updated_at,
+                broker.retired_at,
                 broker.created_at,
                 broker.updated_at,
+                broker.opt_out_url,
+                serde_json::to_string(&broker.request_methods)?,
+                serde_json::to_string(&broker.verification)?,
+                broker.processing_days,
+                broker.relisting_days,
+                serde_json::to_string(&broker.jurisdictions)?,
+                broker.parent_id,
+                broker.shared_database,
             ],
         )?;
         Ok(())
@@ -37,22 +68,10 @@ impl Database {
     pub fn get_broker(&self, id: &str) -> anyhow::Result<Option<Broker>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, website, description, category, connector, registry_updated_at, created_at, updated_at
+            "SELECT id, name, website, description, category, connector, privacy_email, postal_address, script, wasm_manifest, registry_source, registry_updated_at, retired_at, created_at, updated_at, opt_out_url, request_methods, verification, processing_days, relisting_days, jurisdictions, parent_id, shared_database
              FROM brokers WHERE id = ?1",
         )?;
-        let mut rows = stmt.query_map(params![id], |row| {
-            Ok(Broker {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                website: row.get(2)?,
-                description: row.get(3)?,
-                category: row.get(4)?,
-                connector: row.get(5)?,
-                registry_updated_at: row.get(6)?,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            })
-        })?;
+        let mut rows = stmt.query_map(params![id], Self::map_broker_row)?;
         match rows.next() {
             Some(row) => Ok(Some(row?)),
             None => Ok(None),
@@ -65,43 +84,19 @@ impl Database {
 
         if let Some(cat) = category {
             let mut stmt = conn.prepare(
-                "SELECT id, name, website, description, category, connector, registry_updated_at, created_at, updated_at
-                 FROM brokers WHERE category = ?1 ORDER BY name",
+                "SELECT id, name, website, description, category, connector, privacy_email, postal_address, script, wasm_manifest, registry_source, registry_updated_at, retired_at, created_at, updated_at, opt_out_url, request_methods, verification, processing_days, relisting_days, jurisdictions, parent_id, shared_database
+                 FROM brokers WHERE category = ?1 AND retired_at IS NULL ORDER BY name",
             )?;
-            let rows = stmt.query_map(params![cat], |row| {
-                Ok(Broker {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    website: row.get(2)?,
-                    description: row.get(3)?,
-                    category: row.get(4)?,
-                    connector: row.get(5)?,
-                    registry_updated_at: row.get(6)?,
-                    created_at: row.get(7)?,
-                    updated_at: row.get(8)?,
-                })
-            })?;
+            let rows = stmt.query_map(params![cat], Self::map_broker_row)?;
             for row in rows {
                 brokers.push(row?);
             }
         } else {
             let mut stmt = conn.prepare(
-                "SELECT id, name, website, description, category, connector, registry_updated_at, created_at, updated_at
-                 FROM brokers ORDER BY name",
+                "SELECT id, name, website, description, category, connector, privacy_email, postal_address, script, wasm_manifest, registry_source, registry_updated_at, retired_at, created_at, updated_at, opt_out_url, request_methods, verification, processing_days, relisting_days, jurisdictions, parent_id, shared_database
+                 FROM brokers WHERE retired_at IS NULL ORDER BY name",
             )?;
-            let rows = stmt.query_map([], |row| {
-                Ok(Broker {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    website: row.get(2)?,
-                    description: row.get(3)?,
-                    category: row.get(4)?,
-                    connector: row.get(5)?,
-                    registry_updated_at: row.get(6)?,
-                    created_at: row.get(7)?,
-                    updated_at: row.get(8)?,
-                })
-            })?;
+            let rows = stmt.query_map([], Self::map_broker_row)?;
             for row in rows {
                 brokers.push(row?);
             }
@@ -110,6 +105,58 @@ impl Database {
         Ok(brokers)
     }
 
+    /// Brokers that came from the registry, retired ones included.
+    pub fn list_registry_brokers(&self) -> anyhow::Result<Vec<Broker>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, website, description, category, connector, privacy_email, postal_address, script, wasm_manifest, registry_source, registry_updated_at, retired_at, created_at, updated_at, opt_out_url, request_methods, verification, processing_days, relisting_days, jurisdictions, parent_id, shared_database
+             FROM brokers WHERE registry_updated_at IS NOT NULL ORDER BY name",
+        )?;
+        let rows = stmt.query_map([], Self::map_broker_row)?;
+        let mut brokers = Vec::new();
+        for row in rows {
+            brokers.push(row?);
+        }
+        Ok(brokers)
+    }
+
+    pub fn retire_broker(&self, id: &str, retired_at: &str) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE brokers SET retired_at = ?2, updated_at = ?2 WHERE id = ?1 AND retired_at IS NULL",
+            params![id, retired_at],
+        )?;
+        Ok(())
+    }
+
+    fn map_broker_row(row: &rusqlite::Row) -> rusqlite::Result<Broker> {
+        Ok(Broker {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            website: row.get(2)?,
+            description: row.get(3)?,
+            category: row.get(4)?,
+            connector: row.get(5)?,
+            privacy_email: row.get(6)?,
+            postal_address: row.get(7)?,
+            script: row.get(8)?,
+            wasm_manifest: row.get(9)?,
+            registry_source: row.get(10)?,
+            registry_updated_at: row.get(11)?,
+            retired_at: row.get(12)?,
+            created_at: row.get(13)?,
+            updated_at: row.get(14)?,
+            opt_out_url: row.get(15)?,
+            request_methods: json_list(row, 16)?,
+            verification: json_list(row, 17)?,
+            processing_days: row.get(18)?,
+            relisting_days: row.get(19)?,
+            jurisdictions: json_list(row, 20)?,
+            parent_id: row.get(21)?,
+            shared_database: row.get(22)?,
+        })
+    }
+
     // --- Personal Records ---
 
     pub fn upsert_personal_record(&self, record: &PersonalRecord) -> anyhow::Result<()> {
@@ -212,8 +259,8 @@ impl Database {
     pub fn insert_deletion_request(&self, req: &DeletionRequest) -> anyhow::Result<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO deletion_requests (id, broker_id, personal_record_id, status, submitted_at, completed_at, error_message, external_ref, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            "INSERT INTO deletion_requests (id, broker_id, personal_record_id, status, submitted_at, completed_at, error_message, external_ref, created_at, updated_at, covered_by)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
                 req.id,
                 req.broker_id,
@@ -225,6 +272,7 @@ impl Database {
                 req.external_ref,
                 req.created_at,
                 req.updated_at,
+                req.covered_by,
             ],
         )?;
         Ok(())
@@ -246,6 +294,19 @@ impl Database {
         Ok(())
     }
 
+    pub fn get_deletion_request(&self, id: &str) -> anyhow::Result<Option<DeletionRequest>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, broker_id, personal_record_id, status, submitted_at, completed_at, error_message, external_ref, created_at, updated_at, covered_by
+             FROM deletion_requests WHERE id = ?1",
+        )?;
+        let mut rows = stmt.query_map(params![id], Self::map_deletion_row)?;
+        match rows.next() {
+            Some(row) => Ok(Some(row?)),
+            None => Ok(None),
+        }
+    }
+
     pub fn list_deletion_requests(
         &self,
         broker_id: Option<&str>,
@@ -255,7 +316,7 @@ impl Database {
 
         if let Some(bid) = broker_id {
             let mut stmt = conn.prepare(
-                "SELECT id, broker_id, personal_record_id, status, submitted_at, completed_at, error_message, external_ref, created_at, updated_at
+                "SELECT id, broker_id, personal_record_id, status, submitted_at, completed_at, error_message, external_ref, created_at, updated_at, covered_by
                  FROM deletion_requests WHERE broker_id = ?1 ORDER BY created_at DESC",
             )?;
             let rows = stmt.query_map(params![bid], Self::map_deletion_row)?;
@@ -264,7 +325,7 @@ impl Database {
             }
         } else {
             let mut stmt = conn.prepare(
-                "SELECT id, broker_id, personal_record_id, status, submitted_at, completed_at, error_message, external_ref, created_at, updated_at
+                "SELECT id, broker_id, personal_record_id, status, submitted_at, completed_at, error_message, external_ref, created_at, updated_at, covered_by
                  FROM deletion_requests ORDER BY created_at DESC",
             )?;
             let rows = stmt.query_map([], Self::map_deletion_row)?;
@@ -276,6 +337,39 @@ impl Database {
         Ok(requests)
     }
 
+    /// Update every deletion request that shares a submission reference.
+    /// A submission covers several records, each tracked as its own request.
+    pub fn update_deletion_requests_by_ref(
+        &self,
+        broker_id: &str,
+        external_ref: &str,
+        status: &str,
+        new_external_ref: &str,
+        error_message: Option<&str>,
+        updated_at: &str,
+    ) -> anyhow::Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute(
+            "UPDATE deletion_requests SET
+                status = ?3,
+                external_ref = ?4,
+                error_message = ?5,
+                submitted_at = CASE WHEN ?3 = 'submitted' THEN COALESCE(submitted_at, ?6) ELSE submitted_at END,
+                completed_at = CASE WHEN ?3 IN ('completed', 'not_found') THEN COALESCE(completed_at, ?6) ELSE completed_at END,
+                updated_at = ?6
+             WHERE broker_id = ?1 AND external_ref = ?2",
+            params![
+                broker_id,
+                external_ref,
+                status,
+                new_external_ref,
+                error_message,
+                updated_at,
+            ],
+        )?;
+        Ok(n)
+    }
+
     fn map_deletion_row(row: &rusqlite::Row) -> rusqlite::Result<DeletionRequest> {
         Ok(DeletionRequest {
             id: row.get(0)?,
@@ -288,9 +382,413 @@ impl Database {
             external_ref: row.get(7)?,
             created_at: row.get(8)?,
             updated_at: row.get(9)?,
+            covered_by: row.get(10)?,
+        })
+    }
+
+    // --- Manual Tasks ---
+
+    pub fn insert_manual_task(&self, task: &ManualTask) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO manual_tasks (id, broker_id, external_ref, step_key, kind, instructions, url, prefill_json, status, response, completed_at, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                task.id,
+                task.broker_id,
+                task.external_ref,
+                task.step_key,
+                task.kind,
+                task.instructions,
+                task.url,
+                task.prefill_json,
+                task.status,
+                task.response,
+                task.completed_at,
+                task.created_at,
+                task.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn update_manual_task(&self, task: &ManualTask) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE manual_tasks SET external_ref = ?2, status = ?3, response = ?4, completed_at = ?5, updated_at = ?6
+             WHERE id = ?1",
+            params![
+                task.id,
+                task.external_ref,
+                task.status,
+                task.response,
+                task.completed_at,
+                task.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Find tasks whose ID starts with `prefix`, so users can type the short
+    /// IDs shown by `tasks list`.
+    pub fn find_manual_tasks(&self, prefix: &str) -> anyhow::Result<Vec<ManualTask>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, broker_id, external_ref, step_key, kind, instructions, url, prefill_json, status, response, completed_at, created_at, updated_at
+             FROM manual_tasks WHERE id LIKE ?1 || '%' ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map(params![prefix], Self::map_manual_task_row)?;
+        let mut tasks = Vec::new();
+        for row in rows {
+            tasks.push(row?);
+        }
+        Ok(tasks)
+    }
+
+    pub fn list_manual_tasks(&self, status: Option<&str>) -> anyhow::Result<Vec<ManualTask>> {
+        let conn = self.conn.lock().unwrap();
+        let mut tasks = Vec::new();
+
+        if let Some(st) = status {
+            let mut stmt = conn.prepare(
+                "SELECT id, broker_id, external_ref, step_key, kind, instructions, url, prefill_json, status, response, completed_at, created_at, updated_at
+                 FROM manual_tasks WHERE status = ?1 ORDER BY created_at",
+            )?;
+            let rows = stmt.query_map(params![st], Self::map_manual_task_row)?;
+            for row in rows {
+                tasks.push(row?);
+            }
+        } else {
+            let mut stmt = conn.prepare(
+                "SELECT id, broker_id, external_ref, step_key, kind, instructions, url, prefill_json, status, response, completed_at, created_at, updated_at
+                 FROM manual_tasks ORDER BY created_at",
+            )?;
+            let rows = stmt.query_map([], Self::map_manual_task_row)?;
+            for row in rows {
+                tasks.push(row?);
+            }
+        }
+
+        Ok(tasks)
+    }
+
+    pub fn list_manual_tasks_for_ref(
+        &self,
+        broker_id: &str,
+        external_ref: &str,
+    ) -> anyhow::Result<Vec<ManualTask>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, broker_id, external_ref, step_key, kind, instructions, url, prefill_json, status, response, completed_at, created_at, updated_at
+             FROM manual_tasks WHERE broker_id = ?1 AND external_ref = ?2 ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map(params![broker_id, external_ref], Self::map_manual_task_row)?;
+        let mut tasks = Vec::new();
+        for row in rows {
+            tasks.push(row?);
+        }
+        Ok(tasks)
+    }
+
+    pub fn count_open_manual_tasks(
+        &self,
+        broker_id: &str,
+        external_ref: &str,
+    ) -> anyhow::Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM manual_tasks
+             WHERE broker_id = ?1 AND external_ref = ?2 AND status = 'open'",
+            params![broker_id, external_ref],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Point all tasks of a submission at a new reference, e.g. once the
+    /// broker handed out a confirmation number.
+    pub fn rename_manual_task_ref(
+        &self,
+        broker_id: &str,
+        old_ref: &str,
+        new_ref: &str,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE manual_tasks SET external_ref = ?3 WHERE broker_id = ?1 AND external_ref = ?2",
+            params![broker_id, old_ref, new_ref],
+        )?;
+        Ok(())
+    }
+
+    fn map_manual_task_row(row: &rusqlite::Row) -> rusqlite::Result<ManualTask> {
+        Ok(ManualTask {
+            id: row.get(0)?,
+            broker_id: row.get(1)?,
+            external_ref: row.get(2)?,
+            step_key: row.get(3)?,
+            kind: row.get(4)?,
+            instructions: row.get(5)?,
+            url: row.get(6)?,
+            prefill_json: row.get(7)?,
+            status: row.get(8)?,
+            response: row.get(9)?,
+            completed_at: row.get(10)?,
+            created_at: row.get(11)?,
+            updated_at: row.get(12)?,
+        })
+    }
+
+    // --- Deletion Workflows ---
+
+    pub fn insert_deletion_workflow(&self, workflow: &DeletionWorkflow) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO deletion_workflows (id, broker_id, external_ref, step, state, data_json, entered_at, wake_at, attempts, last_error, revision, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                workflow.id,
+                workflow.broker_id,
+                workflow.external_ref,
+                workflow.step,
+                workflow.state,
+                workflow.data_json,
+                workflow.entered_at,
+                workflow.wake_at,
+                workflow.attempts,
+                workflow.last_error,
+                workflow.revision,
+                workflow.created_at,
+                workflow.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Write a workflow back, unless another run changed it since it was
+    /// read. Returns whether the write happened; the caller then holds the
+    /// next revision.
+    pub fn update_deletion_workflow(&self, workflow: &DeletionWorkflow) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute(
+            "UPDATE deletion_workflows SET
+                step = ?3, state = ?4, data_json = ?5, entered_at = ?6, wake_at = ?7,
+                attempts = ?8, last_error = ?9, updated_at = ?10, revision = revision + 1
+             WHERE id = ?1 AND revision = ?2",
+            params![
+                workflow.id,
+                workflow.revision,
+                workflow.step,
+                workflow.state,
+                workflow.data_json,
+                workflow.entered_at,
+                workflow.wake_at,
+                workflow.attempts,
+                workflow.last_error,
+                workflow.updated_at,
+            ],
+        )?;
+        Ok(n > 0)
+    }
+
+    pub fn get_deletion_workflow(
+        &self,
+        broker_id: &str,
+        external_ref: &str,
+    ) -> anyhow::Result<Option<DeletionWorkflow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, broker_id, external_ref, step, state, data_json, entered_at, wake_at, attempts, last_error, revision, created_at, updated_at
+             FROM deletion_workflows WHERE broker_id = ?1 AND external_ref = ?2",
+        )?;
+        let mut rows = stmt.query_map(
+            params![broker_id, external_ref],
+            Self::map_deletion_workflow_row,
+        )?;
+        match rows.next() {
+            Some(row) => Ok(Some(row?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn list_deletion_workflows(
+        &self,
+        state: Option<&str>,
+    ) -> anyhow::Result<Vec<DeletionWorkflow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, broker_id, external_ref, step, state, data_json, entered_at, wake_at, attempts, last_error, revision, created_at, updated_at
+             FROM deletion_workflows WHERE ?1 IS NULL OR state = ?1 ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map(params![state], Self::map_deletion_workflow_row)?;
+        let mut workflows = Vec::new();
+        for row in rows {
+            workflows.push(row?);
+        }
+        Ok(workflows)
+    }
+
+    pub fn rename_deletion_workflow_ref(
+        &self,
+        broker_id: &str,
+        old_ref: &str,
+        new_ref: &str,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE deletion_workflows SET external_ref = ?3, revision = revision + 1
+             WHERE broker_id = ?1 AND external_ref = ?2",
+            params![broker_id, old_ref, new_ref],
+        )?;
+        Ok(())
+    }
+
+    fn map_deletion_workflow_row(row: &rusqlite::Row) -> rusqlite::Result<DeletionWorkflow> {
+        Ok(DeletionWorkflow {
+            id: row.get(0)?,
+            broker_id: row.get(1)?,
+            external_ref: row.get(2)?,
+            step: row.get(3)?,
+            state: row.get(4)?,
+            data_json: row.get(5)?,
+            entered_at: row.get(6)?,
+            wake_at: row.get(7)?,
+            attempts: row.get(8)?,
+            last_error: row.get(9)?,
+            revision: row.get(10)?,
+            created_at: row.get(11)?,
+            updated_at: row.get(12)?,
         })
     }
 
+    // --- Email Evidence ---
+
+    /// Store a broker email. Returns `false` if it was already recorded,
+    /// which keeps repeated mailbox checks idempotent.
+    pub fn insert_email_evidence(&self, evidence: &EmailEvidence) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute(
+            "INSERT INTO email_evidence (id, message_id, broker_id, external_ref, sender, subject, classification, followed_link, raw_message, received_at, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT(message_id) DO NOTHING",
+            params![
+                evidence.id,
+                evidence.message_id,
+                evidence.broker_id,
+                evidence.external_ref,
+                evidence.sender,
+                evidence.subject,
+                evidence.classification,
+                evidence.followed_link,
+                evidence.raw_message,
+                evidence.received_at,
+                evidence.created_at,
+            ],
+        )?;
+        Ok(n > 0)
+    }
+
+    pub fn has_email_evidence(&self, message_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM email_evidence WHERE message_id = ?1",
+            params![message_id],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Whether a reply of `classification` about a request arrived at or
+    /// after `since`.
+    pub fn has_email_reply(
+        &self,
+        broker_id: &str,
+        external_ref: &str,
+        classification: &str,
+        since: &str,
+    ) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM email_evidence
+             WHERE broker_id = ?1 AND external_ref = ?2 AND classification = ?3 AND created_at >= ?4",
+            params![broker_id, external_ref, classification, since],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn list_email_evidence(&self, broker_id: &str) -> anyhow::Result<Vec<EmailEvidence>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, message_id, broker_id, external_ref, sender, subject, classification, followed_link, raw_message, received_at, created_at
+             FROM email_evidence WHERE broker_id = ?1 ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map(params![broker_id], |row| {
+            Ok(EmailEvidence {
+                id: row.get(0)?,
+                message_id: row.get(1)?,
+                broker_id: row.get(2)?,
+                external_ref: row.get(3)?,
+                sender: row.get(4)?,
+                subject: row.get(5)?,
+                classification: row.get(6)?,
+                followed_link: row.get(7)?,
+                raw_message: row.get(8)?,
+                received_at: row.get(9)?,
+                created_at: row.get(10)?,
+            })
+        })?;
+        let mut evidence = Vec::new();
+        for row in rows {
+            evidence.push(row?);
+        }
+        Ok(evidence)
+    }
+
+    // --- Postal Mailings ---
+
+    pub fn insert_postal_mailing(&self, mailing: &PostalMailing) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO postal_mailings (id, broker_id, external_ref, letter_path, sent_on, tracking_number, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                mailing.id,
+                mailing.broker_id,
+                mailing.external_ref,
+                mailing.letter_path,
+                mailing.sent_on,
+                mailing.tracking_number,
+                mailing.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_postal_mailings(&self) -> anyhow::Result<Vec<PostalMailing>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, broker_id, external_ref, letter_path, sent_on, tracking_number, created_at
+             FROM postal_mailings ORDER BY sent_on DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(PostalMailing {
+                id: row.get(0)?,
+                broker_id: row.get(1)?,
+                external_ref: row.get(2)?,
+                letter_path: row.get(3)?,
+                sent_on: row.get(4)?,
+                tracking_number: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?;
+        let mut mailings = Vec::new();
+        for row in rows {
+            mailings.push(row?);
+        }
+        Ok(mailings)
+    }
+
     // --- Registry Meta ---
 
     pub fn set_registry_meta(&self, key: &str, value: &str) -> anyhow::Result<()> {
@@ -312,4 +810,55 @@ impl Database {
             None => Ok(None),
         }
     }
+
+    // --- Broker sessions ---
+
+    /// The stored `(cookies, state)` JSON of a connector's session.
+    pub fn get_broker_session(&self, broker_id: &str) -> anyhow::Result<Option<(String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT cookies, state FROM broker_sessions WHERE broker_id = ?1")?;
+        let mut rows = stmt.query_map(params![broker_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        match rows.next() {
+            Some(row) => Ok(Some(row?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_broker_session(
+        &self,
+        broker_id: &str,
+        cookies: &str,
+        state: &str,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO broker_sessions (broker_id, cookies, state, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(broker_id) DO UPDATE SET
+                cookies = excluded.cookies,
+                state = excluded.state,
+                updated_at = excluded.updated_at",
+            params![broker_id, cookies, state, chrono::Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether there was a session to delete.
+    pub fn delete_broker_session(&self, broker_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let n = conn.execute(
+            "DELETE FROM broker_sessions WHERE broker_id = ?1",
+            params![broker_id],
+        )?;
+        Ok(n > 0)
+    }
+}
+
+/// A JSON array of strings stored in a TEXT column.
+fn json_list(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<Vec<String>> {
+    let text: String = row.get(idx)?;
+    serde_json::from_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
 }
@@ -29,4 +29,16 @@ pub enum AppError {
 
     #[error("Configuration error: {0}")]
     Config(String),
+
+    /// A connector needs a `PersonQuery` field the user did not give.
+    #[error("{broker} requires --{}", .field.replace('_', "-"))]
+    MissingField { broker: String, field: String },
+
+    /// The connector cannot perform this operation at all.
+    #[error("{0}")]
+    Unsupported(String),
+
+    /// A request outside the connector's declared domains.
+    #[error("{connector} may not access {url}")]
+    Blocked { connector: String, url: String },
 }
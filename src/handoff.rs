@@ -0,0 +1,295 @@
+//! Browser handoff for opt-outs that cannot be automated.
+//!
+//! A short-lived HTTP listener on 127.0.0.1 serves a page with the broker's
+//! opt-out link, instructions and the exact values to paste, plus a form to
+//! report the confirmation number back. The number can also be pasted into
+//! the terminal, whichever comes first.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, mpsc};
+
+use crate::report::html::escape_html;
+
+/// One instruction block shown on the handoff page.
+#[derive(Debug, Clone)]
+pub struct HandoffStep {
+    pub instructions: String,
+    pub url: Option<String>,
+    pub prefill: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HandoffPage {
+    pub broker_name: String,
+    pub steps: Vec<HandoffStep>,
+}
+
+impl HandoffPage {
+    /// The first URL among the steps, suggested when no browser can be launched.
+    fn optout_url(&self) -> Option<&str> {
+        self.steps.iter().find_map(|s| s.url.as_deref())
+    }
+}
+
+/// Run the handoff and wait for the user to report a confirmation number.
+///
+/// Returns `Ok(None)` when the timeout elapses. An empty confirmation means
+/// the broker did not hand out a number.
+pub async fn run(
+    page: &HandoffPage,
+    open_browser: bool,
+    timeout: Duration,
+) -> anyhow::Result<Option<String>> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let local_url = format!("http://{}/{token}", listener.local_addr()?);
+
+    print_instructions(page, &local_url);
+
+    if open_browser && let Err(e) = open_in_browser(&local_url) {
+        tracing::warn!("Could not open browser: {e}");
+        if let Some(url) = page.optout_url() {
+            println!("Open this page manually: {url}");
+        }
+    }
+
+    println!(
+        "\nEnter the confirmation number in the browser page, or paste it here and press Enter \
+         (leave empty if none was given):"
+    );
+    let mut pasted = stdin_lines().lock().await;
+    // Whatever was typed before this prompt is not an answer to it.
+    while pasted.try_recv().is_ok() {}
+
+    let served = serve(&listener, page, &token);
+    tokio::select! {
+        confirmation = served => confirmation.map(Some),
+        Some(line) = pasted.recv() => Ok(Some(line.trim().to_string())),
+        _ = tokio::time::sleep(timeout) => Ok(None),
+    }
+}
+
+fn print_instructions(page: &HandoffPage, local_url: &str) {
+    println!("Opt-out handoff for {}", page.broker_name);
+    println!("Handoff page: {local_url}");
+    for (i, step) in page.steps.iter().enumerate() {
+        println!("\n{}. {}", i + 1, step.instructions);
+        if let Some(url) = &step.url {
+            println!("   {url}");
+        }
+        for (label, value) in &step.prefill {
+            println!("   {label}: {value}");
+        }
+    }
+}
+
+/// Lines typed on stdin. A single plain thread reads them for every handoff
+/// of the process: an unanswered prompt leaves no reader behind to swallow
+/// the answer to the next one, and never keeps the runtime from shutting
+/// down.
+fn stdin_lines() -> &'static Mutex<mpsc::UnboundedReceiver<String>> {
+    static LINES: OnceLock<Mutex<mpsc::UnboundedReceiver<String>>> = OnceLock::new();
+    LINES.get_or_init(|| {
+        let (tx, rx) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Mutex::new(rx)
+    })
+}
+
+/// Serve the handoff page until the confirmation form is submitted.
+async fn serve(listener: &TcpListener, page: &HandoffPage, token: &str) -> anyhow::Result<String> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        match handle_connection(&mut stream, page, token).await {
+            Ok(Some(confirmation)) => return Ok(confirmation),
+            Ok(None) => {}
+            Err(e) => tracing::debug!("Handoff connection error: {e}"),
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: &mut TcpStream,
+    page: &HandoffPage,
+    token: &str,
+) -> anyhow::Result<Option<String>> {
+    let mut buf = vec![0u8; 8192];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let target = request
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("GET "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or("");
+
+    let url = reqwest::Url::parse(&format!("http://localhost{target}"))?;
+    let page_path = format!("/{token}");
+    let confirm_path = format!("/{token}/confirm");
+
+    if url.path() == page_path {
+        write_response(stream, "200 OK", &render_page(page, &confirm_path)).await?;
+        Ok(None)
+    } else if url.path() == confirm_path {
+        let confirmation = url
+            .query_pairs()
+            .find(|(k, _)| k == "ref")
+            .map(|(_, v)| v.trim().to_string())
+            .unwrap_or_default();
+        write_response(
+            stream,
+            "200 OK",
+            "<!DOCTYPE html><html><body><p>Thanks — data-breaker recorded your confirmation. \
+             You can close this tab.</p></body></html>",
+        )
+        .await?;
+        Ok(Some(confirmation))
+    } else {
+        write_response(stream, "404 Not Found", "Not found").await?;
+        Ok(None)
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> anyhow::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+fn render_page(page: &HandoffPage, confirm_path: &str) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"UTF-8\">\n");
+    html.push_str(&format!(
+        "<title>Opt out of {}</title>\n",
+        escape_html(&page.broker_name)
+    ));
+    html.push_str("<style>\n");
+    html.push_str("  body { font-family: system-ui, sans-serif; max-width: 720px; margin: 2rem auto; padding: 0 1rem; color: #1a1a1a; }\n");
+    html.push_str("  .value { font-family: monospace; background: #f5f5f5; padding: 0.2rem 0.4rem; border-radius: 4px; }\n");
+    html.push_str("  li { margin-bottom: 1rem; }\n");
+    html.push_str("</style>\n</head>\n<body>\n");
+    html.push_str(&format!(
+        "<h1>Opt out of {}</h1>\n<ol>\n",
+        escape_html(&page.broker_name)
+    ));
+
+    for step in &page.steps {
+        html.push_str(&format!("<li><p>{}</p>\n", escape_html(&step.instructions)));
+        if let Some(url) = &step.url {
+            html.push_str(&format!(
+                "<p><a href=\"{0}\" target=\"_blank\" rel=\"noopener noreferrer\">{0}</a></p>\n",
+                escape_html(url)
+            ));
+        }
+        if !step.prefill.is_empty() {
+            html.push_str("<ul>\n");
+            for (label, value) in &step.prefill {
+                html.push_str(&format!(
+                    "<li>{}: <span class=\"value\">{}</span></li>\n",
+                    escape_html(label),
+                    escape_html(value)
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+        html.push_str("</li>\n");
+    }
+
+    html.push_str("</ol>\n<h2>Done?</h2>\n");
+    html.push_str(&format!(
+        "<form method=\"get\" action=\"{}\">\n<label>Confirmation number (leave empty if none): <input name=\"ref\" autofocus></label>\n<button type=\"submit\">Record</button>\n</form>\n",
+        escape_html(confirm_path)
+    ));
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Open a URL with the platform's default handler.
+fn open_in_browser(url: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut cmd = std::process::Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut c = std::process::Command::new("cmd");
+        c.args(["/C", "start", ""]);
+        c
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut cmd = std::process::Command::new("xdg-open");
+
+    cmd.arg(url)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page() -> HandoffPage {
+        HandoffPage {
+            broker_name: "Example <Broker>".into(),
+            steps: vec![HandoffStep {
+                instructions: "Submit the form".into(),
+                url: Some("https://example.com/optout".into()),
+                prefill: vec![("Name".into(), "Jane Doe".into())],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_render_page_escapes_and_prefills() {
+        let html = render_page(&page(), "/tok/confirm");
+        assert!(html.contains("Example &lt;Broker&gt;"));
+        assert!(html.contains("Jane Doe"));
+        assert!(html.contains("https://example.com/optout"));
+        assert!(html.contains("action=\"/tok/confirm\""));
+    }
+
+    #[tokio::test]
+    async fn test_serve_captures_confirmation() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let page = page();
+        let server = serve(&listener, &page, "tok");
+
+        let client = async {
+            let c = reqwest::Client::new();
+            let wrong = c.get(format!("{base}/other")).send().await.unwrap();
+            assert_eq!(wrong.status(), 404);
+            let body = c
+                .get(format!("{base}/tok"))
+                .send()
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+            assert!(body.contains("Submit the form"));
+            c.get(format!("{base}/tok/confirm?ref=AB+12%2F3"))
+                .send()
+                .await
+                .unwrap();
+        };
+
+        let (confirmation, ()) = tokio::join!(server, client);
+        assert_eq!(confirmation.unwrap(), "AB 12/3");
+    }
+}
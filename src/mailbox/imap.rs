@@ -0,0 +1,229 @@
+//! Just enough IMAP4rev1 to read recent messages: LOGIN, EXAMINE,
+//! UID SEARCH SINCE and UID FETCH BODY.PEEK[]. The mailbox is opened
+//! read-only, so nothing is flagged or moved.
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::config::MailboxConfig;
+
+/// Fetch the raw messages received in the last `lookback_days` days.
+pub async fn fetch_recent(config: &MailboxConfig) -> anyhow::Result<Vec<Vec<u8>>> {
+    let host = config
+        .host
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("IMAP mailbox requires `host`"))?;
+    let port = config.port.unwrap_or(if config.tls { 993 } else { 143 });
+    let username = config
+        .username
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("IMAP mailbox requires `username`"))?;
+    let password = config.password().ok_or_else(|| {
+        anyhow::anyhow!("IMAP mailbox requires `password` or DATA_BREAKER_IMAP_PASSWORD")
+    })?;
+    let since =
+        chrono::Utc::now().date_naive() - chrono::Duration::days(i64::from(config.lookback_days));
+
+    let tcp = TcpStream::connect((host, port)).await?;
+    if config.tls {
+        let mut roots = tokio_rustls::rustls::RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let tls_config = tokio_rustls::rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(tls_config));
+        let server_name = tokio_rustls::rustls::pki_types::ServerName::try_from(host.to_string())?;
+        let stream = connector.connect(server_name, tcp).await?;
+        ImapSession::new(stream)
+            .fetch_since(username, &password, &config.folder, since)
+            .await
+    } else {
+        ImapSession::new(tcp)
+            .fetch_since(username, &password, &config.folder, since)
+            .await
+    }
+}
+
+struct ImapSession<S> {
+    stream: BufReader<S>,
+    next_tag: u32,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> ImapSession<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            next_tag: 1,
+        }
+    }
+
+    async fn fetch_since(
+        mut self,
+        username: &str,
+        password: &str,
+        folder: &str,
+        since: chrono::NaiveDate,
+    ) -> anyhow::Result<Vec<Vec<u8>>> {
+        let greeting = self.read_line().await?;
+        if !greeting.starts_with("* OK") && !greeting.starts_with("* PREAUTH") {
+            anyhow::bail!("Unexpected IMAP greeting: {}", greeting.trim_end());
+        }
+
+        self.command(&format!("LOGIN {} {}", quote(username), quote(password)))
+            .await?;
+        self.command(&format!("EXAMINE {}", quote(folder))).await?;
+
+        let search = self
+            .command(&format!("UID SEARCH SINCE {}", since.format("%d-%b-%Y")))
+            .await?;
+        let uids: Vec<&str> = search
+            .lines
+            .iter()
+            .filter_map(|l| l.strip_prefix("* SEARCH"))
+            .flat_map(|rest| rest.split_whitespace())
+            .collect();
+
+        let messages = if uids.is_empty() {
+            vec![]
+        } else {
+            let fetch = format!("UID FETCH {} BODY.PEEK[]", uids.join(","));
+            self.command(&fetch).await?.literals
+        };
+
+        // Best effort: the messages are already in hand.
+        let _ = self.command("LOGOUT").await;
+        Ok(messages)
+    }
+
+    /// Send a tagged command and collect untagged lines and literals until
+    /// the tagged completion, failing on NO/BAD.
+    async fn command(&mut self, command: &str) -> anyhow::Result<Response> {
+        let tag = format!("a{}", self.next_tag);
+        self.next_tag += 1;
+        self.stream
+            .get_mut()
+            .write_all(format!("{tag} {command}\r\n").as_bytes())
+            .await?;
+        self.stream.get_mut().flush().await?;
+
+        let mut response = Response::default();
+        loop {
+            let line = self.read_line().await?;
+            if let Some(status) = line.strip_prefix(&format!("{tag} ")) {
+                if status.starts_with("OK") {
+                    return Ok(response);
+                }
+                let verb = command.split_whitespace().next().unwrap_or(command);
+                anyhow::bail!("IMAP {verb} failed: {}", status.trim_end());
+            }
+            if let Some(len) = literal_len(&line) {
+                let mut literal = vec![0u8; len];
+                self.stream.read_exact(&mut literal).await?;
+                response.literals.push(literal);
+            }
+            response.lines.push(line);
+        }
+    }
+
+    async fn read_line(&mut self) -> anyhow::Result<String> {
+        let mut buf = Vec::new();
+        let n = self.stream.read_until(b'\n', &mut buf).await?;
+        if n == 0 {
+            anyhow::bail!("IMAP server closed the connection");
+        }
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+}
+
+#[derive(Default)]
+struct Response {
+    lines: Vec<String>,
+    literals: Vec<Vec<u8>>,
+}
+
+/// Length of a `{N}` literal announced at the end of a response line.
+fn literal_len(line: &str) -> Option<usize> {
+    let line = line.trim_end();
+    let open = line.rfind('{')?;
+    line.strip_suffix('}')?[open + 1..].parse().ok()
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MailboxKind;
+    use tokio::net::TcpListener;
+
+    /// A scripted IMAP stand-in serving a single message.
+    async fn fake_imap(listener: TcpListener, message: &'static str) -> Vec<String> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let mut seen = Vec::new();
+        write.write_all(b"* OK fake IMAP ready\r\n").await.unwrap();
+
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let (tag, cmd) = line.split_once(' ').unwrap();
+            seen.push(cmd.to_string());
+            let reply = if cmd.starts_with("UID SEARCH") {
+                format!("* SEARCH 7\r\n{tag} OK SEARCH completed\r\n")
+            } else if cmd.starts_with("UID FETCH") {
+                format!(
+                    "* 1 FETCH (UID 7 BODY[] {{{}}}\r\n{message})\r\n{tag} OK FETCH completed\r\n",
+                    message.len()
+                )
+            } else if cmd.starts_with("LOGOUT") {
+                format!("* BYE\r\n{tag} OK LOGOUT completed\r\n")
+            } else {
+                format!("{tag} OK done\r\n")
+            };
+            write.write_all(reply.as_bytes()).await.unwrap();
+            if cmd.starts_with("LOGOUT") {
+                break;
+            }
+        }
+        seen
+    }
+
+    #[tokio::test]
+    async fn test_fetch_recent_from_fake_server() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let message = "Subject: hello\r\nMessage-ID: <m1@example.com>\r\n\r\nbody\r\n";
+        let server = tokio::spawn(fake_imap(listener, message));
+
+        let config = MailboxConfig {
+            kind: MailboxKind::Imap,
+            path: None,
+            host: Some("127.0.0.1".into()),
+            port: Some(port),
+            tls: false,
+            username: Some("jane".into()),
+            password: Some("secret \"quoted\"".into()),
+            folder: "INBOX".into(),
+            lookback_days: 30,
+            allowed_link_domains: vec![],
+        };
+        let messages = fetch_recent(&config).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0], message.as_bytes());
+
+        let seen = server.await.unwrap();
+        assert_eq!(seen[0], r#"LOGIN "jane" "secret \"quoted\"""#);
+        assert_eq!(seen[1], r#"EXAMINE "INBOX""#);
+        assert!(seen[2].starts_with("UID SEARCH SINCE "));
+        assert_eq!(seen[3], "UID FETCH 7 BODY.PEEK[]");
+    }
+
+    #[test]
+    fn test_literal_len() {
+        assert_eq!(literal_len("* 1 FETCH (UID 7 BODY[] {42}\r\n"), Some(42));
+        assert_eq!(literal_len("* SEARCH 1 2\r\n"), None);
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+/// Read every message in a Maildir (`new/` and `cur/`). Messages are left
+/// in place; the mail client stays in charge of flags.
+pub fn read_maildir(path: &Path) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut messages = Vec::new();
+    for sub in ["new", "cur"] {
+        let dir = path.join(sub);
+        if !dir.is_dir() {
+            continue;
+        }
+        let mut entries: Vec<_> = std::fs::read_dir(&dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect();
+        entries.sort();
+        for entry in entries {
+            messages.push(std::fs::read(entry)?);
+        }
+    }
+    if messages.is_empty() && !path.join("cur").is_dir() && !path.join("new").is_dir() {
+        anyhow::bail!("{} is not a Maildir (no cur/ or new/)", path.display());
+    }
+    Ok(messages)
+}
+
+/// Split an mbox file into messages on `From ` separator lines, undoing
+/// the `>From ` quoting of body lines. Bytes are kept as they are: 8-bit
+/// bodies are decoded later by their own MIME headers.
+pub fn read_mbox(path: &Path) -> anyhow::Result<Vec<Vec<u8>>> {
+    let data = std::fs::read(path)?;
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+
+    for line in data.split_inclusive(|&b| b == b'\n') {
+        if line.starts_with(b"From ") {
+            if let Some(msg) = current.take() {
+                messages.push(msg);
+            }
+            current = Some(Vec::new());
+            continue;
+        }
+        if let Some(msg) = current.as_mut() {
+            let unquoted = line.iter().position(|&b| b != b'>').unwrap_or(line.len());
+            if unquoted > 0 && line[unquoted..].starts_with(b"From ") {
+                msg.extend_from_slice(&line[1..]);
+            } else {
+                msg.extend_from_slice(line);
+            }
+        }
+    }
+    messages.extend(current);
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_mbox_splits_and_unquotes() {
+        let path = std::env::temp_dir().join(format!("db-mbox-{}", uuid::Uuid::new_v4()));
+        // The second body is Latin-1, as 8-bit mail often is.
+        std::fs::write(
+            &path,
+            b"From a@example.com Mon Jan  1 00:00:00 2024\nSubject: one\n\n>From here\n\
+             From b@example.com Mon Jan  1 00:00:00 2024\nSubject: two\n\ncaf\xe9\n",
+        )
+        .unwrap();
+        let messages = read_mbox(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(messages.len(), 2);
+        let first = String::from_utf8(messages[0].clone()).unwrap();
+        assert!(first.contains("Subject: one"));
+        assert!(first.contains("\nFrom here"));
+        assert!(messages[1].ends_with(b"caf\xe9\n"));
+    }
+
+    #[test]
+    fn test_read_maildir() {
+        let root = std::env::temp_dir().join(format!("db-maildir-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("new")).unwrap();
+        std::fs::create_dir_all(root.join("cur")).unwrap();
+        std::fs::write(root.join("new").join("1.eml"), "Subject: hi\n\nbody\n").unwrap();
+        let messages = read_maildir(&root).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(messages.len(), 1);
+    }
+}
@@ -0,0 +1,713 @@
+//! Broker replies to deletion requests: fetch them from a mailbox, match
+//! them to `DeletionRequest`s, follow confirmation links and turn the
+//! outcome into a status transition. Matched emails are kept as evidence;
+//! unrelated mail is never stored.
+
+pub mod imap;
+pub mod maildir;
+
+use mail_parser::MessageParser;
+
+use crate::broker::http::HttpService;
+use crate::broker::{ManualStep, ManualStepKind};
+use crate::config::{MailboxConfig, MailboxKind};
+use crate::db::Database;
+use crate::db::models::{Broker, EmailEvidence};
+
+/// Statuses for which broker replies are still expected.
+const OPEN_STATUSES: &[&str] = &["awaiting_user", "submitted", "in_progress"];
+
+/// Keywords a link must contain before it is treated as a confirmation link.
+/// Anything else (unsubscribe, tracking, marketing) is never followed.
+const CONFIRM_LINK_HINTS: &[&str] = &[
+    "confirm",
+    "verify",
+    "verification",
+    "validate",
+    "optout",
+    "opt-out",
+    "opt_out",
+];
+
+/// Fetch raw messages from the configured mailbox.
+pub async fn fetch_messages(config: &MailboxConfig) -> anyhow::Result<Vec<Vec<u8>>> {
+    match config.kind {
+        MailboxKind::Imap => imap::fetch_recent(config).await,
+        MailboxKind::Maildir | MailboxKind::Mbox => {
+            let path = config
+                .path
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("Mailbox of this kind requires `path`"))?;
+            if config.kind == MailboxKind::Maildir {
+                maildir::read_maildir(path)
+            } else {
+                maildir::read_mbox(path)
+            }
+        }
+    }
+}
+
+/// What a broker's reply says about the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyKind {
+    ConfirmationRequired,
+    Acknowledged,
+    Completed,
+    NotFound,
+    Rejected,
+    Unknown,
+}
+
+impl ReplyKind {
+    pub const ALL: [ReplyKind; 6] = [
+        ReplyKind::ConfirmationRequired,
+        ReplyKind::Acknowledged,
+        ReplyKind::Completed,
+        ReplyKind::NotFound,
+        ReplyKind::Rejected,
+        ReplyKind::Unknown,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplyKind::ConfirmationRequired => "confirmation_required",
+            ReplyKind::Acknowledged => "acknowledged",
+            ReplyKind::Completed => "completed",
+            ReplyKind::NotFound => "not_found",
+            ReplyKind::Rejected => "rejected",
+            ReplyKind::Unknown => "unknown",
+        }
+    }
+}
+
+/// The parts of an email needed for matching and classification.
+#[derive(Debug, Clone)]
+pub struct InboundEmail {
+    pub message_id: String,
+    pub sender: Option<String>,
+    pub subject: Option<String>,
+    /// Message-IDs from In-Reply-To and References, without angle brackets.
+    pub thread_ids: Vec<String>,
+    pub text: String,
+    pub links: Vec<String>,
+    pub received_at: Option<String>,
+    pub raw: String,
+}
+
+pub fn parse_email(raw: &[u8]) -> Option<InboundEmail> {
+    let message = MessageParser::default().parse(raw)?;
+
+    let sender = message
+        .from()
+        .and_then(|a| a.first())
+        .and_then(|a| a.address())
+        .map(|a| a.to_ascii_lowercase());
+    let subject = message.subject().map(str::to_string);
+    let received_at = message.date().map(|d| d.to_rfc3339());
+
+    let mut thread_ids = Vec::new();
+    for value in [message.in_reply_to(), message.references()] {
+        if let Some(id) = value.as_text() {
+            thread_ids.push(normalize_message_id(id));
+        } else if let Some(ids) = value.as_text_list() {
+            thread_ids.extend(ids.iter().map(|id| normalize_message_id(id)));
+        }
+    }
+
+    let mut text = String::new();
+    if let Some(body) = message.body_text(0) {
+        text.push_str(&body);
+    }
+    let mut links = extract_links(&text);
+    if let Some(html) = message.body_html(0) {
+        for link in extract_links(&html) {
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+    }
+
+    let raw = String::from_utf8_lossy(raw).into_owned();
+    let message_id = match message.message_id() {
+        Some(id) => normalize_message_id(id),
+        // Without an ID, identify the email by its content so re-reads dedupe.
+        None => format!(
+            "no-id:{}:{}:{}",
+            sender.as_deref().unwrap_or(""),
+            received_at.as_deref().unwrap_or(""),
+            subject.as_deref().unwrap_or("")
+        ),
+    };
+
+    Some(InboundEmail {
+        message_id,
+        sender,
+        subject,
+        thread_ids,
+        text,
+        links,
+        received_at,
+        raw,
+    })
+}
+
+pub fn normalize_message_id(id: &str) -> String {
+    id.trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string()
+}
+
+/// Find http(s) links in text or HTML.
+fn extract_links(text: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("http") {
+        let candidate = &rest[start..];
+        if !(candidate.starts_with("https://") || candidate.starts_with("http://")) {
+            rest = &candidate[4..];
+            continue;
+        }
+        let end = candidate
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>' | ')' | ']'))
+            .unwrap_or(candidate.len());
+        let link = candidate[..end]
+            .trim_end_matches(['.', ',', ';', ':'])
+            .replace("&amp;", "&");
+        if !links.contains(&link) {
+            links.push(link);
+        }
+        rest = &candidate[end..];
+    }
+    links
+}
+
+/// Classify a broker reply by its wording. Order matters: a rejection or
+/// "not found" notice often also thanks the user for their request.
+pub fn classify(email: &InboundEmail) -> ReplyKind {
+    let haystack =
+        format!("{}\n{}", email.subject.as_deref().unwrap_or(""), email.text).to_lowercase();
+    let any = |phrases: &[&str]| phrases.iter().any(|p| haystack.contains(p));
+
+    if any(&[
+        "unable to verify your identity",
+        "could not verify your identity",
+        "cannot verify your identity",
+        "request has been denied",
+        "request was denied",
+        "request has been declined",
+        "unable to process your request",
+    ]) {
+        ReplyKind::Rejected
+    } else if any(&[
+        "could not find",
+        "couldn't find",
+        "unable to locate",
+        "did not find any",
+        "no records matching",
+        "no record of you",
+        "no information about you",
+    ]) {
+        ReplyKind::NotFound
+    } else if any(&[
+        "confirm your request",
+        "confirm your email",
+        "confirm your opt",
+        "verify your email",
+        "verify your request",
+        "click the link below to confirm",
+        "verification link",
+    ]) {
+        ReplyKind::ConfirmationRequired
+    } else if any(&[
+        "has been removed",
+        "have been removed",
+        "has been deleted",
+        "have been deleted",
+        "successfully opted out",
+        "opt-out is complete",
+        "opt out is complete",
+        "request has been completed",
+        "request is complete",
+    ]) {
+        ReplyKind::Completed
+    } else if any(&[
+        "received your request",
+        "request is being processed",
+        "we are processing your request",
+        "request has been received",
+    ]) {
+        ReplyKind::Acknowledged
+    } else {
+        ReplyKind::Unknown
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CheckSummary {
+    pub scanned: usize,
+    pub matched: usize,
+    pub updated: usize,
+    pub links_followed: usize,
+}
+
+/// Match, classify and apply a batch of raw messages. Messages already
+/// recorded as evidence are skipped, so this can run repeatedly.
+pub async fn process_messages(
+    db: &Database,
+    http: &HttpService,
+    raw_messages: &[Vec<u8>],
+    allowed_link_domains: &[String],
+) -> anyhow::Result<CheckSummary> {
+    let mut summary = CheckSummary::default();
+    let brokers = db.list_brokers(None)?;
+
+    for raw in raw_messages {
+        summary.scanned += 1;
+        let Some(email) = parse_email(raw) else {
+            continue;
+        };
+        if db.has_email_evidence(&email.message_id)? {
+            continue;
+        }
+        let Some((broker, external_ref)) = match_request(db, &brokers, &email)? else {
+            continue;
+        };
+        summary.matched += 1;
+
+        let kind = classify(&email);
+        let mut allowed = broker_domains(broker);
+        allowed.extend(allowed_link_domains.iter().map(|d| d.to_ascii_lowercase()));
+
+        let mut followed_link = None;
+        let transition = match kind {
+            ReplyKind::ConfirmationRequired => {
+                let candidates: Vec<&String> = email
+                    .links
+                    .iter()
+                    .filter(|l| is_confirmation_link(l))
+                    .collect();
+                let allowed_link = candidates.iter().find(|l| link_allowed(l, &allowed));
+                let followed = match allowed_link {
+                    Some(link) => match follow_link(http, link, allowed.clone()).await {
+                        Ok(()) => {
+                            summary.links_followed += 1;
+                            followed_link = Some(link.to_string());
+                            true
+                        }
+                        Err(e) => {
+                            tracing::warn!("Could not follow confirmation link: {e}");
+                            false
+                        }
+                    },
+                    None => false,
+                };
+                if followed {
+                    Some(("in_progress", None))
+                } else {
+                    // Never click links outside the broker's domains, and
+                    // do not drop one that failed: hand it to the user.
+                    crate::cli::tasks::record_manual_steps(
+                        db,
+                        &broker.id,
+                        &external_ref,
+                        &[ManualStep {
+                            key: format!("email-confirm:{}", email.message_id),
+                            kind: ManualStepKind::EmailLink,
+                            instructions: format!(
+                                "{} asked you to confirm your request by email (\"{}\")",
+                                broker.name,
+                                email.subject.as_deref().unwrap_or("no subject")
+                            ),
+                            url: allowed_link.or(candidates.first()).map(|l| l.to_string()),
+                            prefill: vec![],
+                        }],
+                    )?;
+                    Some(("awaiting_user", None))
+                }
+            }
+            ReplyKind::Acknowledged => Some(("in_progress", None)),
+            ReplyKind::Completed => Some(("completed", None)),
+            // "No record of you" does not confirm a deletion.
+            ReplyKind::NotFound => Some(("not_found", None)),
+            ReplyKind::Rejected => Some((
+                "rejected",
+                Some("Broker rejected the request (see email evidence)"),
+            )),
+            ReplyKind::Unknown => None,
+        };
+
+        let now = chrono::Utc::now().to_rfc3339();
+        db.insert_email_evidence(&EmailEvidence {
+            id: uuid::Uuid::new_v4().to_string(),
+            message_id: email.message_id.clone(),
+            broker_id: broker.id.clone(),
+            external_ref: Some(external_ref.clone()),
+            sender: email.sender.clone(),
+            subject: email.subject.clone(),
+            classification: kind.as_str().to_string(),
+            followed_link,
+            raw_message: email.raw.clone(),
+            received_at: email.received_at.clone(),
+            created_at: now.clone(),
+        })?;
+
+        if let Some((status, message)) = transition {
+            db.update_deletion_requests_by_ref(
+                &broker.id,
+                &external_ref,
+                status,
+                &external_ref,
+                message,
+                &now,
+            )?;
+            summary.updated += 1;
+            tracing::info!(
+                "{}: reply classified as {} -> {status}",
+                broker.id,
+                kind.as_str()
+            );
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Find the open deletion request an email is about: first by the thread
+/// headers (our Message-ID), then by the sender's domain.
+fn match_request<'a>(
+    db: &Database,
+    brokers: &'a [Broker],
+    email: &InboundEmail,
+) -> anyhow::Result<Option<(&'a Broker, String)>> {
+    let open: Vec<_> = db
+        .list_deletion_requests(None)?
+        .into_iter()
+        .filter(|r| OPEN_STATUSES.contains(&r.status.as_str()))
+        .collect();
+
+    for req in &open {
+        let Some(ext_ref) = &req.external_ref else {
+            continue;
+        };
+        if email.thread_ids.contains(&normalize_message_id(ext_ref))
+            && let Some(broker) = brokers.iter().find(|b| b.id == req.broker_id)
+        {
+            return Ok(Some((broker, ext_ref.clone())));
+        }
+    }
+
+    let Some(sender_domain) = email
+        .sender
+        .as_deref()
+        .and_then(|s| s.rsplit_once('@'))
+        .map(|(_, d)| d.to_string())
+    else {
+        return Ok(None);
+    };
+
+    for broker in brokers {
+        if !broker_domains(broker)
+            .iter()
+            .any(|d| domain_matches(&sender_domain, d))
+        {
+            continue;
+        }
+        // Requests are listed newest first.
+        if let Some(ext_ref) = open
+            .iter()
+            .filter(|r| r.broker_id == broker.id)
+            .find_map(|r| r.external_ref.clone())
+        {
+            return Ok(Some((broker, ext_ref)));
+        }
+    }
+    Ok(None)
+}
+
+/// Domains that belong to a broker: its website and privacy email domain.
+fn broker_domains(broker: &Broker) -> Vec<String> {
+    let mut domains = Vec::new();
+    if let Some(host) = broker
+        .website
+        .as_deref()
+        .and_then(|w| reqwest::Url::parse(w).ok())
+        .and_then(|u| u.host_str().map(str::to_string))
+    {
+        domains.push(host.trim_start_matches("www.").to_ascii_lowercase());
+    }
+    if let Some((_, domain)) = broker
+        .privacy_email
+        .as_deref()
+        .and_then(|e| e.rsplit_once('@'))
+    {
+        let domain = domain.to_ascii_lowercase();
+        if !domains.contains(&domain) {
+            domains.push(domain);
+        }
+    }
+    domains
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+fn is_confirmation_link(link: &str) -> bool {
+    let lower = link.to_lowercase();
+    CONFIRM_LINK_HINTS.iter().any(|h| lower.contains(h))
+}
+
+fn link_allowed(link: &str, allowed: &[String]) -> bool {
+    reqwest::Url::parse(link)
+        .ok()
+        .filter(|u| u.scheme() == "https")
+        .and_then(|u| u.host_str().map(str::to_string))
+        .is_some_and(|host| allowed.iter().any(|d| domain_matches(&host, d)))
+}
+
+/// GET a confirmation link, refusing redirects that leave the allowed domains.
+async fn follow_link(http: &HttpService, link: &str, allowed: Vec<String>) -> anyhow::Result<()> {
+    let policy = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= 5 {
+            attempt.error("too many redirects")
+        } else if link_allowed(attempt.url().as_str(), &allowed) {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    });
+    let client = http.builder().redirect(policy).build()?;
+    let resp = client.get(link).send().await?;
+    if !resp.status().is_success() {
+        anyhow::bail!("confirmation link returned HTTP {}", resp.status());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::DeletionRequest;
+
+    fn broker() -> Broker {
+        let now = chrono::Utc::now().to_rfc3339();
+        Broker {
+            id: "acme".into(),
+            name: "Acme Data".into(),
+            website: Some("https://www.acme.example".into()),
+            description: None,
+            category: None,
+            connector: Some("email".into()),
+            privacy_email: Some("privacy@acme-mail.example".into()),
+            postal_address: None,
+            script: None,
+            wasm_manifest: None,
+            registry_source: None,
+            registry_updated_at: None,
+            retired_at: None,
+            opt_out_url: None,
+            request_methods: vec![],
+            verification: vec![],
+            processing_days: None,
+            relisting_days: None,
+            jurisdictions: vec![],
+            parent_id: None,
+            shared_database: false,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    fn setup() -> Database {
+        let db = Database::open_in_memory().unwrap();
+        db.upsert_broker(&broker()).unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        db.insert_deletion_request(&DeletionRequest {
+            id: "del-1".into(),
+            broker_id: "acme".into(),
+            personal_record_id: None,
+            status: "submitted".into(),
+            submitted_at: Some(now.clone()),
+            completed_at: None,
+            error_message: None,
+            external_ref: Some("<req-1@example.com>".into()),
+            created_at: now.clone(),
+            updated_at: now,
+            covered_by: None,
+        })
+        .unwrap();
+        db
+    }
+
+    fn email(headers: &str, body: &str) -> Vec<u8> {
+        format!("{headers}\r\nContent-Type: text/plain\r\n\r\n{body}\r\n").into_bytes()
+    }
+
+    #[test]
+    fn test_classify_replies() {
+        let parse = |body: &str| parse_email(&email("Subject: Re: request", body)).unwrap();
+        assert_eq!(
+            classify(&parse("Your information has been removed.")),
+            ReplyKind::Completed
+        );
+        assert_eq!(
+            classify(&parse("Thanks, but we could not find any records.")),
+            ReplyKind::NotFound
+        );
+        assert_eq!(
+            classify(&parse("Please click the link below to confirm.")),
+            ReplyKind::ConfirmationRequired
+        );
+        assert_eq!(
+            classify(&parse("We were unable to verify your identity.")),
+            ReplyKind::Rejected
+        );
+        assert_eq!(classify(&parse("Hello there")), ReplyKind::Unknown);
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let links = extract_links(
+            "Go to https://acme.example/confirm?t=1&amp;u=2. Or <a href=\"http://x.example/a\">x</a>",
+        );
+        assert_eq!(
+            links,
+            vec!["https://acme.example/confirm?t=1&u=2", "http://x.example/a"]
+        );
+    }
+
+    #[test]
+    fn test_link_allowed_only_on_broker_domains() {
+        let allowed = broker_domains(&broker());
+        assert_eq!(allowed, vec!["acme.example", "acme-mail.example"]);
+        assert!(link_allowed(
+            "https://optout.acme.example/confirm",
+            &allowed
+        ));
+        assert!(!link_allowed("http://acme.example/confirm", &allowed));
+        assert!(!link_allowed(
+            "https://acme.example.evil.test/confirm",
+            &allowed
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reply_by_message_id_completes_request() {
+        let db = setup();
+        let raw = email(
+            "From: Privacy <noreply@unrelated.example>\r\nMessage-ID: <reply-1@unrelated.example>\r\nIn-Reply-To: <req-1@example.com>\r\nSubject: Your request",
+            "Your records have been deleted.",
+        );
+
+        let summary = process_messages(
+            &db,
+            &HttpService::default(),
+            std::slice::from_ref(&raw),
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.matched, 1);
+        assert_eq!(summary.updated, 1);
+
+        let req = &db.list_deletion_requests(None).unwrap()[0];
+        assert_eq!(req.status, "completed");
+        assert!(req.completed_at.is_some());
+        let evidence = db.list_email_evidence("acme").unwrap();
+        assert_eq!(evidence.len(), 1);
+        assert_eq!(evidence[0].classification, "completed");
+
+        // Reprocessing the same mailbox is a no-op.
+        let again = process_messages(&db, &HttpService::default(), &[raw], &[])
+            .await
+            .unwrap();
+        assert_eq!(again.matched, 0);
+    }
+
+    #[tokio::test]
+    async fn test_not_found_reply_is_not_completion() {
+        let db = setup();
+        let raw = email(
+            "From: privacy@acme-mail.example\r\nMessage-ID: <reply-3@acme-mail.example>\r\nIn-Reply-To: <req-1@example.com>\r\nSubject: Your request",
+            "We have no record of you in our systems.",
+        );
+
+        let summary = process_messages(&db, &HttpService::default(), &[raw], &[])
+            .await
+            .unwrap();
+        assert_eq!(summary.updated, 1);
+
+        let req = &db.list_deletion_requests(None).unwrap()[0];
+        assert_eq!(req.status, "not_found");
+        assert!(req.completed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_failed_confirmation_link_becomes_task() {
+        let db = setup();
+        // Nothing listens on a port that was just released.
+        let port = std::net::TcpListener::bind(("127.0.0.1", 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let link = format!("https://127.0.0.1:{port}/confirm?id=9");
+        let raw = email(
+            "From: privacy@acme-mail.example\r\nMessage-ID: <reply-4@acme-mail.example>\r\nSubject: Confirm",
+            &format!("Please confirm your request: {link}"),
+        );
+
+        let allowed = vec!["127.0.0.1".to_string()];
+        let summary = process_messages(&db, &HttpService::default(), &[raw], &allowed)
+            .await
+            .unwrap();
+        assert_eq!(summary.matched, 1);
+        assert_eq!(summary.links_followed, 0);
+
+        let req = &db.list_deletion_requests(None).unwrap()[0];
+        assert_eq!(req.status, "awaiting_user");
+        let tasks = db.list_manual_tasks(Some("open")).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].url.as_deref(), Some(link.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_unlisted_confirmation_link_becomes_task() {
+        let db = setup();
+        let raw = email(
+            "From: privacy@acme-mail.example\r\nMessage-ID: <reply-2@acme-mail.example>\r\nSubject: Confirm",
+            "Please confirm your request: https://tracker.example/confirm?id=9",
+        );
+
+        let summary = process_messages(&db, &HttpService::default(), &[raw], &[])
+            .await
+            .unwrap();
+        assert_eq!(summary.matched, 1);
+        assert_eq!(summary.links_followed, 0);
+
+        let req = &db.list_deletion_requests(None).unwrap()[0];
+        assert_eq!(req.status, "awaiting_user");
+        let tasks = db.list_manual_tasks(Some("open")).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(
+            tasks[0].url.as_deref(),
+            Some("https://tracker.example/confirm?id=9")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unrelated_mail_is_not_stored() {
+        let db = setup();
+        let raw = email(
+            "From: friend@mail.example\r\nMessage-ID: <hi@mail.example>\r\nSubject: Lunch?",
+            "Your records have been deleted, just kidding.",
+        );
+        let summary = process_messages(&db, &HttpService::default(), &[raw], &[])
+            .await
+            .unwrap();
+        assert_eq!(summary.matched, 0);
+        assert!(db.list_email_evidence("acme").unwrap().is_empty());
+    }
+}
@@ -3,10 +3,17 @@ mod cli;
 mod config;
 mod db;
 mod error;
+mod handoff;
+mod mailbox;
 mod report;
 
 use clap::Parser;
-use cli::{BrokerCommand, Cli, Command, RegistryCommand};
+use std::sync::Arc;
+
+use cli::{
+    BrokerCommand, Cli, Command, MailboxCommand, PostalCommand, RegistryCommand, SessionCommand,
+    TasksCommand,
+};
 
 use crate::broker::PersonQuery;
 
@@ -31,21 +38,57 @@ async fn main() -> anyhow::Result<()> {
 
     // Open database
     let db_path = config::db_path()?;
-    let db = db::Database::open(&db_path)?;
+    let db = Arc::new(db::Database::open(&db_path)?);
+
+    let config = config::Config::load()?;
+    cli::registry::seed_bundled(&db)?;
 
     // Build connector registry
-    let connectors = broker::build_connector_registry();
+    let http = broker::http::HttpService::new(&config.http)?.with_sessions(db.clone());
+    let scenario = broker::dummy::Scenario::from_config(
+        &config.dummy,
+        std::env::var(broker::dummy::SCENARIO_ENV).ok().as_deref(),
+    )?;
+    let mut connectors = broker::build_connector_registry(scenario, &http);
+    broker::plugin::add_plugin_connectors(&mut connectors, &config.plugins).await?;
+    broker::script::add_script_connectors(&mut connectors, &db, &config.scripts, &http).await?;
+    broker::wasm::add_wasm_connectors(&mut connectors, &db, &config.wasm, &http).await?;
+    broker::email::add_email_connectors(&mut connectors, &db, &config)?;
+    broker::postal::add_postal_connectors(&mut connectors, &db, &config)?;
 
     match cli.command {
         Command::Registry { command } => match command {
-            RegistryCommand::Update => cli::registry::update_registry(&db).await?,
-            RegistryCommand::Info => cli::registry::registry_info(&db)?,
+            RegistryCommand::Update {
+                from_file,
+                trust_unsigned,
+            } => {
+                cli::registry::update_registry(
+                    &db,
+                    &http,
+                    &config.registry,
+                    from_file.as_deref(),
+                    trust_unsigned,
+                )
+                .await?
+            }
+            RegistryCommand::Info => cli::registry::registry_info(&db, &config.registry)?,
+            RegistryCommand::Sources => cli::registry::registry_sources(&db, &config.registry)?,
+            RegistryCommand::Validate { file } => cli::registry::validate_registry(&file)?,
+            RegistryCommand::ImportState {
+                state,
+                file,
+                dry_run,
+                output,
+            } => cli::registry::import_state(&db, &state, &file, dry_run, output.as_deref())?,
         },
         Command::Broker { command } => match command {
             BrokerCommand::List { category } => {
                 cli::broker::list_brokers(&db, category.as_deref())?
             }
-            BrokerCommand::Info { id } => cli::broker::broker_info(&db, &id)?,
+            BrokerCommand::Info { id } => cli::broker::broker_info(&db, &connectors, &id)?,
+            BrokerCommand::Session {
+                command: SessionCommand::Clear { id },
+            } => cli::broker::clear_session(&db, &id)?,
         },
         Command::Scan {
             first_name,
@@ -70,15 +113,22 @@ async fn main() -> anyhow::Result<()> {
             all,
             broker: broker_id,
             record,
+            handoff,
+            first_name,
+            last_name,
+            email,
+            phone,
+            city,
+            state,
         } => {
-            // Build a minimal query for deletion — connectors may need it
+            // Connectors fall back to the query where scan records say nothing
             let query = PersonQuery {
-                first_name: String::new(),
-                last_name: String::new(),
-                email: None,
-                phone: None,
-                city: None,
-                state: None,
+                first_name: first_name.unwrap_or_default(),
+                last_name: last_name.unwrap_or_default(),
+                email,
+                phone,
+                city,
+                state,
             };
             cli::delete::delete(
                 &db,
@@ -87,12 +137,73 @@ async fn main() -> anyhow::Result<()> {
                 all,
                 broker_id.as_deref(),
                 record.as_deref(),
+                handoff,
             )
             .await?;
         }
         Command::Status { broker, filter } => {
             cli::status::status(&db, &connectors, broker.as_deref(), filter.as_deref()).await?;
         }
+        Command::Tasks { command } => match command {
+            TasksCommand::List { all, broker } => {
+                cli::tasks::list_tasks(&db, all, broker.as_deref())?
+            }
+            TasksCommand::Show { id } => cli::tasks::show_task(&db, &id)?,
+            TasksCommand::Done { id, value } => {
+                cli::tasks::complete_task(&db, &connectors, &id, value.as_deref()).await?
+            }
+            TasksCommand::Skip { id } => cli::tasks::skip_task(&db, &connectors, &id).await?,
+            TasksCommand::Handoff {
+                id,
+                no_browser,
+                timeout,
+            } => {
+                cli::tasks::handoff_task(
+                    &db,
+                    &connectors,
+                    &id,
+                    !no_browser,
+                    std::time::Duration::from_secs(timeout),
+                )
+                .await?
+            }
+        },
+        Command::Mailbox { command } => match command {
+            MailboxCommand::Check {
+                maildir,
+                mbox,
+                watch,
+            } => {
+                cli::mailbox::check_mailbox(
+                    &db,
+                    &config,
+                    &http,
+                    &connectors,
+                    maildir.as_deref(),
+                    mbox.as_deref(),
+                    watch,
+                )
+                .await?
+            }
+        },
+        Command::Postal { command } => match command {
+            PostalCommand::List => cli::postal::list_letters(&db)?,
+            PostalCommand::Log {
+                task,
+                sent_on,
+                tracking,
+            } => {
+                cli::postal::log_mailing(
+                    &db,
+                    &connectors,
+                    &task,
+                    sent_on.as_deref(),
+                    tracking.as_deref(),
+                )
+                .await?
+            }
+        },
+        Command::Coverage { list } => cli::coverage::coverage(&db, &connectors, list)?,
         Command::Report { format, output } => {
             cli::report::generate_report(&db, &format, output.as_deref())?;
         }
@@ -30,6 +30,11 @@ pub fn render(report: &Report) -> anyhow::Result<String> {
     html.push_str("<div class=\"summary\">\n");
     write_stat(&mut html, "Brokers Tracked", report.summary.total_brokers);
     write_stat(&mut html, "Records Found", report.summary.total_records);
+    write_stat(
+        &mut html,
+        "Awaiting You",
+        report.summary.deletions_awaiting_user,
+    );
     write_stat(
         &mut html,
         "Deletions Submitted",
@@ -40,6 +45,11 @@ pub fn render(report: &Report) -> anyhow::Result<String> {
         "Deletions Completed",
         report.summary.deletions_completed,
     );
+    write_stat(
+        &mut html,
+        "No Record Held",
+        report.summary.deletions_not_found,
+    );
     write_stat(
         &mut html,
         "Deletions Failed",
@@ -90,7 +100,7 @@ fn write_stat(html: &mut String, label: &str, value: usize) {
     ));
 }
 
-fn escape_html(s: &str) -> String {
+pub fn escape_html(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -28,8 +28,11 @@ pub struct ReportSummary {
     pub total_records: usize,
     pub total_deletions: usize,
     pub deletions_pending: usize,
+    pub deletions_awaiting_user: usize,
     pub deletions_submitted: usize,
     pub deletions_completed: usize,
+    /// The broker had no record to delete.
+    pub deletions_not_found: usize,
     pub deletions_failed: usize,
 }
 
@@ -47,6 +50,10 @@ impl Report {
                 .iter()
                 .filter(|r| r.status == "pending")
                 .count(),
+            deletions_awaiting_user: deletion_requests
+                .iter()
+                .filter(|r| r.status == "awaiting_user")
+                .count(),
             deletions_submitted: deletion_requests
                 .iter()
                 .filter(|r| r.status == "submitted")
@@ -55,6 +62,10 @@ impl Report {
                 .iter()
                 .filter(|r| r.status == "completed")
                 .count(),
+            deletions_not_found: deletion_requests
+                .iter()
+                .filter(|r| r.status == "not_found")
+                .count(),
             deletions_failed: deletion_requests
                 .iter()
                 .filter(|r| r.status == "failed" || r.status == "rejected")
@@ -28,6 +28,10 @@ pub fn render(report: &Report) -> anyhow::Result<String> {
         "  Pending:            {}\n",
         report.summary.deletions_pending
     ));
+    output.push_str(&format!(
+        "  Awaiting you:       {}\n",
+        report.summary.deletions_awaiting_user
+    ));
     output.push_str(&format!(
         "  Submitted:          {}\n",
         report.summary.deletions_submitted
@@ -36,6 +40,10 @@ pub fn render(report: &Report) -> anyhow::Result<String> {
         "  Completed:          {}\n",
         report.summary.deletions_completed
     ));
+    output.push_str(&format!(
+        "  No record held:     {}\n",
+        report.summary.deletions_not_found
+    ));
     output.push_str(&format!(
         "  Failed/Rejected:    {}\n",
         report.summary.deletions_failed
@@ -0,0 +1,232 @@
+//! End-to-end tests: the real `data-breaker` binary, with a Rhai connector,
+//! against the mock site in `mock_site`.
+
+mod mock_site;
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tokio::process::Command;
+
+struct MockBroker {
+    base: String,
+}
+
+impl MockBroker {
+    async fn start(args: &[&str]) -> Self {
+        let args = std::iter::once("mock-broker").chain(args.iter().copied());
+        let args = <mock_site::Args as clap::Parser>::try_parse_from(args).unwrap();
+        Self {
+            base: mock_site::spawn(args).await.unwrap(),
+        }
+    }
+
+    async fn confirmation_link(&self) -> String {
+        let outbox: serde_json::Value = reqwest::get(format!("{}/_mock/outbox", self.base))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        outbox[0]["link"].as_str().unwrap().to_string()
+    }
+}
+
+/// Isolated config and data directories with the mock connector installed.
+struct Home {
+    dir: PathBuf,
+}
+
+impl Home {
+    fn new(mock: &MockBroker, config: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("db-e2e-{}", uuid::Uuid::new_v4()));
+        let config_dir = dir.join("config/data-breaker");
+        std::fs::create_dir_all(config_dir.join("scripts")).unwrap();
+        let script = include_str!("fixtures/mock-broker.rhai").replace("__BASE_URL__", &mock.base);
+        std::fs::write(config_dir.join("scripts/mock.rhai"), script).unwrap();
+        // The mock listens on localhost, which no registry entry grants.
+        let config = format!("{config}\n[scripts.domains]\nmock = [\"127.0.0.1\"]\n");
+        std::fs::write(config_dir.join("config.toml"), config).unwrap();
+        Self { dir }
+    }
+
+    async fn run(&self, args: &[&str]) -> String {
+        let output = self.output(args).await;
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        assert!(
+            output.status.success(),
+            "data-breaker {args:?} failed:\n{stdout}\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        stdout
+    }
+
+    /// Run a command that must fail, returning its stderr.
+    async fn run_failing(&self, args: &[&str]) -> String {
+        let output = self.output(args).await;
+        assert!(!output.status.success(), "data-breaker {args:?} succeeded");
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    }
+
+    async fn output(&self, args: &[&str]) -> std::process::Output {
+        Command::new(env!("CARGO_BIN_EXE_data-breaker"))
+            .args(args)
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .env("XDG_DATA_HOME", self.dir.join("data"))
+            .env_remove("RUST_LOG")
+            .output()
+            .await
+            .unwrap()
+    }
+
+    async fn scan(&self) -> String {
+        self.run(&[
+            "scan",
+            "--first-name",
+            "Jane",
+            "--last-name",
+            "Doe",
+            "--state",
+            "NY",
+            "--brokers",
+            "mock",
+        ])
+        .await
+    }
+
+    fn db(&self) -> rusqlite::Connection {
+        rusqlite::Connection::open(db_path(&self.dir)).unwrap()
+    }
+
+    fn open_task(&self) -> (String, String) {
+        self.db()
+            .query_row(
+                "SELECT id, kind FROM manual_tasks WHERE status = 'open'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap()
+    }
+
+    fn request_status(&self) -> String {
+        self.db()
+            .query_row("SELECT DISTINCT status FROM deletion_requests", [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+}
+
+fn db_path(dir: &Path) -> PathBuf {
+    dir.join("data/data-breaker/data-breaker.db")
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[tokio::test]
+async fn test_scan_delete_confirm_and_complete() {
+    let mock = MockBroker::start(&[]).await;
+    let home = Home::new(&mock, "");
+
+    let out = home.scan().await;
+    assert!(out.contains("Found 3 record(s)"), "{out}");
+    assert!(out.contains("Albany, NY"), "{out}");
+
+    let out = home.run(&["delete", "--broker", "mock"]).await;
+    assert!(out.contains("1 manual step(s) required"), "{out}");
+    assert_eq!(home.request_status(), "awaiting_user");
+
+    let (task, kind) = home.open_task();
+    assert_eq!(kind, "email_link");
+    // A resume that fails leaves the task open to retry.
+    let err = home.run_failing(&["tasks", "done", &task]).await;
+    assert!(err.contains("confirmation link"), "{err}");
+    assert_eq!(home.open_task().0, task);
+    assert_eq!(home.request_status(), "awaiting_user");
+
+    let link = mock.confirmation_link().await;
+    let out = home.run(&["tasks", "done", &task, "--value", &link]).await;
+    assert!(out.contains("is now submitted"), "{out}");
+    assert!(out.contains("Email confirmed"), "{out}");
+    let error: Option<String> = home
+        .db()
+        .query_row("SELECT error_message FROM deletion_requests", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(error, None);
+
+    let out = home.run(&["status"]).await;
+    assert!(out.contains("completed"), "{out}");
+    assert!(out.contains("MB-0001"), "{out}");
+    assert_eq!(home.request_status(), "completed");
+}
+
+#[tokio::test]
+async fn test_unconfirmed_request_stays_in_progress() {
+    let mock = MockBroker::start(&["--removal-secs", "3600"]).await;
+    let home = Home::new(&mock, "");
+    home.scan().await;
+    home.run(&["delete", "--broker", "mock"]).await;
+    let (task, _) = home.open_task();
+    let link = mock.confirmation_link().await;
+    home.run(&["tasks", "done", &task, "--value", &link]).await;
+
+    home.run(&["status"]).await;
+    assert_eq!(home.request_status(), "in_progress");
+}
+
+#[tokio::test]
+async fn test_captcha_becomes_a_manual_task() {
+    let mock = MockBroker::start(&["--fail", "captcha"]).await;
+    let home = Home::new(&mock, "");
+    home.scan().await;
+
+    let out = home.run(&["delete", "--broker", "mock"]).await;
+    assert!(out.contains("manual step(s) required"), "{out}");
+    let (_, kind) = home.open_task();
+    assert_eq!(kind, "solve_captcha");
+}
+
+#[tokio::test]
+async fn test_rate_limit_is_reported() {
+    let mock = MockBroker::start(&["--fail", "rate-limit"]).await;
+    let home = Home::new(&mock, "");
+    let out = home.scan().await;
+    assert!(out.contains("search failed with HTTP 429"), "{out}");
+}
+
+#[tokio::test]
+async fn test_server_error_fails_the_deletion() {
+    let mock = MockBroker::start(&["--fail", "server-error"]).await;
+    let home = Home::new(&mock, "");
+    let out = home.scan().await;
+    assert!(out.contains("search failed with HTTP 500"), "{out}");
+
+    // Records from a healthy scan are needed to reach the opt-out form.
+    let healthy = MockBroker::start(&[]).await;
+    let script = include_str!("fixtures/mock-broker.rhai");
+    let scripts = home.dir.join("config/data-breaker/scripts/mock.rhai");
+    std::fs::write(&scripts, script.replace("__BASE_URL__", &healthy.base)).unwrap();
+    home.scan().await;
+    std::fs::write(&scripts, script.replace("__BASE_URL__", &mock.base)).unwrap();
+
+    let out = home.run(&["delete", "--broker", "mock"]).await;
+    assert!(out.contains("opt-out form failed with HTTP 500"), "{out}");
+    assert!(out.contains("3 failed"), "{out}");
+}
+
+#[tokio::test]
+async fn test_slow_site_hits_the_script_time_limit() {
+    let mock = MockBroker::start(&["--delay-ms", "5000"]).await;
+    let home = Home::new(&mock, "[scripts]\ntimeout_secs = 1\n");
+
+    let started = Instant::now();
+    let out = home.scan().await;
+    assert!(out.contains("Error:"), "{out}");
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
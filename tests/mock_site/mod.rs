@@ -0,0 +1,320 @@
+//! A fake people-search site for end-to-end tests, also runnable as a demo
+//! with `cargo run --example mock-broker`. It lives outside `src/` so that
+//! installing data-breaker never installs it.
+//!
+//! Endpoints:
+//!
+//! | request                         | response                                  |
+//! |---------------------------------|-------------------------------------------|
+//! | `GET /search?first_name&last_name&state` | JSON `{"results": [...]}`        |
+//! | `GET /people/<id>`              | profile page                              |
+//! | `GET /optout`                   | opt-out form with a hidden `token`        |
+//! | `POST /optout` (`profile_id`, `email`, `token`) | page with the request ref, and a confirmation "email" |
+//! | `GET /confirm?ref&code`         | confirms the request                      |
+//! | `GET /status?ref`               | JSON `{"ref", "status", "completed_at"}`  |
+//! | `GET /_mock/outbox`             | JSON list of confirmation emails sent     |
+//!
+//! `--fail` makes the site misbehave: `captcha` puts a CAPTCHA on the
+//! opt-out form and rejects submissions, `rate-limit` answers 429 and
+//! `server-error` answers 500 everywhere but `/_mock/`. `--delay-ms` slows
+//! every response down.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clap::{Parser, ValueEnum};
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Failure {
+    Captcha,
+    RateLimit,
+    ServerError,
+}
+
+#[derive(Debug, Parser)]
+#[command(name = "mock-broker", about = "Fake data broker site for testing")]
+pub struct Args {
+    /// Port to listen on (0 picks a free one)
+    #[arg(long, default_value_t = 0)]
+    port: u16,
+    /// Make the site misbehave
+    #[arg(long, value_enum)]
+    fail: Option<Failure>,
+    /// Delay before every response, in milliseconds
+    #[arg(long, default_value_t = 0)]
+    delay_ms: u64,
+    /// Seconds after confirmation before a request shows as removed
+    #[arg(long, default_value_t = 0)]
+    removal_secs: u64,
+}
+
+struct OptOut {
+    profile_id: String,
+    code: String,
+    confirmed_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct State {
+    next_ref: u32,
+    requests: HashMap<String, OptOut>,
+    outbox: Vec<serde_json::Value>,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    form: HashMap<String, String>,
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, value: serde_json::Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: value.to_string(),
+        }
+    }
+
+    fn html(status: u16, body: String) -> Self {
+        Self {
+            status,
+            content_type: "text/html; charset=utf-8",
+            body: format!("<!DOCTYPE html><html><body>{body}</body></html>"),
+        }
+    }
+}
+
+const FORM_TOKEN: &str = "mock-token-7f3a";
+
+/// Start the site in the background of the current runtime and return its
+/// base URL. It runs until the runtime shuts down.
+pub async fn spawn(args: Args) -> anyhow::Result<String> {
+    let args = Arc::new(args);
+    let listener = TcpListener::bind(("127.0.0.1", args.port)).await?;
+    let base = format!("http://{}", listener.local_addr()?);
+
+    let state = Arc::new(Mutex::new(State::default()));
+    let site = base.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (args, state, base) = (args.clone(), state.clone(), site.clone());
+            tokio::spawn(async move {
+                if let Err(e) = serve(stream, &args, &state, &base).await {
+                    eprintln!("mock-broker: {e}");
+                }
+            });
+        }
+    });
+    Ok(base)
+}
+
+async fn serve(
+    stream: TcpStream,
+    args: &Args,
+    state: &Mutex<State>,
+    base: &str,
+) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let request = read_request(&mut reader).await?;
+    if args.delay_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(args.delay_ms)).await;
+    }
+    let response = handle(&request, args, state, base);
+    let reason = match response.status {
+        200 => "OK",
+        403 => "Forbidden",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        _ => "Internal Server Error",
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    if response.status == 429 {
+        head.push_str("Retry-After: 1\r\n");
+    }
+    let stream = reader.get_mut();
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(b"\r\n").await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    Ok(())
+}
+
+async fn read_request(reader: &mut BufReader<TcpStream>) -> anyhow::Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or("/").to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse()?;
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    let url = reqwest::Url::parse(&format!("http://mock{target}"))?;
+    let form_url = reqwest::Url::parse(&format!("http://mock/?{}", String::from_utf8(body)?))?;
+    Ok(Request {
+        method,
+        path: url.path().to_string(),
+        query: url.query_pairs().into_owned().collect(),
+        form: form_url.query_pairs().into_owned().collect(),
+    })
+}
+
+fn handle(req: &Request, args: &Args, state: &Mutex<State>, base: &str) -> Response {
+    let mut state = state.lock().unwrap();
+    if req.path == "/_mock/outbox" {
+        return Response::json(200, json!(state.outbox));
+    }
+    match args.fail {
+        Some(Failure::RateLimit) => {
+            return Response::json(429, json!({ "error": "rate limited" }));
+        }
+        Some(Failure::ServerError) => {
+            return Response::html(500, "<h1>Internal Server Error</h1>".into());
+        }
+        _ => {}
+    }
+    let captcha = args.fail == Some(Failure::Captcha);
+    let param = |name: &str| req.query.get(name).map(String::as_str).unwrap_or_default();
+
+    match (req.method.as_str(), req.path.as_str()) {
+        ("GET", "/search") => {
+            let (first, last) = (param("first_name"), param("last_name"));
+            if first.is_empty() || last.is_empty() {
+                return Response::json(200, json!({ "results": [] }));
+            }
+            let id = format!("{}-{}-1", first.to_lowercase(), last.to_lowercase());
+            let state_code = Some(param("state"))
+                .filter(|s| !s.is_empty())
+                .unwrap_or("NY");
+            Response::json(
+                200,
+                json!({ "results": [{
+                    "id": id,
+                    "name": format!("{first} {last}"),
+                    "age": 41,
+                    "city": "Albany",
+                    "state": state_code,
+                }] }),
+            )
+        }
+        ("GET", path) if path.starts_with("/people/") => Response::html(
+            200,
+            format!("<h1 class=\"profile\">{}</h1>", &path["/people/".len()..]),
+        ),
+        ("GET", "/optout") => {
+            let widget = if captcha {
+                "<div class=\"g-recaptcha\" data-sitekey=\"mock\"></div>"
+            } else {
+                ""
+            };
+            Response::html(
+                200,
+                format!(
+                    "<form method=\"post\" action=\"/optout\">\
+                     <input type=\"hidden\" name=\"token\" value=\"{FORM_TOKEN}\">\
+                     <input name=\"profile_id\"><input name=\"email\">{widget}\
+                     <button>Remove my record</button></form>"
+                ),
+            )
+        }
+        ("POST", "/optout") => {
+            if captcha {
+                return Response::html(
+                    403,
+                    "<p class=\"error\">Please solve the CAPTCHA</p>".into(),
+                );
+            }
+            let field = |name: &str| req.form.get(name).cloned().unwrap_or_default();
+            if field("token") != FORM_TOKEN {
+                return Response::html(403, "<p class=\"error\">Invalid form token</p>".into());
+            }
+            let profile_id = field("profile_id");
+            if profile_id.is_empty() {
+                return Response::html(200, "<p class=\"error\">Select a listing</p>".into());
+            }
+            state.next_ref += 1;
+            let reference = format!("MB-{:04}", state.next_ref);
+            let code = format!("{:08x}", state.next_ref.wrapping_mul(2_654_435_761));
+            state.outbox.push(json!({
+                "to": field("email"),
+                "subject": "Confirm your opt-out request",
+                "link": format!("{base}/confirm?ref={reference}&code={code}"),
+            }));
+            state.requests.insert(
+                reference.clone(),
+                OptOut {
+                    profile_id,
+                    code,
+                    confirmed_at: None,
+                },
+            );
+            Response::html(
+                200,
+                format!(
+                    "<p>Request <span id=\"ref\">{reference}</span> received. \
+                     Check your email to confirm it.</p>"
+                ),
+            )
+        }
+        ("GET", "/confirm") => match state.requests.get_mut(param("ref")) {
+            Some(optout) if optout.code == param("code") => {
+                optout.confirmed_at.get_or_insert_with(Instant::now);
+                Response::html(
+                    200,
+                    format!("<p>Listing {} will be removed.</p>", optout.profile_id),
+                )
+            }
+            _ => Response::html(
+                404,
+                "<p class=\"error\">Invalid confirmation link</p>".into(),
+            ),
+        },
+        ("GET", "/status") => match state.requests.get(param("ref")) {
+            Some(optout) => {
+                let removed = optout
+                    .confirmed_at
+                    .is_some_and(|t| t.elapsed() >= Duration::from_secs(args.removal_secs));
+                let status = match optout.confirmed_at {
+                    None => "unconfirmed",
+                    Some(_) if removed => "removed",
+                    Some(_) => "processing",
+                };
+                let completed_at = removed.then(|| chrono::Utc::now().to_rfc3339());
+                Response::json(
+                    200,
+                    json!({ "ref": param("ref"), "status": status, "completed_at": completed_at }),
+                )
+            }
+            None => Response::json(404, json!({ "error": "unknown request" })),
+        },
+        _ => Response::html(404, "<h1>Not Found</h1>".into()),
+    }
+}